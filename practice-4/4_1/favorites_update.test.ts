@@ -1,9 +1,13 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program, web3 } from "@coral-xyz/anchor";
-import { Favorites } from "../target/types/favorites";
+import { FavoritesUpdate } from "../4_2/target/types/favorites_update";
 import { airdropIfRequired, getCustomErrorMessage } from "@solana-developers/helpers";
 import { expect, describe, test, it } from '@jest/globals';
-import { systemProgramErrors } from "./system-program-errors";
+import { systemProgramErrors } from "../4_2/tests/system-program-errors";
+
+// This program is built from favorites_update now, these are the tests the
+// original version had
+const TOKEN_PROGRAM_ID = anchor.utils.token.TOKEN_PROGRAM_ID;
 
 jest.setTimeout(300000);
 
@@ -13,7 +17,7 @@ describe("favorites", () => {
 
   it("Writes and update our favorites to the blockchain", async () => {
     const user = web3.Keypair.generate();
-    const program = anchor.workspace.Favorites as Program<Favorites>;
+    const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
 
     console.log(`User public key: ${user.publicKey}`);
 
@@ -36,8 +40,10 @@ describe("favorites", () => {
         .setFavorites(favoriteNumber, favoriteColor)
         .accounts({
           user: user.publicKey,
-          // Note that both `favorites` and `system_program` are added
-          // automatically.
+          // Rewards are minted to the user's account of the token program.
+          // Note that `favorites`, `system_program` and the other reward
+          // accounts are added automatically.
+          tokenProgram: TOKEN_PROGRAM_ID,
         })
         // Sign the transaction
         .signers([user])
@@ -75,6 +81,7 @@ describe("favorites", () => {
         .updateFavorites(updatedFavoriteNumber, updatedFavoriteColor)
         .accounts({
           user: user.publicKey,
+          authority: user.publicKey,
         })
         .signers([user])
         .rpc();
@@ -92,4 +99,119 @@ describe("favorites", () => {
     expect(updatedDataFromPda.color).toEqual(updatedFavoriteColor);
     expect(updatedDataFromPda.number.toNumber()).toEqual(updatedFavoriteNumber.toNumber());
   });
+
+  it("Closes the favorites, refunds the rent and allows creating them again", async () => {
+    const user = web3.Keypair.generate();
+    const recipient = web3.Keypair.generate();
+    const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+    const connection = anchor.getProvider().connection;
+
+    await airdropIfRequired(
+      connection,
+      user.publicKey,
+      0.5 * web3.LAMPORTS_PER_SOL,
+      1 * web3.LAMPORTS_PER_SOL
+    );
+
+    await program.methods
+      .setFavorites(new anchor.BN(7), "green")
+      .accounts({ user: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([user])
+      .rpc();
+
+    const [favoritesPda] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("favorites"), user.publicKey.toBuffer()],
+      program.programId
+    );
+
+    // The account holds exactly the rent exempt minimum for its size, which
+    // follows the length of the color
+    const account = await connection.getAccountInfo(favoritesPda);
+    const rent = await connection.getMinimumBalanceForRentExemption(account.data.length);
+    expect(account.lamports).toEqual(rent);
+
+    await program.methods
+      .closeFavorites()
+      .accountsPartial({
+        authority: user.publicKey,
+        favorites: favoritesPda,
+        user: user.publicKey,
+        recipient: recipient.publicKey,
+        history: null,
+      })
+      .signers([user])
+      .rpc();
+
+    expect(await connection.getAccountInfo(favoritesPda)).toBeNull();
+    expect(await connection.getBalance(recipient.publicKey)).toEqual(rent);
+
+    // The PDA is free again
+    await program.methods
+      .setFavorites(new anchor.BN(8), "blue")
+      .accounts({ user: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([user])
+      .rpc();
+    const recreated = await program.account.favorites.fetch(favoritesPda);
+    expect(recreated.number.toNumber()).toEqual(8);
+    expect(recreated.color).toEqual("blue");
+  });
+
+  it("Only accepts CSS color names and hex codes, stored lowercase", async () => {
+    const user = web3.Keypair.generate();
+    const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+
+    await airdropIfRequired(
+      anchor.getProvider().connection,
+      user.publicKey,
+      0.5 * web3.LAMPORTS_PER_SOL,
+      1 * web3.LAMPORTS_PER_SOL
+    );
+
+    await expect(
+      program.methods
+        .setFavorites(new anchor.BN(1), "blurple")
+        .accounts({ user: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+        .signers([user])
+        .rpc()
+    ).rejects.toThrow("InvalidColor");
+
+    await program.methods
+      .setFavorites(new anchor.BN(1), "#00FF7F")
+      .accounts({ user: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([user])
+      .rpc();
+
+    const [favoritesPda] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("favorites"), user.publicKey.toBuffer()],
+      program.programId
+    );
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.color).toEqual("#00ff7f");
+  });
+
+  it("Reads the favorites back with get_favorites", async () => {
+    const user = web3.Keypair.generate();
+    const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+
+    await airdropIfRequired(
+      anchor.getProvider().connection,
+      user.publicKey,
+      0.5 * web3.LAMPORTS_PER_SOL,
+      1 * web3.LAMPORTS_PER_SOL
+    );
+
+    await program.methods
+      .setFavorites(new anchor.BN(11), "olive")
+      .accounts({ user: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([user])
+      .rpc();
+
+    // `view` simulates the transaction and decodes the return data
+    const favorites = await program.methods
+      .getFavorites()
+      .accounts({ user: user.publicKey })
+      .view();
+    expect(favorites.number.toNumber()).toEqual(11);
+    expect(favorites.color).toEqual("olive");
+  });
 });
@@ -1,93 +1,5 @@
-use anchor_lang::prelude::*;
-
-declare_id!("DABBEEKjXnmL6Bfedxrf8YkEAPZYLNCKe4utAGTTWPxH");
-
-// Anchor programs always use
-pub const ANCHOR_DISCRIMINATOR_SIZE: usize = 8;
-
-// What we will put inside the Favorites PDA
-#[account]
-#[derive(InitSpace)]
-pub struct Favorites {
-    pub number: u64,
-
-    #[max_len(50)]
-    pub color: String,
-}
-
-// When people call the set_favorites instruction, they will need to provide the accounts that will
-// be modified. This keeps Solana fast!
-#[derive(Accounts)]
-pub struct SetFavorites<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-
-    #[account(
-        init,
-        payer = user,
-        space = ANCHOR_DISCRIMINATOR_SIZE + Favorites::INIT_SPACE,
-        seeds = [b"favorites", user.key().as_ref()],
-        bump,
-    )]
-    pub favorites: Account<'info, Favorites>,
-
-    pub system_program: Program<'info, System>,
-}
-
-// New instruction for updating the favorites
-#[derive(Accounts)]
-pub struct UpdateFavorites<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-
-    #[account(
-        mut,
-        seeds = [b"favorites", user.key().as_ref()],
-        bump,
-    )]
-    pub favorites: Account<'info, Favorites>,
-}
-
-// Our Solana program!
-#[program]
-pub mod favorites {
-    use super::*;
-
-    // Our instruction handler! It sets the user's favorite number and color
-    pub fn set_favorites(context: Context<SetFavorites>, number: u64, color: String) -> Result<()> {
-        let user_public_key = context.accounts.user.key();
-        msg!("Greetings from {}", context.program_id);
-        msg!(
-            "User {}'s favorite number is {} and favorite color is: {}",
-            user_public_key,
-            number,
-            color
-        );
-
-        context
-            .accounts
-            .favorites
-            .set_inner(Favorites { number, color });
-        Ok(())
-    }
-
-    // Instruction to update the user's favorite number and color
-    pub fn update_favorites(context: Context<UpdateFavorites>, number: u64, color: String) -> Result<()> {
-        let user_public_key = context.accounts.user.key();
-        msg!("Greetings from {}", context.program_id);
-        msg!(
-            "Updating User {}'s favorite number to {} and favorite color to: {}",
-            user_public_key,
-            number,
-            color
-        );
-
-        let favorites = &mut context.accounts.favorites;
-        favorites.number = number;
-        favorites.color = color;
-
-        Ok(())
-    }
-
-    // We can also add a get_favorites instruction to get the user's favorite number and color
-}
+// The original favorites program. It is built from `practice-4/4_2/programs/favorites_update`
+// now, which is deployed at the same program id and has every instruction this version had.
+// Accounts this version created still use the 70-byte layout, `FavoritesV0`, and are converted
+// to the current one by `upgrade_favorites`.
+pub use favorites_update::*;
@@ -0,0 +1,342 @@
+//! Instruction builders, addresses and account decoding for the favorites
+//! program. Nothing here talks to an RPC node, see the `favorites` binary for
+//! that.
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use favorites_update::{Favorites, FavoritesLayout, FavoritesLegacy};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+pub use favorites_update::ID as PROGRAM_ID;
+
+/// The favorites account of `user`, in any layout.
+pub fn favorites_address(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"favorites", user.as_ref()], &PROGRAM_ID).0
+}
+
+/// The account written by `set_favorites_legacy`.
+pub fn legacy_favorites_address(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"favorites-legacy", user.as_ref()], &PROGRAM_ID).0
+}
+
+pub fn history_address(favorites: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"history", favorites.as_ref()], &PROGRAM_ID).0
+}
+
+pub fn reward_mint_address() -> Pubkey {
+    Pubkey::find_program_address(&[favorites_update::REWARD_MINT_SEED], &PROGRAM_ID).0
+}
+
+pub fn reward_authority_address() -> Pubkey {
+    Pubkey::find_program_address(&[favorites_update::REWARD_AUTHORITY_SEED], &PROGRAM_ID).0
+}
+
+/// Records that `user` got the reward, it outlives their favorites.
+pub fn reward_claim_address(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[favorites_update::REWARD_CLAIM_SEED, user.as_ref()],
+        &PROGRAM_ID,
+    )
+    .0
+}
+
+/// Creates the favorites of `user`. With `token_program`, the owner of the
+/// reward mint, it also mints the reward into their associated token account
+/// unless they got it before. Without it no reward is minted.
+pub fn set_favorites(
+    user: &Pubkey,
+    number: u64,
+    color: &str,
+    token_program: Option<&Pubkey>,
+) -> Instruction {
+    let reward_mint = reward_mint_address();
+    let reward = |address: Pubkey| token_program.map(|_| address);
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: favorites_update::accounts::SetFavorites {
+            user: *user,
+            favorites: favorites_address(user),
+            system_program: anchor_lang::system_program::ID,
+            reward_authority: reward(reward_authority_address()),
+            reward_mint: reward(reward_mint),
+            user_reward_account: token_program.map(|token_program| {
+                get_associated_token_address_with_program_id(user, &reward_mint, token_program)
+            }),
+            associated_token_program: reward(anchor_spl::associated_token::ID),
+            token_program: token_program.copied(),
+            reward_claim: reward(reward_claim_address(user)),
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::SetFavorites {
+            number,
+            color: color.to_string(),
+        }
+        .data(),
+    }
+}
+
+pub fn set_favorites_legacy(user: &Pubkey, number: u64, color: &str) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: favorites_update::accounts::SetFavoritesLegacy {
+            user: *user,
+            favorites: legacy_favorites_address(user),
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::SetFavoritesLegacy {
+            number,
+            color: color.to_string(),
+        }
+        .data(),
+    }
+}
+
+/// The session PDA registering `session_key` for the favorites of `user`.
+pub fn session_address(user: &Pubkey, session_key: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"session",
+            favorites_address(user).as_ref(),
+            session_key.as_ref(),
+        ],
+        &PROGRAM_ID,
+    )
+    .0
+}
+
+/// Updates the favorites of `user`, signed by its authority or a delegate.
+/// Accounts with a history must pass `history_enabled` so the change is
+/// recorded there.
+pub fn update_favorites(
+    user: &Pubkey,
+    authority: &Pubkey,
+    number: u64,
+    color: &str,
+    history_enabled: bool,
+) -> Instruction {
+    update_instruction(user, authority, number, color, history_enabled, None)
+}
+
+/// Same as [`update_favorites`], signed by a session key registered with
+/// [`create_session`] instead of a wallet. The session key pays nothing, so
+/// the update fails if the account would have to grow.
+pub fn update_favorites_with_session(
+    user: &Pubkey,
+    session_key: &Pubkey,
+    number: u64,
+    color: &str,
+    history_enabled: bool,
+) -> Instruction {
+    let session = session_address(user, session_key);
+    update_instruction(
+        user,
+        session_key,
+        number,
+        color,
+        history_enabled,
+        Some(session),
+    )
+}
+
+fn update_instruction(
+    user: &Pubkey,
+    authority: &Pubkey,
+    number: u64,
+    color: &str,
+    history_enabled: bool,
+    session: Option<Pubkey>,
+) -> Instruction {
+    let favorites = favorites_address(user);
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: favorites_update::accounts::UpdateFavorites {
+            favorites,
+            user: *user,
+            authority: *authority,
+            system_program: anchor_lang::system_program::ID,
+            history: history_enabled.then(|| history_address(&favorites)),
+            session,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::UpdateFavorites {
+            number,
+            color: color.to_string(),
+        }
+        .data(),
+    }
+}
+
+/// Replaces every delegate of `user` with `new_delegate`, or removes them all
+/// when it is `None`.
+pub fn set_authority(
+    user: &Pubkey,
+    authority: &Pubkey,
+    new_delegate: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: favorites_update::accounts::SetAuthority {
+            authority: *authority,
+            favorites: favorites_address(user),
+            user: *user,
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::SetAuthority { new_delegate }.data(),
+    }
+}
+
+/// Lets `session_key` update the favorites of `user` until `expires_at` (a
+/// Unix timestamp), at most `max_updates` times. Signed by the authority.
+pub fn create_session(
+    user: &Pubkey,
+    authority: &Pubkey,
+    session_key: &Pubkey,
+    permissions: u8,
+    expires_at: i64,
+    max_updates: u16,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: favorites_update::accounts::CreateSession {
+            authority: *authority,
+            favorites: favorites_address(user),
+            user: *user,
+            session: session_address(user, session_key),
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::CreateSession {
+            session_key: *session_key,
+            permissions,
+            expires_at,
+            max_updates,
+        }
+        .data(),
+    }
+}
+
+/// Ends a session early, signed by the authority that created it or by the
+/// session key. The rent goes back to `creator`.
+pub fn close_session(
+    user: &Pubkey,
+    session_key: &Pubkey,
+    signer: &Pubkey,
+    creator: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: favorites_update::accounts::CloseSession {
+            signer: *signer,
+            session: session_address(user, session_key),
+            creator: *creator,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::CloseSession {}.data(),
+    }
+}
+
+/// Rewrites an account of an older program version in the current layout.
+pub fn upgrade_favorites(user: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: favorites_update::accounts::UpgradeFavorites {
+            user: *user,
+            favorites: favorites_address(user),
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::UpgradeFavorites {}.data(),
+    }
+}
+
+/// Decodes the favorites account of `user` together with the layout it was
+/// written in. Older layouts need `upgrade_favorites` before any update.
+pub fn decode_favorites(
+    data: &[u8],
+    user: &Pubkey,
+) -> anchor_lang::Result<(Favorites, FavoritesLayout)> {
+    let layout = Favorites::layout_of(data)?;
+    Ok((Favorites::try_from_any_layout(data, user)?, layout))
+}
+
+pub fn decode_legacy_favorites(data: &[u8]) -> anchor_lang::Result<FavoritesLegacy> {
+    FavoritesLegacy::try_deserialize(&mut &data[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{AnchorSerialize, Discriminator};
+
+    #[test]
+    fn builds_update_with_and_without_history() {
+        let user = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let favorites = favorites_address(&user);
+
+        let instruction = update_favorites(&user, &delegate, 1, "red", false);
+        let keys: Vec<_> = instruction
+            .accounts
+            .iter()
+            .map(|meta| meta.pubkey)
+            .collect();
+        // Anchor passes the program id for a missing optional account
+        assert_eq!(
+            keys,
+            [
+                favorites,
+                user,
+                delegate,
+                anchor_lang::system_program::ID,
+                PROGRAM_ID,
+                PROGRAM_ID
+            ]
+        );
+        assert!(instruction.accounts[2].is_signer);
+        assert!(instruction
+            .data
+            .starts_with(favorites_update::instruction::UpdateFavorites::DISCRIMINATOR));
+
+        let instruction = update_favorites(&user, &delegate, 1, "red", true);
+        assert_eq!(instruction.accounts[4].pubkey, history_address(&favorites));
+        assert!(instruction.accounts[4].is_writable);
+
+        let instruction = update_favorites_with_session(&user, &delegate, 1, "red", false);
+        assert_eq!(
+            instruction.accounts[5].pubkey,
+            session_address(&user, &delegate)
+        );
+        assert!(instruction.accounts[2].is_signer);
+    }
+
+    #[test]
+    fn derives_distinct_addresses_per_seed() {
+        let user = Pubkey::new_unique();
+        assert_ne!(favorites_address(&user), legacy_favorites_address(&user));
+        assert_eq!(
+            set_favorites_legacy(&user, 1, "red").accounts[1].pubkey,
+            legacy_favorites_address(&user)
+        );
+    }
+
+    #[test]
+    fn decodes_accounts_in_any_layout() {
+        let user = Pubkey::new_unique();
+        let mut data = Favorites::DISCRIMINATOR.to_vec();
+        favorites_update::FavoritesV0 {
+            number: 3,
+            color: "red".to_string(),
+        }
+        .serialize(&mut data)
+        .unwrap();
+        data.resize(Favorites::V0_SPACE, 0);
+
+        let (favorites, layout) = decode_favorites(&data, &user).unwrap();
+        assert_eq!(layout, FavoritesLayout::V0);
+        assert_eq!(favorites.number, 3);
+        assert_eq!(favorites.authority, user);
+        assert!(decode_favorites(&data[..4], &user).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+//! Command line client for the favorites program.
+//!
+//! ```text
+//! favorites --url http://127.0.0.1:8899 set 7 teal
+//! favorites get
+//! favorites get --user <PUBKEY>
+//! favorites update 8 "#00ff7f" --user <OWNER>
+//! favorites delegate <PUBKEY>
+//! favorites delegate --remove
+//! favorites session <PUBKEY> --expires-in 3600 --max-updates 20
+//! favorites session <PUBKEY> --revoke
+//! ```
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use favorites_client::{
+    close_session, create_session, decode_favorites, favorites_address, reward_mint_address,
+    set_authority, set_favorites, update_favorites, upgrade_favorites, PROGRAM_ID,
+};
+use favorites_update::{
+    Favorites, FavoritesLayout, PERMISSION_UPDATE_COLOR, PERMISSION_UPDATE_NUMBER,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    account::from_account,
+    clock::Clock,
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+    transaction::Transaction,
+};
+
+#[derive(Parser)]
+#[command(name = "favorites", about = "Read and change favorites")]
+struct Cli {
+    /// RPC endpoint, defaults to a local test validator.
+    #[arg(
+        long,
+        global = true,
+        env = "FAVORITES_RPC_URL",
+        default_value = "http://127.0.0.1:8899"
+    )]
+    url: String,
+
+    /// Keypair paying for and signing transactions, the Solana CLI's
+    /// default keypair if not given.
+    #[arg(long, global = true, env = "FAVORITES_KEYPAIR")]
+    keypair: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the favorites of a user, yours by default.
+    Get {
+        #[arg(long)]
+        user: Option<Pubkey>,
+    },
+    /// Create your favorites account, the first one also gets the reward token.
+    Set { number: u64, color: String },
+    /// Change favorites you own or are a delegate of.
+    Update {
+        number: u64,
+        color: String,
+        /// Owner of the favorites, yourself by default.
+        #[arg(long)]
+        user: Option<Pubkey>,
+    },
+    /// Make a key the only delegate of your favorites.
+    Delegate {
+        #[arg(required_unless_present = "remove")]
+        delegate: Option<Pubkey>,
+        /// Remove all delegates instead.
+        #[arg(long, conflicts_with = "delegate")]
+        remove: bool,
+    },
+    /// Move your favorites written by an older program version to the
+    /// current layout.
+    Upgrade,
+    /// Let a session key update your number and color without your wallet
+    /// signing every change.
+    Session {
+        key: Pubkey,
+        /// Seconds until the session expires, at most a week.
+        #[arg(long, default_value_t = 3600)]
+        expires_in: i64,
+        /// How many updates the session key may sign.
+        #[arg(long, default_value_t = 20)]
+        max_updates: u16,
+        /// Close the session instead and get its rent back.
+        #[arg(long)]
+        revoke: bool,
+    },
+}
+
+struct FavoritesClient {
+    rpc: RpcClient,
+    payer: Keypair,
+}
+
+impl FavoritesClient {
+    /// Reads the favorites of `user`, whatever layout they were written in.
+    fn fetch(&self, user: &Pubkey) -> Result<(Favorites, FavoritesLayout)> {
+        let address = favorites_address(user);
+        let account = self
+            .rpc
+            .get_account(&address)
+            .with_context(|| format!("{user} has no favorites at {address}"))?;
+        if account.owner != PROGRAM_ID {
+            bail!("{address} is not owned by the favorites program");
+        }
+        Ok(decode_favorites(&account.data, user)?)
+    }
+
+    /// The token program owning the reward mint, `None` until
+    /// `init_reward_mint` created it.
+    fn reward_token_program(&self) -> Result<Option<Pubkey>> {
+        let mint = reward_mint_address();
+        let account = self
+            .rpc
+            .get_account_with_commitment(&mint, self.rpc.commitment())?
+            .value;
+        Ok(account.map(|account| account.owner))
+    }
+
+    /// The cluster's clock, which the program checks session expiries
+    /// against.
+    fn clock(&self) -> Result<Clock> {
+        let account = self.rpc.get_account(&solana_sdk::sysvar::clock::ID)?;
+        from_account(&account).context("failed to decode the clock sysvar")
+    }
+
+    /// Sends `instructions` with the payer as their only signer.
+    fn send(&self, instructions: &[Instruction]) -> Result<()> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &[&self.payer],
+            blockhash,
+        );
+        let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+        println!("Signature: {signature}");
+        Ok(())
+    }
+}
+
+/// Where `solana-keygen new` writes its keypair by default.
+fn solana_cli_keypair() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("HOME is not set, pass --keypair")?;
+    Ok(PathBuf::from(home).join(".config/solana/id.json"))
+}
+
+fn print_favorites(user: &Pubkey, favorites: &Favorites, layout: FavoritesLayout) {
+    println!("Favorites {}", favorites_address(user));
+    println!("Number    {}", favorites.number);
+    println!("Color     {}", favorites.color);
+    println!("Authority {}", favorites.authority);
+    for delegate in &favorites.delegates {
+        println!(
+            "Delegate  {} (permissions {:#06b})",
+            delegate.key, delegate.permissions
+        );
+    }
+    for entry in &favorites.entries {
+        println!("Entry     {entry:?}");
+    }
+    if layout != FavoritesLayout::CURRENT {
+        println!("Layout    {layout:?}, run `favorites upgrade` before changing it");
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let keypair_path = match cli.keypair {
+        Some(path) => path,
+        None => solana_cli_keypair()?,
+    };
+    let payer = read_keypair_file(&keypair_path)
+        .map_err(|err| anyhow!("failed to read keypair {}: {err}", keypair_path.display()))?;
+    let client = FavoritesClient {
+        rpc: RpcClient::new_with_commitment(cli.url, CommitmentConfig::confirmed()),
+        payer,
+    };
+    let me = client.payer.pubkey();
+
+    match cli.command {
+        Command::Get { user } => {
+            let user = user.unwrap_or(me);
+            let (favorites, layout) = client.fetch(&user)?;
+            print_favorites(&user, &favorites, layout);
+        }
+        Command::Set { number, color } => {
+            let token_program = client.reward_token_program()?;
+            if token_program.is_none() {
+                println!("The reward mint doesn't exist yet, no reward is minted");
+            }
+            client.send(&[set_favorites(&me, number, &color, token_program.as_ref())])?;
+        }
+        Command::Update {
+            number,
+            color,
+            user,
+        } => {
+            let user = user.unwrap_or(me);
+            let (favorites, _) = client.fetch(&user)?;
+            client.send(&[update_favorites(
+                &user,
+                &me,
+                number,
+                &color,
+                favorites.history_enabled,
+            )])?;
+        }
+        Command::Delegate { delegate, remove } => {
+            let new_delegate = if remove { None } else { delegate };
+            client.send(&[set_authority(&me, &me, new_delegate)])?;
+        }
+        Command::Upgrade => {
+            client.send(&[upgrade_favorites(&me)])?;
+        }
+        Command::Session {
+            key,
+            expires_in,
+            max_updates,
+            revoke,
+        } => {
+            if revoke {
+                client.send(&[close_session(&me, &key, &me, &me)])?;
+            } else {
+                let now = client.clock()?.unix_timestamp;
+                client.send(&[create_session(
+                    &me,
+                    &me,
+                    &key,
+                    PERMISSION_UPDATE_NUMBER | PERMISSION_UPDATE_COLOR,
+                    now + expires_in,
+                    max_updates,
+                )])?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+// CSS named colors, sorted so they can be binary searched
+pub const CSS_COLOR_NAMES: [&str; 148] = [
+    "aliceblue",
+    "antiquewhite",
+    "aqua",
+    "aquamarine",
+    "azure",
+    "beige",
+    "bisque",
+    "black",
+    "blanchedalmond",
+    "blue",
+    "blueviolet",
+    "brown",
+    "burlywood",
+    "cadetblue",
+    "chartreuse",
+    "chocolate",
+    "coral",
+    "cornflowerblue",
+    "cornsilk",
+    "crimson",
+    "cyan",
+    "darkblue",
+    "darkcyan",
+    "darkgoldenrod",
+    "darkgray",
+    "darkgreen",
+    "darkgrey",
+    "darkkhaki",
+    "darkmagenta",
+    "darkolivegreen",
+    "darkorange",
+    "darkorchid",
+    "darkred",
+    "darksalmon",
+    "darkseagreen",
+    "darkslateblue",
+    "darkslategray",
+    "darkslategrey",
+    "darkturquoise",
+    "darkviolet",
+    "deeppink",
+    "deepskyblue",
+    "dimgray",
+    "dimgrey",
+    "dodgerblue",
+    "firebrick",
+    "floralwhite",
+    "forestgreen",
+    "fuchsia",
+    "gainsboro",
+    "ghostwhite",
+    "gold",
+    "goldenrod",
+    "gray",
+    "green",
+    "greenyellow",
+    "grey",
+    "honeydew",
+    "hotpink",
+    "indianred",
+    "indigo",
+    "ivory",
+    "khaki",
+    "lavender",
+    "lavenderblush",
+    "lawngreen",
+    "lemonchiffon",
+    "lightblue",
+    "lightcoral",
+    "lightcyan",
+    "lightgoldenrodyellow",
+    "lightgray",
+    "lightgreen",
+    "lightgrey",
+    "lightpink",
+    "lightsalmon",
+    "lightseagreen",
+    "lightskyblue",
+    "lightslategray",
+    "lightslategrey",
+    "lightsteelblue",
+    "lightyellow",
+    "lime",
+    "limegreen",
+    "linen",
+    "magenta",
+    "maroon",
+    "mediumaquamarine",
+    "mediumblue",
+    "mediumorchid",
+    "mediumpurple",
+    "mediumseagreen",
+    "mediumslateblue",
+    "mediumspringgreen",
+    "mediumturquoise",
+    "mediumvioletred",
+    "midnightblue",
+    "mintcream",
+    "mistyrose",
+    "moccasin",
+    "navajowhite",
+    "navy",
+    "oldlace",
+    "olive",
+    "olivedrab",
+    "orange",
+    "orangered",
+    "orchid",
+    "palegoldenrod",
+    "palegreen",
+    "paleturquoise",
+    "palevioletred",
+    "papayawhip",
+    "peachpuff",
+    "peru",
+    "pink",
+    "plum",
+    "powderblue",
+    "purple",
+    "rebeccapurple",
+    "red",
+    "rosybrown",
+    "royalblue",
+    "saddlebrown",
+    "salmon",
+    "sandybrown",
+    "seagreen",
+    "seashell",
+    "sienna",
+    "silver",
+    "skyblue",
+    "slateblue",
+    "slategray",
+    "slategrey",
+    "snow",
+    "springgreen",
+    "steelblue",
+    "tan",
+    "teal",
+    "thistle",
+    "tomato",
+    "turquoise",
+    "violet",
+    "wheat",
+    "white",
+    "whitesmoke",
+    "yellow",
+    "yellowgreen",
+];
+
+// A color that passed validation: a CSS color name or a `#rrggbb` hex code,
+// always stored lowercase so "Red" and "red" are the same favorite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Color(String);
+
+impl Color {
+    pub fn parse(input: &str) -> Result<Self> {
+        let color = input.to_ascii_lowercase();
+        let valid = match color.strip_prefix('#') {
+            Some(hex) => hex.len() == 6 && hex.bytes().all(|byte| byte.is_ascii_hexdigit()),
+            None => CSS_COLOR_NAMES.binary_search(&color.as_str()).is_ok(),
+        };
+        require!(valid, ErrorCode::InvalidColor);
+        Ok(Color(color))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(input: &str) -> Option<String> {
+        Color::parse(input).ok().map(Color::into_string)
+    }
+
+    #[test]
+    fn names_are_sorted() {
+        assert!(CSS_COLOR_NAMES.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn accepts_named_colors_in_any_case() {
+        assert_eq!(parsed("red").as_deref(), Some("red"));
+        assert_eq!(parsed("RebeccaPurple").as_deref(), Some("rebeccapurple"));
+        assert_eq!(
+            parsed("LIGHTGOLDENRODYELLOW").as_deref(),
+            Some("lightgoldenrodyellow")
+        );
+    }
+
+    #[test]
+    fn accepts_hex_colors_in_any_case() {
+        assert_eq!(parsed("#1a2B3c").as_deref(), Some("#1a2b3c"));
+        assert_eq!(parsed("#FFFFFF").as_deref(), Some("#ffffff"));
+    }
+
+    #[test]
+    fn rejects_everything_else() {
+        for input in [
+            "", "blurple", " red", "red ", "#fff", "#1234567", "#12345g", "1a2b3c", "##12345",
+        ] {
+            assert_eq!(parsed(input), None, "{input:?} should be rejected");
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use anchor_lang::prelude::*;
+
+use crate::{Entry, FavoritesLayout, Role};
+
+// Also emitted by `set_favorites_legacy`, with the legacy account as
+// `favorites`
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FavoritesSet {
+    pub favorites: Pubkey,
+    pub user: Pubkey,
+    pub number: u64,
+    pub color: String,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FavoritesUpdated {
+    pub favorites: Pubkey,
+    // The authority or the delegate that signed the update
+    pub actor: Pubkey,
+    pub number: u64,
+    pub color: String,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Migrated {
+    pub favorites: Pubkey,
+    pub legacy_favorites: Pubkey,
+    pub user: Pubkey,
+    pub number: u64,
+    pub color: String,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FavoritesUpgraded {
+    pub favorites: Pubkey,
+    pub user: Pubkey,
+    pub previous_layout: FavoritesLayout,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RewardMinted {
+    pub favorites: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DelegateChanged {
+    pub favorites: Pubkey,
+    pub delegate: Pubkey,
+    // Zero when the delegate was removed
+    pub permissions: u8,
+    pub expires_at: Option<i64>,
+    pub changed_by: Pubkey,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionCreated {
+    pub favorites: Pubkey,
+    pub session_key: Pubkey,
+    pub permissions: u8,
+    pub expires_at: i64,
+    pub max_updates: u16,
+}
+
+// Revoked by the authority or given up by the session key
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionClosed {
+    pub favorites: Pubkey,
+    pub session_key: Pubkey,
+    pub closed_by: Pubkey,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorityProposed {
+    pub favorites: Pubkey,
+    // `None` when a pending transfer was cancelled
+    pub pending_authority: Option<Pubkey>,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorityTransferred {
+    pub favorites: Pubkey,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LimitsChanged {
+    pub favorites: Pubkey,
+    pub cooldown_slots: u64,
+    pub delegate_daily_quota: u16,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryChanged {
+    pub favorites: Pubkey,
+    pub actor: Pubkey,
+    pub index: u8,
+    // `None` when the entry was removed
+    pub entry: Option<Entry>,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEnabled {
+    pub favorites: Pubkey,
+    pub history: Pubkey,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FavoritesClosed {
+    pub favorites: Pubkey,
+    pub recipient: Pubkey,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupCreated {
+    pub group: Pubkey,
+    pub group_id: u64,
+    pub creator: Pubkey,
+    pub number: u64,
+    pub color: String,
+    pub threshold: u8,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupMemberChanged {
+    pub group: Pubkey,
+    pub member: Pubkey,
+    // `None` when the member was removed
+    pub role: Option<Role>,
+    pub changed_by: Pubkey,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupThresholdChanged {
+    pub group: Pubkey,
+    pub threshold: u8,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupChangeProposed {
+    pub group: Pubkey,
+    pub pending_change: Pubkey,
+    pub proposer: Pubkey,
+    pub number: u64,
+    pub color: String,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupChangeApproved {
+    pub group: Pubkey,
+    pub pending_change: Pubkey,
+    pub approver: Pubkey,
+    pub approvals: u8,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupFavoritesUpdated {
+    pub group: Pubkey,
+    pub actor: Pubkey,
+    pub number: u64,
+    pub color: String,
+    // The applied change, `None` when an admin updated directly
+    pub pending_change: Option<Pubkey>,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupChangeCancelled {
+    pub group: Pubkey,
+    pub pending_change: Pubkey,
+    pub cancelled_by: Pubkey,
+}
+
+#[cfg(not(target_os = "solana"))]
+pub use decode::*;
+
+// Turns transaction logs back into events, for indexers
+#[cfg(not(target_os = "solana"))]
+mod decode {
+    use super::*;
+    use anchor_lang::Discriminator;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum FavoritesEvent {
+        FavoritesSet(FavoritesSet),
+        FavoritesUpdated(FavoritesUpdated),
+        Migrated(Migrated),
+        FavoritesUpgraded(FavoritesUpgraded),
+        RewardMinted(RewardMinted),
+        DelegateChanged(DelegateChanged),
+        SessionCreated(SessionCreated),
+        SessionClosed(SessionClosed),
+        AuthorityProposed(AuthorityProposed),
+        AuthorityTransferred(AuthorityTransferred),
+        LimitsChanged(LimitsChanged),
+        EntryChanged(EntryChanged),
+        HistoryEnabled(HistoryEnabled),
+        FavoritesClosed(FavoritesClosed),
+        GroupCreated(GroupCreated),
+        GroupMemberChanged(GroupMemberChanged),
+        GroupThresholdChanged(GroupThresholdChanged),
+        GroupChangeProposed(GroupChangeProposed),
+        GroupChangeApproved(GroupChangeApproved),
+        GroupFavoritesUpdated(GroupFavoritesUpdated),
+        GroupChangeCancelled(GroupChangeCancelled),
+    }
+
+    fn parse<E: Discriminator + AnchorDeserialize>(data: &[u8]) -> Option<E> {
+        let payload = data.strip_prefix(E::DISCRIMINATOR)?;
+        E::try_from_slice(payload).ok()
+    }
+
+    impl FavoritesEvent {
+        // Decodes the bytes of one `Program data:` line: the event
+        // discriminator followed by the borsh payload.
+        pub fn decode(data: &[u8]) -> Option<Self> {
+            parse(data)
+                .map(Self::FavoritesSet)
+                .or_else(|| parse(data).map(Self::FavoritesUpdated))
+                .or_else(|| parse(data).map(Self::Migrated))
+                .or_else(|| parse(data).map(Self::FavoritesUpgraded))
+                .or_else(|| parse(data).map(Self::RewardMinted))
+                .or_else(|| parse(data).map(Self::DelegateChanged))
+                .or_else(|| parse(data).map(Self::SessionCreated))
+                .or_else(|| parse(data).map(Self::SessionClosed))
+                .or_else(|| parse(data).map(Self::AuthorityProposed))
+                .or_else(|| parse(data).map(Self::AuthorityTransferred))
+                .or_else(|| parse(data).map(Self::LimitsChanged))
+                .or_else(|| parse(data).map(Self::EntryChanged))
+                .or_else(|| parse(data).map(Self::HistoryEnabled))
+                .or_else(|| parse(data).map(Self::FavoritesClosed))
+                .or_else(|| parse(data).map(Self::GroupCreated))
+                .or_else(|| parse(data).map(Self::GroupMemberChanged))
+                .or_else(|| parse(data).map(Self::GroupThresholdChanged))
+                .or_else(|| parse(data).map(Self::GroupChangeProposed))
+                .or_else(|| parse(data).map(Self::GroupChangeApproved))
+                .or_else(|| parse(data).map(Self::GroupFavoritesUpdated))
+                .or_else(|| parse(data).map(Self::GroupChangeCancelled))
+        }
+    }
+
+    // Collects the events of this program from the log messages of a
+    // transaction, in order. Data logged by other programs, including ones
+    // invoked from ours, is skipped, as are lines that don't decode.
+    pub fn decode_logs<S: AsRef<str>>(logs: &[S]) -> Vec<FavoritesEvent> {
+        let program_id = crate::ID.to_string();
+        let mut invoked = Vec::new();
+        let mut events = Vec::new();
+
+        for line in logs {
+            let line = line.as_ref();
+            if let Some(data) = line.strip_prefix("Program data: ") {
+                if invoked.last() != Some(&program_id.as_str()) {
+                    continue;
+                }
+                let event = STANDARD
+                    .decode(data)
+                    .ok()
+                    .and_then(|bytes| FavoritesEvent::decode(&bytes));
+                events.extend(event);
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            if words.next() != Some("Program") {
+                continue;
+            }
+            match (words.next(), words.next()) {
+                (Some(program), Some("invoke")) => invoked.push(program),
+                (Some(_), Some("success" | "failed:")) => {
+                    invoked.pop();
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Event;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    fn data_line(event: &impl Event) -> String {
+        format!("Program data: {}", STANDARD.encode(event.data()))
+    }
+
+    fn updated() -> FavoritesUpdated {
+        FavoritesUpdated {
+            favorites: Pubkey::new_unique(),
+            actor: Pubkey::new_unique(),
+            number: 7,
+            color: "teal".to_string(),
+        }
+    }
+
+    #[test]
+    fn decodes_events_by_discriminator() {
+        let favorites = Pubkey::new_unique();
+        let events = [
+            FavoritesEvent::FavoritesUpdated(updated()),
+            FavoritesEvent::EntryChanged(EntryChanged {
+                favorites,
+                actor: Pubkey::new_unique(),
+                index: 2,
+                entry: Some(Entry::Tag("rust".to_string())),
+            }),
+            FavoritesEvent::AuthorityProposed(AuthorityProposed {
+                favorites,
+                pending_authority: None,
+            }),
+        ];
+        for event in events {
+            let data = match &event {
+                FavoritesEvent::FavoritesUpdated(e) => e.data(),
+                FavoritesEvent::EntryChanged(e) => e.data(),
+                FavoritesEvent::AuthorityProposed(e) => e.data(),
+                _ => unreachable!(),
+            };
+            assert_eq!(FavoritesEvent::decode(&data), Some(event));
+        }
+        assert_eq!(FavoritesEvent::decode(&[0; 8]), None);
+    }
+
+    #[test]
+    fn only_decodes_data_logged_by_this_program() {
+        let program = crate::ID.to_string();
+        let other = Pubkey::new_unique().to_string();
+        let ours = updated();
+        let theirs = updated();
+        let logs = vec![
+            format!("Program {other} invoke [1]"),
+            data_line(&theirs),
+            format!("Program {program} invoke [2]"),
+            "Program log: Instruction: UpdateFavorites".to_string(),
+            data_line(&ours),
+            format!("Program {program} consumed 5000 of 200000 compute units"),
+            format!("Program {program} success"),
+            data_line(&theirs),
+            "Program data: not base64!".to_string(),
+            format!("Program {other} success"),
+        ];
+
+        assert_eq!(
+            decode_logs(&logs),
+            vec![FavoritesEvent::FavoritesUpdated(ours)]
+        );
+    }
+}
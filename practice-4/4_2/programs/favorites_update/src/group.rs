@@ -0,0 +1,281 @@
+use anchor_lang::prelude::*;
+
+use crate::{ErrorCode, ANCHOR_DISCRIMINATOR_SIZE, MAX_COLOR_LEN};
+
+pub const MAX_MEMBERS: usize = 8;
+
+// Admins act like the authority of a personal favorites account: they update
+// directly and manage the members. Other members only propose and approve.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum Role {
+    Admin,
+    Member,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct Member {
+    pub key: Pubkey,
+    pub role: Role,
+}
+
+// Favorites shared by a group, seeded by the group id instead of a user.
+// Always allocated at `INIT_SPACE`, the member list is small.
+#[account]
+#[derive(InitSpace)]
+pub struct GroupFavorites {
+    pub group_id: u64,
+    pub number: u64,
+    #[max_len(MAX_COLOR_LEN)]
+    pub color: String,
+    #[max_len(MAX_MEMBERS)]
+    pub members: Vec<Member>,
+    // Approvals a pending change needs before any member can apply it
+    pub threshold: u8,
+    // Id of the next pending change, part of its seeds
+    pub next_change_id: u64,
+}
+
+impl GroupFavorites {
+    pub fn role_of(&self, key: &Pubkey) -> Option<Role> {
+        self.members
+            .iter()
+            .find(|member| member.key == *key)
+            .map(|member| member.role)
+    }
+
+    pub fn is_admin(&self, key: &Pubkey) -> bool {
+        self.role_of(key) == Some(Role::Admin)
+    }
+
+    // Only approvals of current members count, so removing a member also
+    // withdraws their approvals.
+    pub fn approvals(&self, change: &PendingChange) -> usize {
+        change
+            .approvals
+            .iter()
+            .filter(|key| self.role_of(key).is_some())
+            .count()
+    }
+
+    // Checks what every member change has to keep true: no duplicates, at
+    // least one admin, and a threshold the members can still reach.
+    pub fn validate(&self) -> Result<()> {
+        require!(self.members.len() <= MAX_MEMBERS, ErrorCode::TooManyMembers);
+        for (index, member) in self.members.iter().enumerate() {
+            require!(
+                !self.members[..index].iter().any(|m| m.key == member.key),
+                ErrorCode::DuplicateMember
+            );
+        }
+        require!(
+            self.members.iter().any(|m| m.role == Role::Admin),
+            ErrorCode::LastAdmin
+        );
+        require!(
+            self.threshold != 0 && usize::from(self.threshold) <= self.members.len(),
+            ErrorCode::InvalidThreshold
+        );
+        Ok(())
+    }
+}
+
+// A proposed update waiting for approvals. The proposer pays for it and gets
+// the rent back once it's applied or cancelled.
+#[account]
+#[derive(InitSpace)]
+pub struct PendingChange {
+    pub group: Pubkey,
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub number: u64,
+    #[max_len(MAX_COLOR_LEN)]
+    pub color: String,
+    #[max_len(MAX_MEMBERS)]
+    pub approvals: Vec<Pubkey>,
+}
+
+#[derive(Accounts)]
+#[instruction(group_id: u64)]
+pub struct CreateGroup<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(
+        init,
+        payer = creator,
+        space = ANCHOR_DISCRIMINATOR_SIZE + GroupFavorites::INIT_SPACE,
+        seeds = [b"group", group_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub group: Account<'info, GroupFavorites>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageMembers<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"group", group.group_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = group.is_admin(&admin.key()) @ ErrorCode::Unauthorized,
+    )]
+    pub group: Account<'info, GroupFavorites>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeGroupChange<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"group", group.group_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = group.role_of(&proposer.key()).is_some() @ ErrorCode::NotAMember,
+    )]
+    pub group: Account<'info, GroupFavorites>,
+    #[account(
+        init,
+        payer = proposer,
+        space = ANCHOR_DISCRIMINATOR_SIZE + PendingChange::INIT_SPACE,
+        seeds = [
+            b"pending-change",
+            group.key().as_ref(),
+            group.next_change_id.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveGroupChange<'info> {
+    pub approver: Signer<'info>,
+    #[account(
+        seeds = [b"group", group.group_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = group.role_of(&approver.key()).is_some() @ ErrorCode::NotAMember,
+    )]
+    pub group: Account<'info, GroupFavorites>,
+    #[account(
+        mut,
+        has_one = group,
+        seeds = [
+            b"pending-change",
+            group.key().as_ref(),
+            pending_change.id.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGroupFavorites<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"group", group.group_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = group.role_of(&signer.key()).is_some() @ ErrorCode::NotAMember,
+    )]
+    pub group: Account<'info, GroupFavorites>,
+    // Required unless the signer is an admin
+    #[account(
+        mut,
+        has_one = group,
+        seeds = [
+            b"pending-change",
+            group.key().as_ref(),
+            pending_change.id.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_change: Option<Account<'info, PendingChange>>,
+    // Gets the rent of the applied change back
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelGroupChange<'info> {
+    // The proposer or an admin
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"group", group.group_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = signer.key() == pending_change.proposer
+            || group.is_admin(&signer.key()) @ ErrorCode::Unauthorized,
+    )]
+    pub group: Account<'info, GroupFavorites>,
+    #[account(
+        mut,
+        close = proposer,
+        has_one = group,
+        has_one = proposer,
+        seeds = [
+            b"pending-change",
+            group.key().as_ref(),
+            pending_change.id.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+    #[account(mut)]
+    pub proposer: SystemAccount<'info>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(members: &[(Pubkey, Role)], threshold: u8) -> GroupFavorites {
+        GroupFavorites {
+            group_id: 1,
+            number: 0,
+            color: "red".to_string(),
+            members: members
+                .iter()
+                .map(|&(key, role)| Member { key, role })
+                .collect(),
+            threshold,
+            next_change_id: 0,
+        }
+    }
+
+    #[test]
+    fn validates_members_and_threshold() {
+        let admin = Pubkey::new_unique();
+        let member = Pubkey::new_unique();
+
+        assert!(group(&[(admin, Role::Admin), (member, Role::Member)], 2)
+            .validate()
+            .is_ok());
+        assert!(group(&[(admin, Role::Admin)], 0).validate().is_err());
+        assert!(group(&[(admin, Role::Admin)], 2).validate().is_err());
+        assert!(group(&[(member, Role::Member)], 1).validate().is_err());
+        assert!(group(&[(admin, Role::Admin), (admin, Role::Member)], 1)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn only_counts_approvals_of_current_members() {
+        let admin = Pubkey::new_unique();
+        let member = Pubkey::new_unique();
+        let former = Pubkey::new_unique();
+        let group = group(&[(admin, Role::Admin), (member, Role::Member)], 2);
+        let mut change = PendingChange {
+            group: Pubkey::new_unique(),
+            id: 0,
+            proposer: member,
+            number: 1,
+            color: "blue".to_string(),
+            approvals: vec![member, former],
+        };
+
+        assert_eq!(group.approvals(&change), 1);
+        change.approvals.push(admin);
+        assert_eq!(group.approvals(&change), 2);
+    }
+}
@@ -0,0 +1,216 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+
+use crate::{
+    Delegate, ErrorCode, Favorites, ANCHOR_DISCRIMINATOR_SIZE, PERMISSION_UPDATE_COLOR,
+    PERMISSION_UPDATE_NUMBER,
+};
+
+// Layout written by the original favorites program (`practice-4/4_1`), which
+// used the same program id, seed and account name. The owner is only known
+// from the seed.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct FavoritesV0 {
+    pub number: u64,
+    #[max_len(50)]
+    pub color: String,
+}
+
+// Layout from before delegates had permissions, with a single delegate
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct FavoritesV1 {
+    pub number: u64,
+    #[max_len(50)]
+    pub color: String,
+    pub authority: Pubkey,
+    pub delegate: Option<Pubkey>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FavoritesLayout {
+    V0,
+    V1,
+    V2,
+}
+
+impl FavoritesLayout {
+    // The layout of `Favorites`, everything else needs `upgrade_favorites`
+    pub const CURRENT: FavoritesLayout = FavoritesLayout::V2;
+}
+
+impl Favorites {
+    // Both old layouts were allocated at their `INIT_SPACE`
+    pub const V0_SPACE: usize = ANCHOR_DISCRIMINATOR_SIZE + FavoritesV0::INIT_SPACE;
+    pub const V1_SPACE: usize = ANCHOR_DISCRIMINATOR_SIZE + FavoritesV1::INIT_SPACE;
+
+    // All layouts share the discriminator. Current accounts are sized to
+    // their content, so they are recognised by decoding to exactly their
+    // length; a 135 byte current account would otherwise look like V1.
+    pub fn layout_of(data: &[u8]) -> Result<FavoritesLayout> {
+        require!(
+            data.len() >= ANCHOR_DISCRIMINATOR_SIZE
+                && data[..ANCHOR_DISCRIMINATOR_SIZE] == *Favorites::DISCRIMINATOR,
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        let mut rest = &data[ANCHOR_DISCRIMINATOR_SIZE..];
+        if Favorites::deserialize(&mut rest).is_ok() && rest.is_empty() {
+            return Ok(FavoritesLayout::V2);
+        }
+        match data.len() {
+            Favorites::V0_SPACE => Ok(FavoritesLayout::V0),
+            Favorites::V1_SPACE => Ok(FavoritesLayout::V1),
+            _ => err!(ErrorCode::UnknownFavoritesLayout),
+        }
+    }
+
+    // Decodes a favorites account written with any layout. `user` is the
+    // key the account is seeded by, the owner of V0 accounts.
+    pub fn try_from_any_layout(data: &[u8], user: &Pubkey) -> Result<Favorites> {
+        let layout = Favorites::layout_of(data)?;
+        let payload = &mut &data[ANCHOR_DISCRIMINATOR_SIZE..];
+        match layout {
+            FavoritesLayout::V0 => Ok(Favorites::from_v0(
+                FavoritesV0::deserialize(payload)?,
+                *user,
+            )),
+            FavoritesLayout::V1 => Ok(FavoritesV1::deserialize(payload)?.into()),
+            FavoritesLayout::V2 => Ok(Favorites::deserialize(payload)?),
+        }
+    }
+
+    pub fn from_v0(old: FavoritesV0, authority: Pubkey) -> Favorites {
+        Favorites {
+            number: old.number,
+            color: old.color,
+            authority,
+            delegates: Vec::new(),
+            pending_authority: None,
+            entries: Vec::new(),
+            history_enabled: false,
+            cooldown_slots: 0,
+            last_update_slot: 0,
+            delegate_daily_quota: 0,
+            reward_claimed: false,
+            created_slot: 0,
+        }
+    }
+}
+
+// The single delegate could update both the number and the color
+impl From<FavoritesV1> for Favorites {
+    fn from(old: FavoritesV1) -> Self {
+        let mut favorites = Favorites::from_v0(
+            FavoritesV0 {
+                number: old.number,
+                color: old.color,
+            },
+            old.authority,
+        );
+        favorites.delegates = old
+            .delegate
+            .map(|key| {
+                Delegate::new(
+                    key,
+                    PERMISSION_UPDATE_NUMBER | PERMISSION_UPDATE_COLOR,
+                    None,
+                )
+            })
+            .into_iter()
+            .collect();
+        favorites
+    }
+}
+
+#[derive(Accounts)]
+pub struct UpgradeFavorites<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: old layouts don't deserialize as `Favorites`, the layout is
+    /// checked by hand in `upgrade_favorites`
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"favorites", user.key().as_ref()],
+        bump,
+    )]
+    pub favorites: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account_data(layout: &impl AnchorSerialize, space: usize) -> Vec<u8> {
+        let mut data = Favorites::DISCRIMINATOR.to_vec();
+        layout.serialize(&mut data).unwrap();
+        data.resize(space, 0);
+        data
+    }
+
+    #[test]
+    fn reads_every_layout() {
+        let user = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+
+        let v0 = account_data(
+            &FavoritesV0 {
+                number: 1,
+                color: "red".to_string(),
+            },
+            Favorites::V0_SPACE,
+        );
+        assert_eq!(Favorites::layout_of(&v0).unwrap(), FavoritesLayout::V0);
+        let favorites = Favorites::try_from_any_layout(&v0, &user).unwrap();
+        assert_eq!(favorites.authority, user);
+        assert_eq!(favorites.color, "red");
+
+        let v1 = account_data(
+            &FavoritesV1 {
+                number: 2,
+                color: "blue".to_string(),
+                authority: user,
+                delegate: Some(delegate),
+            },
+            Favorites::V1_SPACE,
+        );
+        assert_eq!(Favorites::layout_of(&v1).unwrap(), FavoritesLayout::V1);
+        let favorites = Favorites::try_from_any_layout(&v1, &Pubkey::default()).unwrap();
+        assert_eq!(favorites.number, 2);
+        assert_eq!(favorites.authority, user);
+        assert_eq!(favorites.delegates, vec![Delegate::new(delegate, 3, None)]);
+    }
+
+    #[test]
+    fn current_accounts_of_the_v1_size_stay_current() {
+        let user = Pubkey::new_unique();
+        let mut favorites = Favorites::from_v0(
+            FavoritesV0 {
+                number: 3,
+                color: "x".repeat(6),
+            },
+            user,
+        );
+        favorites
+            .delegates
+            .push(Delegate::new(Pubkey::new_unique(), 1, None));
+        favorites.reward_claimed = true;
+        assert_eq!(favorites.space(), Favorites::V1_SPACE);
+
+        let data = account_data(&favorites, favorites.space());
+        assert_eq!(Favorites::layout_of(&data).unwrap(), FavoritesLayout::V2);
+        let read = Favorites::try_from_any_layout(&data, &user).unwrap();
+        assert_eq!(read.color, favorites.color);
+        assert_eq!(read.delegates, favorites.delegates);
+        assert!(read.reward_claimed);
+    }
+
+    #[test]
+    fn rejects_unknown_data() {
+        assert!(Favorites::layout_of(&[0; 70]).is_err());
+        assert!(Favorites::try_from_any_layout(&[0; 4], &Pubkey::default()).is_err());
+        let mut data = Favorites::DISCRIMINATOR.to_vec();
+        data.resize(100, 0);
+        assert!(Favorites::layout_of(&data).is_err());
+    }
+}
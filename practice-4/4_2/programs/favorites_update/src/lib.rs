@@ -1,9 +1,46 @@
+#![allow(unexpected_cfgs)]
+// The IDL handlers that `#[program]` adds at the crate root still call
+// `AccountInfo::realloc`, deprecated since Solana 2.3, and no narrower item
+// encloses them. Our own code uses `resize`.
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::TokenInterface};
+
+pub mod color;
+pub mod events;
+pub mod group;
+pub mod layout;
+pub mod reward;
+pub mod session;
+pub use color::Color;
+pub use events::*;
+pub use group::*;
+pub use layout::*;
+pub use reward::*;
+pub use session::*;
 
 declare_id!("DABBEEKjXnmL6Bfedxrf8YkEAPZYLNCKe4utAGTTWPxH");
 
 pub const ANCHOR_DISCRIMINATOR_SIZE: usize = 8;
 
+pub const MAX_DELEGATES: usize = 4;
+pub const MAX_COLOR_LEN: usize = 50;
+pub const MAX_ENTRIES: usize = 8;
+pub const MAX_ENTRY_LEN: usize = 64;
+pub const HISTORY_CAPACITY: usize = 8;
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// Delegate permission bits
+pub const PERMISSION_UPDATE_NUMBER: u8 = 1 << 0;
+pub const PERMISSION_UPDATE_COLOR: u8 = 1 << 1;
+pub const PERMISSION_MANAGE_DELEGATES: u8 = 1 << 2;
+pub const PERMISSION_UPDATE_ENTRIES: u8 = 1 << 3;
+pub const ALL_PERMISSIONS: u8 = PERMISSION_UPDATE_NUMBER
+    | PERMISSION_UPDATE_COLOR
+    | PERMISSION_MANAGE_DELEGATES
+    | PERMISSION_UPDATE_ENTRIES;
+
 #[account]
 #[derive(InitSpace)]
 pub struct FavoritesLegacy {
@@ -12,14 +49,232 @@ pub struct FavoritesLegacy {
     pub color: String,
 }
 
+// Accounts are sized to their content, see `Favorites::space`. `INIT_SPACE`
+// is only the upper bound.
 #[account]
 #[derive(InitSpace)]
 pub struct Favorites {
     pub number: u64,
-    #[max_len(50)]
+    #[max_len(MAX_COLOR_LEN)]
     pub color: String,
     pub authority: Pubkey,
-    pub delegate: Option<Pubkey>,
+    #[max_len(MAX_DELEGATES)]
+    pub delegates: Vec<Delegate>,
+    // Set by `propose_authority` until the new owner accepts
+    pub pending_authority: Option<Pubkey>,
+    #[max_len(MAX_ENTRIES)]
+    pub entries: Vec<Entry>,
+    // Set once `init_history` created the history PDA, from then on every
+    // update has to record itself there
+    pub history_enabled: bool,
+    // Minimum slots between two updates from delegates, 0 to turn it off
+    pub cooldown_slots: u64,
+    pub last_update_slot: u64,
+    // How many updates each delegate may make per UTC day, 0 for no limit
+    pub delegate_daily_quota: u16,
+    // Set when `set_favorites` minted the reward along with this account
+    pub reward_claimed: bool,
+    // Slot the account was created in. Sessions are bound to it, so they
+    // don't carry over when the account is closed and created again.
+    pub created_slot: u64,
+}
+
+// The last `HISTORY_CAPACITY` updates of a favorites account. Once the
+// buffer is full `next` points at the oldest change, which is overwritten.
+#[account]
+#[derive(InitSpace)]
+pub struct FavoritesHistory {
+    pub favorites: Pubkey,
+    pub next: u8,
+    #[max_len(HISTORY_CAPACITY)]
+    pub changes: Vec<Change>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct Change {
+    pub old_number: u64,
+    pub new_number: u64,
+    #[max_len(MAX_COLOR_LEN)]
+    pub old_color: String,
+    #[max_len(MAX_COLOR_LEN)]
+    pub new_color: String,
+    // The authority or the delegate that signed the update
+    pub actor: Pubkey,
+    pub slot: u64,
+}
+
+impl FavoritesHistory {
+    pub fn record(&mut self, change: Change) {
+        if self.changes.len() < HISTORY_CAPACITY {
+            self.changes.push(change);
+        } else {
+            self.changes[usize::from(self.next)] = change;
+        }
+        self.next = ((usize::from(self.next) + 1) % HISTORY_CAPACITY) as u8;
+    }
+
+    // Newest change first
+    pub fn recent(&self) -> impl Iterator<Item = &Change> {
+        let split = usize::from(self.next).min(self.changes.len());
+        let (newer, older) = self.changes.split_at(split);
+        newer.iter().rev().chain(older.iter().rev())
+    }
+}
+
+// Extra typed favorites next to the main number and color
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub enum Entry {
+    Color(#[max_len(MAX_ENTRY_LEN)] String),
+    Number(u64),
+    Url(#[max_len(MAX_ENTRY_LEN)] String),
+    Tag(#[max_len(MAX_ENTRY_LEN)] String),
+}
+
+impl Entry {
+    pub fn space(&self) -> usize {
+        1 + match self {
+            Entry::Number(_) => 8,
+            Entry::Color(text) | Entry::Url(text) | Entry::Tag(text) => 4 + text.len(),
+        }
+    }
+
+    // Checks the limits and returns the entry as it should be stored, with
+    // colors normalised like the main favorite color.
+    pub fn validated(self) -> Result<Self> {
+        match self {
+            Entry::Number(_) => Ok(self),
+            Entry::Color(text) | Entry::Url(text) | Entry::Tag(text)
+                if text.len() > MAX_ENTRY_LEN =>
+            {
+                err!(ErrorCode::EntryTooLong)
+            }
+            Entry::Color(color) => Ok(Entry::Color(Color::parse(&color)?.into_string())),
+            Entry::Url(url) => {
+                require!(
+                    url.starts_with("https://") || url.starts_with("http://"),
+                    ErrorCode::InvalidEntry
+                );
+                Ok(Entry::Url(url))
+            }
+            Entry::Tag(tag) => {
+                require!(!tag.is_empty(), ErrorCode::InvalidEntry);
+                Ok(Entry::Tag(tag))
+            }
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct Delegate {
+    pub key: Pubkey,
+    pub permissions: u8,
+    // Unix timestamp after which the delegate can no longer act
+    pub expires_at: Option<i64>,
+    // Updates made on `quota_day` (days since the Unix epoch)
+    pub updates_today: u16,
+    pub quota_day: u32,
+}
+
+impl Delegate {
+    pub fn new(key: Pubkey, permissions: u8, expires_at: Option<i64>) -> Self {
+        Delegate {
+            key,
+            permissions,
+            expires_at,
+            updates_today: 0,
+            quota_day: 0,
+        }
+    }
+
+    // Counts one update against `quota`, starting over on a new day
+    pub fn use_quota(&mut self, quota: u16, now: i64) -> Result<()> {
+        let today = u32::try_from(now.div_euclid(SECONDS_PER_DAY)).unwrap_or_default();
+        if self.quota_day != today {
+            self.quota_day = today;
+            self.updates_today = 0;
+        }
+        require!(
+            quota == 0 || self.updates_today < quota,
+            ErrorCode::DailyQuotaExceeded
+        );
+        self.updates_today = self.updates_today.saturating_add(1);
+        Ok(())
+    }
+
+    pub fn is_active(&self, now: i64) -> bool {
+        match self.expires_at {
+            Some(expires_at) => now < expires_at,
+            None => true,
+        }
+    }
+
+    pub fn space(&self) -> usize {
+        32 + 1 + 1 + self.expires_at.map_or(0, |_| 8) + 2 + 4
+    }
+}
+
+impl Favorites {
+    // Discriminator, number, color length, authority, delegates length, the
+    // `pending_authority` tag, entries length, `history_enabled`, the rate
+    // limit settings, `reward_claimed` and `created_slot`
+    const FIXED_SPACE: usize =
+        ANCHOR_DISCRIMINATOR_SIZE + 8 + 4 + 32 + 4 + 1 + 4 + 1 + 8 + 8 + 2 + 1 + 8;
+
+    // Size of a new account holding `color`, with no delegates or transfer
+    pub fn space_for(color: &str) -> usize {
+        Self::FIXED_SPACE + color.len()
+    }
+
+    // Size the account needs for what it holds right now
+    pub fn space(&self) -> usize {
+        Self::FIXED_SPACE
+            + self.color.len()
+            + self.delegates.iter().map(Delegate::space).sum::<usize>()
+            + self.pending_authority.map_or(0, |_| 32)
+            + self.entries.iter().map(Entry::space).sum::<usize>()
+    }
+
+    // What `signer` may do right now: everything for the authority, the
+    // granted bits for an unexpired delegate, nothing otherwise.
+    pub fn permissions_of(&self, signer: &Pubkey, now: i64) -> u8 {
+        if self.authority == *signer {
+            return ALL_PERMISSIONS;
+        }
+        self.delegates
+            .iter()
+            .find(|delegate| delegate.key == *signer && delegate.is_active(now))
+            .map_or(0, |delegate| delegate.permissions)
+    }
+
+    // A delegate managing others can't edit its own entry, nor change or
+    // remove a delegate holding permissions it lacks. The owner can.
+    pub fn require_can_manage(&self, signer: &Pubkey, target: &Pubkey, granted: u8) -> Result<()> {
+        if self.authority == *signer {
+            return Ok(());
+        }
+        require_keys_neq!(*signer, *target, ErrorCode::Unauthorized);
+        let held = self
+            .delegates
+            .iter()
+            .find(|delegate| delegate.key == *target)
+            .map_or(0, |delegate| delegate.permissions);
+        require!(held & !granted == 0, ErrorCode::Unauthorized);
+        Ok(())
+    }
+
+    // Access a delegate hands out ends no later than its own
+    pub fn cap_expiry(&self, signer: &Pubkey, expires_at: Option<i64>) -> Option<i64> {
+        let own = self
+            .delegates
+            .iter()
+            .find(|delegate| delegate.key == *signer)
+            .and_then(|delegate| delegate.expires_at);
+        match (expires_at, own) {
+            (Some(expires_at), Some(own)) => Some(expires_at.min(own)),
+            (expires_at, None) => expires_at,
+            (None, own) => own,
+        }
+    }
 }
 
 #[derive(Accounts)]
@@ -38,13 +293,57 @@ pub struct SetFavoritesLegacy<'info> {
 }
 
 #[derive(Accounts)]
+#[instruction(number: u64, color: String)]
 pub struct SetFavorites<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
     #[account(
         init,
         payer = user,
-        space = ANCHOR_DISCRIMINATOR_SIZE + Favorites::INIT_SPACE,
+        space = Favorites::space_for(&color),
+        seeds = [b"favorites", user.key().as_ref()],
+        bump,
+    )]
+    pub favorites: Account<'info, Favorites>,
+    pub system_program: Program<'info, System>,
+    // The reward accounts are optional, see `mint_reward_once`
+    /// CHECK: Only signs for the mint, holds no data
+    #[account(seeds = [REWARD_AUTHORITY_SEED], bump)]
+    pub reward_authority: Option<UncheckedAccount<'info>>,
+    /// CHECK: May not be created yet, the mint CPI checks it otherwise
+    #[account(mut, seeds = [REWARD_MINT_SEED], bump)]
+    pub reward_mint: Option<UncheckedAccount<'info>>,
+    /// CHECK: The user's associated token account for the reward mint, the
+    /// associated token program checks the address when creating it
+    #[account(mut)]
+    pub user_reward_account: Option<UncheckedAccount<'info>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = ANCHOR_DISCRIMINATOR_SIZE + RewardClaim::INIT_SPACE,
+        seeds = [REWARD_CLAIM_SEED, user.key().as_ref()],
+        bump,
+    )]
+    pub reward_claim: Option<Account<'info, RewardClaim>>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateFavorites<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"favorites-legacy", user.key().as_ref()],
+        bump,
+    )]
+    pub legacy_favorites: Account<'info, FavoritesLegacy>,
+    #[account(
+        init,
+        payer = user,
+        space = Favorites::space_for(&legacy_favorites.color),
         seeds = [b"favorites", user.key().as_ref()],
         bump,
     )]
@@ -52,62 +351,980 @@ pub struct SetFavorites<'info> {
     pub system_program: Program<'info, System>,
 }
 
+// `user` is the wallet the PDA was created for, `authority` is whoever signs
+// the update: the owner, their delegate or a session key.
 #[derive(Accounts)]
 pub struct UpdateFavorites<'info> {
+    #[account(
+        mut,
+        seeds = [b"favorites", user.key().as_ref()],
+        bump,
+    )]
+    pub favorites: Account<'info, Favorites>,
+    /// CHECK: Only used to derive the favorites PDA
+    pub user: UncheckedAccount<'info>,
+    // Pays for the account growing, unless it's a session key, see
+    // `UpdateFavorites::fit_to_content`
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    // Required by `update_favorites` once the history is enabled
+    #[account(
+        mut,
+        seeds = [b"history", favorites.key().as_ref()],
+        bump,
+    )]
+    pub history: Option<Account<'info, FavoritesHistory>>,
+    // Required when `authority` is a session key
+    #[account(
+        mut,
+        seeds = [b"session", favorites.key().as_ref(), authority.key().as_ref()],
+        bump,
+    )]
+    pub session: Option<Account<'info, Session>>,
+}
+
+impl UpdateFavorites<'_> {
+    // What `authority` may do right now. A session key gets the permissions
+    // of its session, and every change through it uses up one of its updates.
+    fn granted_permissions(&mut self, now: i64) -> Result<u8> {
+        let granted = self.favorites.permissions_of(&self.authority.key(), now);
+        match self.session.as_mut() {
+            Some(session) if granted == 0 => session.use_update(&self.favorites, now),
+            _ => Ok(granted),
+        }
+    }
+
+    // Anyone but the owner waits for the cooldown between changes and counts
+    // against the daily quota, whichever handler the change goes through.
+    fn rate_limit(&mut self, clock: &Clock) -> Result<()> {
+        let signer = self.authority.key();
+        let favorites = &mut self.favorites;
+        if signer != favorites.authority {
+            let ready_at = favorites
+                .last_update_slot
+                .saturating_add(favorites.cooldown_slots);
+            require!(clock.slot >= ready_at, ErrorCode::CooldownActive);
+            let quota = favorites.delegate_daily_quota;
+            if let Some(delegate) = favorites.delegates.iter_mut().find(|d| d.key == signer) {
+                delegate.use_quota(quota, clock.unix_timestamp)?;
+            }
+        }
+        favorites.last_update_slot = clock.slot;
+        Ok(())
+    }
+
+    // `fit_to_content` with `authority` paying. A session key is a throwaway
+    // key that shouldn't hold lamports, so updates through a session may only
+    // use the rent already in the account.
+    fn fit_to_content(&self) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        if self.favorites.permissions_of(&self.authority.key(), now) == 0 {
+            let required = Rent::get()?.minimum_balance(self.favorites.space());
+            require!(
+                self.favorites.to_account_info().lamports() >= required,
+                ErrorCode::SessionCannotGrow
+            );
+        }
+        fit_to_content(&self.favorites, &self.authority, &self.system_program)
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitHistory<'info> {
     #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"favorites", user.key().as_ref()],
+        bump,
+        constraint = favorites.authority == authority.key() @ ErrorCode::Unauthorized,
+    )]
     pub favorites: Account<'info, Favorites>,
-    /// CHECK: Only used for pubkey comparison
-    pub user: AccountInfo<'info>,
+    /// CHECK: Only used to derive the favorites PDA
+    pub user: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = ANCHOR_DISCRIMINATOR_SIZE + FavoritesHistory::INIT_SPACE,
+        seeds = [b"history", favorites.key().as_ref()],
+        bump,
+    )]
+    pub history: Account<'info, FavoritesHistory>,
+    pub system_program: Program<'info, System>,
+}
+
+// Same shape as `UpdateFavorites`: the owner or a delegate allowed to manage
+// delegates signs as `authority`.
+#[derive(Accounts)]
+pub struct ManageDelegates<'info> {
+    #[account(
+        mut,
+        seeds = [b"favorites", user.key().as_ref()],
+        bump,
+    )]
+    pub favorites: Account<'info, Favorites>,
+    /// CHECK: Only used to derive the favorites PDA
+    pub user: UncheckedAccount<'info>,
+    // Pays for the account growing, see `fit_to_content`
+    #[account(mut)]
     pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
+// The PDA stays seeded by the wallet that created it, so after an ownership
+// transfer `user` and `authority` are different keys.
 #[derive(Accounts)]
 pub struct SetAuthority<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
     #[account(
         mut,
-        seeds = [b"favorites", authority.key().as_ref()],
+        seeds = [b"favorites", user.key().as_ref()],
+        bump,
+        constraint = favorites.authority == authority.key() @ ErrorCode::Unauthorized,
+    )]
+    pub favorites: Account<'info, Favorites>,
+    /// CHECK: Only used to derive the favorites PDA
+    pub user: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+// Read-only, anyone can call it for any user
+#[derive(Accounts)]
+pub struct GetFavorites<'info> {
+    #[account(
+        seeds = [b"favorites", user.key().as_ref()],
+        bump,
+    )]
+    pub favorites: Account<'info, Favorites>,
+    /// CHECK: Only used to derive the favorites PDA
+    pub user: UncheckedAccount<'info>,
+}
+
+// Only the current authority can close, the rent goes to any wallet it picks.
+// `user` can run `set_favorites` again afterwards to re-create the PDA.
+#[derive(Accounts)]
+pub struct CloseFavorites<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        close = recipient,
+        seeds = [b"favorites", user.key().as_ref()],
+        bump,
+        constraint = favorites.authority == authority.key() @ ErrorCode::Unauthorized,
+    )]
+    pub favorites: Account<'info, Favorites>,
+    /// CHECK: Only used to derive the favorites PDA
+    pub user: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+    // The history goes together with the account it belongs to
+    #[account(
+        mut,
+        close = recipient,
+        seeds = [b"history", favorites.key().as_ref()],
+        bump,
+    )]
+    pub history: Option<Account<'info, FavoritesHistory>>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(mut)]
+    pub new_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"favorites", user.key().as_ref()],
         bump,
+        constraint = favorites.pending_authority == Some(new_authority.key())
+            @ ErrorCode::NotPendingAuthority,
     )]
     pub favorites: Account<'info, Favorites>,
+    /// CHECK: Only used to derive the favorites PDA
+    pub user: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+// Grows or shrinks `favorites` to fit its content. `payer` covers any extra
+// rent; surplus rent only goes back when `payer` owns the account, so a
+// delegate can't walk away with lamports the owner put in.
+fn fit_to_content<'info>(
+    favorites: &Account<'info, Favorites>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let info = favorites.to_account_info();
+    let space = favorites.space();
+    let required = Rent::get()?.minimum_balance(space);
+    let current = info.lamports();
+
+    if current < required {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: info.clone(),
+                },
+            ),
+            required - current,
+        )?;
+    } else if current > required && favorites.authority == payer.key() {
+        info.sub_lamports(current - required)?;
+        payer.add_lamports(current - required)?;
+    }
+    info.resize(space)?;
+    Ok(())
+}
+
+fn require_entries_permission(accounts: &mut UpdateFavorites) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        accounts.granted_permissions(now)? & PERMISSION_UPDATE_ENTRIES != 0,
+        ErrorCode::Unauthorized
+    );
+    Ok(())
 }
 
 #[program]
 pub mod favorites_update {
     use super::*;
 
-    pub fn set_favorites_legacy(ctx: Context<SetFavoritesLegacy>, number: u64, color: String) -> Result<()> {
+    pub fn set_favorites_legacy(
+        ctx: Context<SetFavoritesLegacy>,
+        number: u64,
+        color: String,
+    ) -> Result<()> {
         ctx.accounts.favorites.number = number;
-        ctx.accounts.favorites.color = color;
+        ctx.accounts.favorites.color = color.clone();
+        emit!(FavoritesSet {
+            favorites: ctx.accounts.favorites.key(),
+            user: ctx.accounts.user.key(),
+            number,
+            color,
+        });
         Ok(())
     }
 
     pub fn set_favorites(ctx: Context<SetFavorites>, number: u64, color: String) -> Result<()> {
+        require!(color.len() <= MAX_COLOR_LEN, ErrorCode::ColorTooLong);
+        let color = Color::parse(&color)?.into_string();
+        emit!(FavoritesSet {
+            favorites: ctx.accounts.favorites.key(),
+            user: ctx.accounts.user.key(),
+            number,
+            color: color.clone(),
+        });
         let favorites = &mut ctx.accounts.favorites;
         favorites.number = number;
         favorites.color = color;
         favorites.authority = ctx.accounts.user.key();
-        favorites.delegate = None;
+        favorites.delegates = Vec::new();
+        favorites.pending_authority = None;
+        favorites.entries = Vec::new();
+        favorites.history_enabled = false;
+        favorites.cooldown_slots = 0;
+        favorites.last_update_slot = 0;
+        favorites.delegate_daily_quota = 0;
+        favorites.reward_claimed = false;
+        favorites.created_slot = Clock::get()?.slot;
+
+        let reward_authority_bump = ctx.bumps.reward_authority;
+        if mint_reward_once(ctx.accounts, reward_authority_bump)? {
+            ctx.accounts.favorites.reward_claimed = true;
+            emit!(RewardMinted {
+                favorites: ctx.accounts.favorites.key(),
+                user: ctx.accounts.user.key(),
+                amount: REWARD_AMOUNT,
+            });
+        }
+        Ok(())
+    }
+
+    // Moves a legacy account to the `favorites` seed. The legacy account is
+    // closed, so running it a second time fails instead of overwriting data.
+    pub fn migrate_favorites(ctx: Context<MigrateFavorites>) -> Result<()> {
+        let legacy = &ctx.accounts.legacy_favorites;
+        emit!(Migrated {
+            favorites: ctx.accounts.favorites.key(),
+            legacy_favorites: legacy.key(),
+            user: ctx.accounts.user.key(),
+            number: legacy.number,
+            color: legacy.color.clone(),
+        });
+        let favorites = &mut ctx.accounts.favorites;
+        favorites.number = legacy.number;
+        favorites.color = legacy.color.clone();
+        favorites.authority = ctx.accounts.user.key();
+        favorites.delegates = Vec::new();
+        favorites.pending_authority = None;
+        favorites.entries = Vec::new();
+        favorites.history_enabled = false;
+        favorites.cooldown_slots = 0;
+        favorites.last_update_slot = 0;
+        favorites.delegate_daily_quota = 0;
+        favorites.reward_claimed = false;
+        favorites.created_slot = Clock::get()?.slot;
         Ok(())
     }
 
+    // Replaces every delegate with a single one that may update the number
+    // and the color, or removes them all when `new_delegate` is `None`.
     pub fn set_authority(ctx: Context<SetAuthority>, new_delegate: Option<Pubkey>) -> Result<()> {
-        ctx.accounts.favorites.delegate = new_delegate;
+        let favorites_key = ctx.accounts.favorites.key();
+        let changed_by = ctx.accounts.authority.key();
+        let favorites = &mut ctx.accounts.favorites;
+
+        for removed in favorites.delegates.drain(..) {
+            emit!(DelegateChanged {
+                favorites: favorites_key,
+                delegate: removed.key,
+                permissions: 0,
+                expires_at: None,
+                changed_by,
+            });
+        }
+        if let Some(key) = new_delegate {
+            let delegate = Delegate::new(
+                key,
+                PERMISSION_UPDATE_NUMBER | PERMISSION_UPDATE_COLOR,
+                None,
+            );
+            emit!(DelegateChanged {
+                favorites: favorites_key,
+                delegate: key,
+                permissions: delegate.permissions,
+                expires_at: None,
+                changed_by,
+            });
+            favorites.delegates.push(delegate);
+        }
+        fit_to_content(
+            &ctx.accounts.favorites,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )
+    }
+
+    // First half of an ownership transfer. Proposing `None` cancels a pending
+    // transfer; nothing changes until the new owner calls `accept_authority`.
+    pub fn propose_authority(
+        ctx: Context<SetAuthority>,
+        new_authority: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.favorites.pending_authority = new_authority;
+        emit!(AuthorityProposed {
+            favorites: ctx.accounts.favorites.key(),
+            pending_authority: new_authority,
+        });
+        fit_to_content(
+            &ctx.accounts.favorites,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )
+    }
+
+    // Rate limits for delegates; the authority itself is never limited.
+    pub fn set_limits(
+        ctx: Context<SetAuthority>,
+        cooldown_slots: u64,
+        delegate_daily_quota: u16,
+    ) -> Result<()> {
+        let favorites = &mut ctx.accounts.favorites;
+        favorites.cooldown_slots = cooldown_slots;
+        favorites.delegate_daily_quota = delegate_daily_quota;
+        emit!(LimitsChanged {
+            favorites: favorites.key(),
+            cooldown_slots,
+            delegate_daily_quota,
+        });
         Ok(())
     }
 
-    pub fn update_favorites(ctx: Context<UpdateFavorites>, number: u64, color: String) -> Result<()> {
-        let user = ctx.accounts.user.key();
+    // Makes the proposed wallet the owner. Delegates were chosen by the
+    // previous owner, so they are dropped with the transfer.
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let favorites_key = ctx.accounts.favorites.key();
+        let new_authority = ctx.accounts.new_authority.key();
+        let favorites = &mut ctx.accounts.favorites;
+        let previous_authority = favorites.authority;
+
+        for removed in favorites.delegates.drain(..) {
+            emit!(DelegateChanged {
+                favorites: favorites_key,
+                delegate: removed.key,
+                permissions: 0,
+                expires_at: None,
+                changed_by: new_authority,
+            });
+        }
+        favorites.authority = new_authority;
+        favorites.pending_authority = None;
+
+        emit!(AuthorityTransferred {
+            favorites: favorites_key,
+            previous_authority,
+            new_authority,
+        });
+        fit_to_content(
+            &ctx.accounts.favorites,
+            &ctx.accounts.new_authority,
+            &ctx.accounts.system_program,
+        )
+    }
+
+    // Hands the whole account back as return data, for programs reading it
+    // through CPI and for clients simulating the transaction.
+    pub fn get_favorites(ctx: Context<GetFavorites>) -> Result<Favorites> {
+        Ok((*ctx.accounts.favorites).clone())
+    }
+
+    // The `close` constraints move the lamports and wipe the accounts. The
+    // reward stays with the user, `RewardClaim` keeps it from being minted
+    // again.
+    pub fn close_favorites(ctx: Context<CloseFavorites>) -> Result<()> {
+        require!(
+            !ctx.accounts.favorites.history_enabled || ctx.accounts.history.is_some(),
+            ErrorCode::HistoryRequired
+        );
+        emit!(FavoritesClosed {
+            favorites: ctx.accounts.favorites.key(),
+            recipient: ctx.accounts.recipient.key(),
+        });
+        Ok(())
+    }
+
+    // Starts recording updates. The history can't be turned off again other
+    // than by closing the favorites.
+    pub fn init_history(ctx: Context<InitHistory>) -> Result<()> {
+        ctx.accounts.history.favorites = ctx.accounts.favorites.key();
+        ctx.accounts.favorites.history_enabled = true;
+        emit!(HistoryEnabled {
+            favorites: ctx.accounts.favorites.key(),
+            history: ctx.accounts.history.key(),
+        });
+        fit_to_content(
+            &ctx.accounts.favorites,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )
+    }
+
+    // Adds a delegate or replaces the permissions of an existing one. A
+    // delegate managing others can only hand out permissions it holds itself,
+    // for no longer than it holds them, see `require_can_manage`.
+    pub fn set_delegate(
+        ctx: Context<ManageDelegates>,
+        delegate: Pubkey,
+        permissions: u8,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        require!(
+            permissions != 0 && permissions & !ALL_PERMISSIONS == 0,
+            ErrorCode::InvalidPermissions
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let signer = ctx.accounts.authority.key();
+        let favorites_key = ctx.accounts.favorites.key();
         let favorites = &mut ctx.accounts.favorites;
-        if favorites.authority != user {
-            match favorites.delegate {
-                Some(delegate) if delegate == user => {},
-                _ => return Err(error!(ErrorCode::Unauthorized)),
+
+        let granted = favorites.permissions_of(&signer, now);
+        require!(
+            granted & PERMISSION_MANAGE_DELEGATES != 0,
+            ErrorCode::Unauthorized
+        );
+        require!(permissions & !granted == 0, ErrorCode::Unauthorized);
+        favorites.require_can_manage(&signer, &delegate, granted)?;
+        let expires_at = favorites.cap_expiry(&signer, expires_at);
+
+        match favorites
+            .delegates
+            .iter_mut()
+            .find(|existing| existing.key == delegate)
+        {
+            // Usage counts stay, re-granting must not reset the daily quota
+            Some(existing) => {
+                existing.permissions = permissions;
+                existing.expires_at = expires_at;
+            }
+            None => {
+                require!(
+                    favorites.delegates.len() < MAX_DELEGATES,
+                    ErrorCode::TooManyDelegates
+                );
+                favorites
+                    .delegates
+                    .push(Delegate::new(delegate, permissions, expires_at));
             }
         }
+
+        emit!(DelegateChanged {
+            favorites: favorites_key,
+            delegate,
+            permissions,
+            expires_at,
+            changed_by: signer,
+        });
+        fit_to_content(
+            &ctx.accounts.favorites,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )
+    }
+
+    pub fn remove_delegate(ctx: Context<ManageDelegates>, delegate: Pubkey) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let signer = ctx.accounts.authority.key();
+        let favorites_key = ctx.accounts.favorites.key();
+        let favorites = &mut ctx.accounts.favorites;
+
+        // Delegates may always step down themselves
+        if signer != delegate {
+            let granted = favorites.permissions_of(&signer, now);
+            require!(
+                granted & PERMISSION_MANAGE_DELEGATES != 0,
+                ErrorCode::Unauthorized
+            );
+            favorites.require_can_manage(&signer, &delegate, granted)?;
+        }
+
+        let index = favorites
+            .delegates
+            .iter()
+            .position(|existing| existing.key == delegate)
+            .ok_or(ErrorCode::DelegateNotFound)?;
+        favorites.delegates.remove(index);
+
+        emit!(DelegateChanged {
+            favorites: favorites_key,
+            delegate,
+            permissions: 0,
+            expires_at: None,
+            changed_by: signer,
+        });
+        fit_to_content(
+            &ctx.accounts.favorites,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )
+    }
+
+    // Registers `session_key` so it can sign `UpdateFavorites` instructions
+    // until `expires_at`, at most `max_updates` times.
+    pub fn create_session(
+        ctx: Context<CreateSession>,
+        session_key: Pubkey,
+        permissions: u8,
+        expires_at: i64,
+        max_updates: u16,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        Session::validate(permissions, expires_at, max_updates, now)?;
+        ctx.accounts.session.set_inner(Session {
+            favorites: ctx.accounts.favorites.key(),
+            session_key,
+            authority: ctx.accounts.authority.key(),
+            favorites_created_slot: ctx.accounts.favorites.created_slot,
+            permissions,
+            expires_at,
+            updates_left: max_updates,
+        });
+        emit!(SessionCreated {
+            favorites: ctx.accounts.favorites.key(),
+            session_key,
+            permissions,
+            expires_at,
+            max_updates,
+        });
+        Ok(())
+    }
+
+    pub fn close_session(ctx: Context<CloseSession>) -> Result<()> {
+        emit!(SessionClosed {
+            favorites: ctx.accounts.session.favorites,
+            session_key: ctx.accounts.session.session_key,
+            closed_by: ctx.accounts.signer.key(),
+        });
+        Ok(())
+    }
+
+    pub fn add_entry(ctx: Context<UpdateFavorites>, entry: Entry) -> Result<()> {
+        let entry = entry.validated()?;
+        require_entries_permission(ctx.accounts)?;
+        ctx.accounts.rate_limit(&Clock::get()?)?;
+        let favorites = &mut ctx.accounts.favorites;
+        require!(
+            favorites.entries.len() < MAX_ENTRIES,
+            ErrorCode::TooManyEntries
+        );
+
+        emit!(EntryChanged {
+            favorites: favorites.key(),
+            actor: ctx.accounts.authority.key(),
+            index: favorites.entries.len() as u8,
+            entry: Some(entry.clone()),
+        });
+        favorites.entries.push(entry);
+        ctx.accounts.fit_to_content()
+    }
+
+    pub fn replace_entry(ctx: Context<UpdateFavorites>, index: u8, entry: Entry) -> Result<()> {
+        let entry = entry.validated()?;
+        require_entries_permission(ctx.accounts)?;
+        ctx.accounts.rate_limit(&Clock::get()?)?;
+        let favorites = &mut ctx.accounts.favorites;
+
+        let slot = favorites
+            .entries
+            .get_mut(usize::from(index))
+            .ok_or(ErrorCode::EntryNotFound)?;
+        *slot = entry.clone();
+        emit!(EntryChanged {
+            favorites: favorites.key(),
+            actor: ctx.accounts.authority.key(),
+            index,
+            entry: Some(entry),
+        });
+        ctx.accounts.fit_to_content()
+    }
+
+    // Later entries move down by one, so clients should re-read indexes
+    // after a removal.
+    pub fn remove_entry(ctx: Context<UpdateFavorites>, index: u8) -> Result<()> {
+        require_entries_permission(ctx.accounts)?;
+        ctx.accounts.rate_limit(&Clock::get()?)?;
+        let favorites = &mut ctx.accounts.favorites;
+        require!(
+            usize::from(index) < favorites.entries.len(),
+            ErrorCode::EntryNotFound
+        );
+
+        favorites.entries.remove(usize::from(index));
+        emit!(EntryChanged {
+            favorites: favorites.key(),
+            actor: ctx.accounts.authority.key(),
+            index,
+            entry: None,
+        });
+        ctx.accounts.fit_to_content()
+    }
+
+    pub fn update_favorites(
+        ctx: Context<UpdateFavorites>,
+        number: u64,
+        color: String,
+    ) -> Result<()> {
+        require!(color.len() <= MAX_COLOR_LEN, ErrorCode::ColorTooLong);
+        let color = Color::parse(&color)?.into_string();
+        let now = Clock::get()?.unix_timestamp;
+        let granted = ctx.accounts.granted_permissions(now)?;
+        let favorites = &mut ctx.accounts.favorites;
+
+        let mut needed = 0;
+        if favorites.number != number {
+            needed |= PERMISSION_UPDATE_NUMBER;
+        }
+        if favorites.color != color {
+            needed |= PERMISSION_UPDATE_COLOR;
+        }
+        require!(
+            granted != 0 && needed & !granted == 0,
+            ErrorCode::Unauthorized
+        );
+
+        let clock = Clock::get()?;
+        let signer = ctx.accounts.authority.key();
+        ctx.accounts.rate_limit(&clock)?;
+        let favorites = &mut ctx.accounts.favorites;
+
+        match ctx.accounts.history.as_mut() {
+            Some(history) => history.record(Change {
+                old_number: favorites.number,
+                new_number: number,
+                old_color: favorites.color.clone(),
+                new_color: color.clone(),
+                actor: signer,
+                slot: clock.slot,
+            }),
+            None => require!(!favorites.history_enabled, ErrorCode::HistoryRequired),
+        }
+
+        emit!(FavoritesUpdated {
+            favorites: favorites.key(),
+            actor: signer,
+            number,
+            color: color.clone(),
+        });
         favorites.number = number;
         favorites.color = color;
+        ctx.accounts.fit_to_content()
+    }
+    pub fn init_reward_mint(_ctx: Context<InitRewardMint>) -> Result<()> {
+        Ok(())
+    }
+
+    // Rewrites an account created by an older version of the program in the
+    // current layout, resizing it in place. The user pays for extra rent and
+    // gets any surplus back.
+    pub fn upgrade_favorites(ctx: Context<UpgradeFavorites>) -> Result<()> {
+        let info = ctx.accounts.favorites.to_account_info();
+        let user = ctx.accounts.user.key();
+
+        let (favorites, previous_layout) = {
+            let data = info.try_borrow_data()?;
+            let layout = Favorites::layout_of(&data)?;
+            require!(
+                layout != FavoritesLayout::CURRENT,
+                ErrorCode::AlreadyUpgraded
+            );
+            (Favorites::try_from_any_layout(&data, &user)?, layout)
+        };
+
+        let space = favorites.space();
+        let required = Rent::get()?.minimum_balance(space);
+        let current = info.lamports();
+        if current < required {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.user.to_account_info(),
+                        to: info.clone(),
+                    },
+                ),
+                required - current,
+            )?;
+        } else if current > required {
+            info.sub_lamports(current - required)?;
+            ctx.accounts.user.add_lamports(current - required)?;
+        }
+        info.resize(space)?;
+
+        let mut data = info.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data;
+        favorites.try_serialize(&mut writer)?;
+
+        emit!(FavoritesUpgraded {
+            favorites: info.key(),
+            user,
+            previous_layout,
+        });
+        Ok(())
+    }
+
+    // Creates favorites shared by a group. The creator becomes its first
+    // admin, `members` are added after it.
+    pub fn create_group(
+        ctx: Context<CreateGroup>,
+        group_id: u64,
+        number: u64,
+        color: String,
+        members: Vec<Member>,
+        threshold: u8,
+    ) -> Result<()> {
+        require!(color.len() <= MAX_COLOR_LEN, ErrorCode::ColorTooLong);
+        let color = Color::parse(&color)?.into_string();
+        let creator = ctx.accounts.creator.key();
+        let group_key = ctx.accounts.group.key();
+        let group = &mut ctx.accounts.group;
+        group.group_id = group_id;
+        group.number = number;
+        group.color = color.clone();
+        group.members = vec![Member {
+            key: creator,
+            role: Role::Admin,
+        }];
+        group.members.extend(members);
+        group.threshold = threshold;
+        group.next_change_id = 0;
+        group.validate()?;
+
+        emit!(GroupCreated {
+            group: group_key,
+            group_id,
+            creator,
+            number,
+            color,
+            threshold,
+        });
+        for member in &group.members {
+            emit!(GroupMemberChanged {
+                group: group_key,
+                member: member.key,
+                role: Some(member.role),
+                changed_by: creator,
+            });
+        }
+        Ok(())
+    }
+
+    // Adds a member or changes the role of an existing one
+    pub fn set_member(ctx: Context<ManageMembers>, member: Pubkey, role: Role) -> Result<()> {
+        let group_key = ctx.accounts.group.key();
+        let group = &mut ctx.accounts.group;
+        match group.members.iter_mut().find(|m| m.key == member) {
+            Some(existing) => existing.role = role,
+            None => group.members.push(Member { key: member, role }),
+        }
+        group.validate()?;
+
+        emit!(GroupMemberChanged {
+            group: group_key,
+            member,
+            role: Some(role),
+            changed_by: ctx.accounts.admin.key(),
+        });
+        Ok(())
+    }
+
+    pub fn remove_member(ctx: Context<ManageMembers>, member: Pubkey) -> Result<()> {
+        let group_key = ctx.accounts.group.key();
+        let group = &mut ctx.accounts.group;
+        let index = group
+            .members
+            .iter()
+            .position(|m| m.key == member)
+            .ok_or(ErrorCode::MemberNotFound)?;
+        group.members.remove(index);
+        group.validate()?;
+
+        emit!(GroupMemberChanged {
+            group: group_key,
+            member,
+            role: None,
+            changed_by: ctx.accounts.admin.key(),
+        });
+        Ok(())
+    }
+
+    pub fn set_threshold(ctx: Context<ManageMembers>, threshold: u8) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+        group.threshold = threshold;
+        group.validate()?;
+
+        emit!(GroupThresholdChanged {
+            group: group.key(),
+            threshold,
+        });
+        Ok(())
+    }
+
+    // Any member can propose an update, which counts as their approval
+    pub fn propose_group_change(
+        ctx: Context<ProposeGroupChange>,
+        number: u64,
+        color: String,
+    ) -> Result<()> {
+        require!(color.len() <= MAX_COLOR_LEN, ErrorCode::ColorTooLong);
+        let color = Color::parse(&color)?.into_string();
+        let proposer = ctx.accounts.proposer.key();
+        let group = &mut ctx.accounts.group;
+        let pending_change = &mut ctx.accounts.pending_change;
+
+        pending_change.group = group.key();
+        pending_change.id = group.next_change_id;
+        pending_change.proposer = proposer;
+        pending_change.number = number;
+        pending_change.color = color.clone();
+        pending_change.approvals = vec![proposer];
+        group.next_change_id += 1;
+
+        emit!(GroupChangeProposed {
+            group: group.key(),
+            pending_change: pending_change.key(),
+            proposer,
+            number,
+            color,
+        });
+        Ok(())
+    }
+
+    pub fn approve_group_change(ctx: Context<ApproveGroupChange>) -> Result<()> {
+        let approver = ctx.accounts.approver.key();
+        let group = &ctx.accounts.group;
+        let pending_change = &mut ctx.accounts.pending_change;
+        require!(
+            !pending_change.approvals.contains(&approver),
+            ErrorCode::AlreadyApproved
+        );
+
+        // Approvals of removed members no longer count, drop them to keep
+        // room for the current ones
+        pending_change
+            .approvals
+            .retain(|key| group.role_of(key).is_some());
+        pending_change.approvals.push(approver);
+
+        emit!(GroupChangeApproved {
+            group: group.key(),
+            pending_change: pending_change.key(),
+            approver,
+            approvals: pending_change.approvals.len() as u8,
+        });
+        Ok(())
+    }
+
+    // Admins update directly. Everybody else applies a pending change that
+    // reached the threshold, which closes it.
+    pub fn update_group_favorites(
+        ctx: Context<UpdateGroupFavorites>,
+        number: u64,
+        color: String,
+    ) -> Result<()> {
+        require!(color.len() <= MAX_COLOR_LEN, ErrorCode::ColorTooLong);
+        let color = Color::parse(&color)?.into_string();
+        let signer = ctx.accounts.signer.key();
+        let group = &mut ctx.accounts.group;
+
+        let applied = match ctx.accounts.pending_change.as_ref() {
+            Some(pending_change) => {
+                require!(
+                    pending_change.number == number && pending_change.color == color,
+                    ErrorCode::ChangeMismatch
+                );
+                require!(
+                    group.approvals(pending_change) >= usize::from(group.threshold),
+                    ErrorCode::NotEnoughApprovals
+                );
+                let proposer = ctx
+                    .accounts
+                    .proposer
+                    .as_ref()
+                    .ok_or(anchor_lang::error::ErrorCode::AccountNotEnoughKeys)?;
+                require_keys_eq!(
+                    proposer.key(),
+                    pending_change.proposer,
+                    anchor_lang::error::ErrorCode::ConstraintHasOne
+                );
+                pending_change.close(proposer.to_account_info())?;
+                Some(pending_change.key())
+            }
+            None => {
+                require!(group.is_admin(&signer), ErrorCode::Unauthorized);
+                None
+            }
+        };
+
+        group.number = number;
+        group.color = color.clone();
+        emit!(GroupFavoritesUpdated {
+            group: group.key(),
+            actor: signer,
+            number,
+            color,
+            pending_change: applied,
+        });
+        Ok(())
+    }
+
+    // The proposer or an admin withdraws a change, refunding the proposer
+    pub fn cancel_group_change(ctx: Context<CancelGroupChange>) -> Result<()> {
+        emit!(GroupChangeCancelled {
+            group: ctx.accounts.group.key(),
+            pending_change: ctx.accounts.pending_change.key(),
+            cancelled_by: ctx.accounts.signer.key(),
+        });
         Ok(())
     }
 }
@@ -116,4 +1333,219 @@ pub mod favorites_update {
 pub enum ErrorCode {
     #[msg("You are not authorized to update this account")]
     Unauthorized,
-}
\ No newline at end of file
+    #[msg("Permissions must be a non-empty combination of the known permission bits")]
+    InvalidPermissions,
+    #[msg("This account already has the maximum number of delegates")]
+    TooManyDelegates,
+    #[msg("No such delegate on this account")]
+    DelegateNotFound,
+    #[msg("Only the proposed authority can accept the transfer")]
+    NotPendingAuthority,
+    #[msg("Color is longer than 50 bytes")]
+    ColorTooLong,
+    #[msg("This account already has the maximum number of entries")]
+    TooManyEntries,
+    #[msg("No entry at this index")]
+    EntryNotFound,
+    #[msg("Entry values are limited to 64 bytes")]
+    EntryTooLong,
+    #[msg("Entry values can't be empty and URLs must start with http:// or https://")]
+    InvalidEntry,
+    #[msg("Colors must be a CSS color name or a #RRGGBB hex code")]
+    InvalidColor,
+    #[msg("This account keeps a history, pass the history account")]
+    HistoryRequired,
+    #[msg("Delegates have to wait for the cooldown before updating again")]
+    CooldownActive,
+    #[msg("This delegate used up its updates for today")]
+    DailyQuotaExceeded,
+    #[msg("Only members of the group can do this")]
+    NotAMember,
+    #[msg("This group already has the maximum number of members")]
+    TooManyMembers,
+    #[msg("This key is already a member of the group")]
+    DuplicateMember,
+    #[msg("No such member in this group")]
+    MemberNotFound,
+    #[msg("A group needs at least one admin")]
+    LastAdmin,
+    #[msg("The threshold must be between 1 and the number of members")]
+    InvalidThreshold,
+    #[msg("This member already approved the change")]
+    AlreadyApproved,
+    #[msg("The change doesn't have enough approvals yet")]
+    NotEnoughApprovals,
+    #[msg("The update doesn't match the approved change")]
+    ChangeMismatch,
+    #[msg("This account doesn't hold any known favorites layout")]
+    UnknownFavoritesLayout,
+    #[msg("This account already uses the current layout")]
+    AlreadyUpgraded,
+    #[msg("Sessions need at least one update and an expiry within the next 7 days")]
+    InvalidSession,
+    #[msg("This session has expired")]
+    SessionExpired,
+    #[msg("This session used up its updates")]
+    SessionExhausted,
+    #[msg("Updates through a session can't grow the account, sign as the owner")]
+    SessionCannotGrow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn favorites(color: &str) -> Favorites {
+        Favorites {
+            number: 7,
+            color: color.to_string(),
+            authority: Pubkey::new_unique(),
+            delegates: Vec::new(),
+            pending_authority: None,
+            entries: Vec::new(),
+            history_enabled: false,
+            cooldown_slots: 0,
+            last_update_slot: 0,
+            delegate_daily_quota: 0,
+            reward_claimed: false,
+            created_slot: 0,
+        }
+    }
+
+    fn serialized_len(favorites: &Favorites) -> usize {
+        ANCHOR_DISCRIMINATOR_SIZE + favorites.try_to_vec().unwrap().len()
+    }
+
+    #[test]
+    fn space_matches_serialized_size() {
+        let mut account = favorites("red");
+        assert_eq!(Favorites::space_for("red"), serialized_len(&account));
+        assert_eq!(account.space(), serialized_len(&account));
+
+        account.color = "x".repeat(MAX_COLOR_LEN);
+        account.delegates = vec![
+            Delegate::new(Pubkey::new_unique(), ALL_PERMISSIONS, None),
+            Delegate::new(
+                Pubkey::new_unique(),
+                PERMISSION_UPDATE_COLOR,
+                Some(1_700_000_000),
+            ),
+        ];
+        account.pending_authority = Some(Pubkey::new_unique());
+        account.entries = vec![
+            Entry::Number(42),
+            Entry::Url("https://solana.com".to_string()),
+            Entry::Tag("gm".to_string()),
+        ];
+        assert_eq!(account.space(), serialized_len(&account));
+    }
+
+    #[test]
+    fn space_never_exceeds_the_upper_bound() {
+        let mut account = favorites(&"x".repeat(MAX_COLOR_LEN));
+        account.delegates = (0..MAX_DELEGATES)
+            .map(|_| Delegate::new(Pubkey::new_unique(), ALL_PERMISSIONS, Some(0)))
+            .collect();
+        account.pending_authority = Some(Pubkey::new_unique());
+        account.entries = vec![Entry::Tag("x".repeat(MAX_ENTRY_LEN)); MAX_ENTRIES];
+        assert_eq!(
+            account.space(),
+            ANCHOR_DISCRIMINATOR_SIZE + Favorites::INIT_SPACE
+        );
+    }
+
+    #[test]
+    fn delegate_quota_resets_every_day() {
+        let mut delegate = Delegate::new(Pubkey::new_unique(), ALL_PERMISSIONS, None);
+        let day = 19_000 * SECONDS_PER_DAY;
+
+        delegate.use_quota(2, day).unwrap();
+        delegate.use_quota(2, day + 60).unwrap();
+        assert!(delegate.use_quota(2, day + 120).is_err());
+
+        delegate.use_quota(2, day + SECONDS_PER_DAY).unwrap();
+        assert_eq!(delegate.updates_today, 1);
+
+        // No quota, no limit
+        for _ in 0..10 {
+            delegate.use_quota(0, day + SECONDS_PER_DAY).unwrap();
+        }
+    }
+
+    fn change(new_number: u64) -> Change {
+        Change {
+            old_number: new_number.saturating_sub(1),
+            new_number,
+            old_color: "red".to_string(),
+            new_color: "blue".to_string(),
+            actor: Pubkey::default(),
+            slot: new_number,
+        }
+    }
+
+    fn recent_numbers(history: &FavoritesHistory) -> Vec<u64> {
+        history.recent().map(|change| change.new_number).collect()
+    }
+
+    #[test]
+    fn history_keeps_the_latest_changes() {
+        let mut history = FavoritesHistory {
+            favorites: Pubkey::new_unique(),
+            next: 0,
+            changes: Vec::new(),
+        };
+        assert!(recent_numbers(&history).is_empty());
+
+        for number in 1..=3 {
+            history.record(change(number));
+        }
+        assert_eq!(recent_numbers(&history), [3, 2, 1]);
+
+        // Three more than fit, the first three are gone
+        for number in 4..=HISTORY_CAPACITY as u64 + 3 {
+            history.record(change(number));
+        }
+        assert_eq!(history.changes.len(), HISTORY_CAPACITY);
+        let expected: Vec<u64> = (4..=HISTORY_CAPACITY as u64 + 3).rev().collect();
+        assert_eq!(recent_numbers(&history), expected);
+    }
+
+    #[test]
+    fn full_history_fits_its_account() {
+        let mut history = FavoritesHistory {
+            favorites: Pubkey::new_unique(),
+            next: 0,
+            changes: Vec::new(),
+        };
+        for number in 0..HISTORY_CAPACITY as u64 {
+            let mut change = change(number);
+            change.old_color = "x".repeat(MAX_COLOR_LEN);
+            change.new_color = "x".repeat(MAX_COLOR_LEN);
+            history.record(change);
+        }
+        assert_eq!(
+            history.try_to_vec().unwrap().len(),
+            FavoritesHistory::INIT_SPACE
+        );
+    }
+
+    #[test]
+    fn validates_entries() {
+        assert!(Entry::Number(0).validated().is_ok());
+        assert!(Entry::Url("https://example.com".to_string())
+            .validated()
+            .is_ok());
+        assert!(Entry::Tag("x".repeat(MAX_ENTRY_LEN)).validated().is_ok());
+        assert_eq!(
+            Entry::Color("#ABCDEF".to_string()).validated().unwrap(),
+            Entry::Color("#abcdef".to_string())
+        );
+
+        assert!(Entry::Url("example.com".to_string()).validated().is_err());
+        assert!(Entry::Tag(String::new()).validated().is_err());
+        assert!(Entry::Tag("x".repeat(MAX_ENTRY_LEN + 1))
+            .validated()
+            .is_err());
+        assert!(Entry::Color("blurple".to_string()).validated().is_err());
+    }
+}
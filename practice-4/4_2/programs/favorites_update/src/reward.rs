@@ -0,0 +1,106 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{create_idempotent, Create},
+    token_interface::{mint_to, Mint, MintTo, TokenInterface},
+};
+
+use crate::SetFavorites;
+
+pub const REWARD_DECIMALS: u8 = 6;
+// One whole token for setting favorites the first time
+pub const REWARD_AMOUNT: u64 = 1_000_000;
+
+pub const REWARD_MINT_SEED: &[u8] = b"reward-mint";
+pub const REWARD_AUTHORITY_SEED: &[u8] = b"reward-authority";
+pub const REWARD_CLAIM_SEED: &[u8] = b"reward-claimed";
+
+// Creates the reward mint. Anyone can run it once, the mint authority is a
+// PDA of this program so only `set_favorites` ever mints. Favorites set before
+// it ran earn no reward.
+#[derive(Accounts)]
+pub struct InitRewardMint<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Only signs for the mint, holds no data
+    #[account(seeds = [REWARD_AUTHORITY_SEED], bump)]
+    pub reward_authority: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [REWARD_MINT_SEED],
+        bump,
+        mint::decimals = REWARD_DECIMALS,
+        mint::authority = reward_authority,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+// Marks `user` as rewarded. It is a PDA of the user alone, so it outlives
+// their favorites and closing and setting them again doesn't earn a second
+// reward.
+#[account]
+#[derive(InitSpace)]
+pub struct RewardClaim {
+    pub claimed: bool,
+}
+
+// Mints the reward for `set_favorites` unless the user already got it. It is
+// skipped without an error when the reward accounts weren't passed or the
+// mint doesn't exist yet. Returns whether it minted.
+pub fn mint_reward_once(
+    accounts: &mut SetFavorites,
+    reward_authority_bump: Option<u8>,
+) -> Result<bool> {
+    let (
+        Some(reward_authority_bump),
+        Some(reward_authority),
+        Some(reward_mint),
+        Some(user_reward_account),
+        Some(associated_token_program),
+        Some(token_program),
+        Some(reward_claim),
+    ) = (
+        reward_authority_bump,
+        accounts.reward_authority.as_ref(),
+        accounts.reward_mint.as_ref(),
+        accounts.user_reward_account.as_ref(),
+        accounts.associated_token_program.as_ref(),
+        accounts.token_program.as_ref(),
+        accounts.reward_claim.as_mut(),
+    )
+    else {
+        return Ok(false);
+    };
+    if reward_mint.data_is_empty() || reward_claim.claimed {
+        return Ok(false);
+    }
+
+    create_idempotent(CpiContext::new(
+        associated_token_program.to_account_info(),
+        Create {
+            payer: accounts.user.to_account_info(),
+            associated_token: user_reward_account.to_account_info(),
+            authority: accounts.user.to_account_info(),
+            mint: reward_mint.to_account_info(),
+            system_program: accounts.system_program.to_account_info(),
+            token_program: token_program.to_account_info(),
+        },
+    ))?;
+
+    let signer_seeds: [&[&[u8]]; 1] = [&[REWARD_AUTHORITY_SEED, &[reward_authority_bump]]];
+    let cpi_context = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        MintTo {
+            mint: reward_mint.to_account_info(),
+            to: user_reward_account.to_account_info(),
+            authority: reward_authority.to_account_info(),
+        },
+        &signer_seeds,
+    );
+    mint_to(cpi_context, REWARD_AMOUNT)?;
+    reward_claim.claimed = true;
+    Ok(true)
+}
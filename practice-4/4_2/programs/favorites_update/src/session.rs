@@ -0,0 +1,163 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    ErrorCode, Favorites, ANCHOR_DISCRIMINATOR_SIZE, PERMISSION_UPDATE_COLOR,
+    PERMISSION_UPDATE_ENTRIES, PERMISSION_UPDATE_NUMBER, SECONDS_PER_DAY,
+};
+
+// Sessions are meant for a browser tab, not as long lived delegates
+pub const MAX_SESSION_SECONDS: i64 = 7 * SECONDS_PER_DAY;
+// What a session can be allowed to do, it never manages delegates
+pub const SESSION_PERMISSIONS: u8 =
+    PERMISSION_UPDATE_NUMBER | PERMISSION_UPDATE_COLOR | PERMISSION_UPDATE_ENTRIES;
+
+// An ephemeral key the authority registered so a front end can sign updates
+// without asking the wallet every time. Works like a delegate with an expiry
+// and a fixed number of updates, kept in its own PDA so sessions don't grow
+// the favorites account.
+#[account]
+#[derive(InitSpace)]
+pub struct Session {
+    pub favorites: Pubkey,
+    pub session_key: Pubkey,
+    // The authority that created the session and paid its rent. The session
+    // stops working once the favorites have another authority.
+    pub authority: Pubkey,
+    // `Favorites::created_slot` of the account the session was created for
+    pub favorites_created_slot: u64,
+    pub permissions: u8,
+    pub expires_at: i64,
+    pub updates_left: u16,
+}
+
+impl Session {
+    pub fn validate(permissions: u8, expires_at: i64, max_updates: u16, now: i64) -> Result<()> {
+        require!(
+            permissions != 0 && permissions & !SESSION_PERMISSIONS == 0,
+            ErrorCode::InvalidPermissions
+        );
+        require!(
+            expires_at > now && expires_at - now <= MAX_SESSION_SECONDS,
+            ErrorCode::InvalidSession
+        );
+        require!(max_updates != 0, ErrorCode::InvalidSession);
+        Ok(())
+    }
+
+    // The permissions the session key has on `favorites` right now. Counts
+    // one update against the budget, so only call it for changes.
+    pub fn use_update(&mut self, favorites: &Favorites, now: i64) -> Result<u8> {
+        require_keys_eq!(self.authority, favorites.authority, ErrorCode::Unauthorized);
+        require!(
+            self.favorites_created_slot == favorites.created_slot,
+            ErrorCode::Unauthorized
+        );
+        require!(now < self.expires_at, ErrorCode::SessionExpired);
+        require!(self.updates_left != 0, ErrorCode::SessionExhausted);
+        self.updates_left -= 1;
+        Ok(self.permissions)
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(session_key: Pubkey)]
+pub struct CreateSession<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"favorites", user.key().as_ref()],
+        bump,
+        constraint = favorites.authority == authority.key() @ ErrorCode::Unauthorized,
+    )]
+    pub favorites: Account<'info, Favorites>,
+    /// CHECK: Only used to derive the favorites PDA
+    pub user: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = ANCHOR_DISCRIMINATOR_SIZE + Session::INIT_SPACE,
+        seeds = [b"session", favorites.key().as_ref(), session_key.as_ref()],
+        bump,
+    )]
+    pub session: Account<'info, Session>,
+    pub system_program: Program<'info, System>,
+}
+
+// The authority revokes a session, or the session key gives it up once the
+// front end is done with it. The rent goes back to whoever created it.
+#[derive(Accounts)]
+pub struct CloseSession<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        close = creator,
+        constraint = signer.key() == session.authority
+            || signer.key() == session.session_key @ ErrorCode::Unauthorized,
+    )]
+    pub session: Account<'info, Session>,
+    #[account(mut, address = session.authority)]
+    pub creator: SystemAccount<'info>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(favorites: &Favorites, updates_left: u16) -> Session {
+        Session {
+            favorites: Pubkey::new_unique(),
+            session_key: Pubkey::new_unique(),
+            authority: favorites.authority,
+            favorites_created_slot: favorites.created_slot,
+            permissions: PERMISSION_UPDATE_NUMBER,
+            expires_at: 1_000,
+            updates_left,
+        }
+    }
+
+    #[test]
+    fn sessions_run_out_of_updates_and_time() {
+        let favorites = Favorites::from_v0(
+            crate::FavoritesV0 {
+                number: 1,
+                color: "red".to_string(),
+            },
+            Pubkey::new_unique(),
+        );
+
+        let mut limited = session(&favorites, 2);
+        assert_eq!(
+            limited.use_update(&favorites, 10).unwrap(),
+            PERMISSION_UPDATE_NUMBER
+        );
+        limited.use_update(&favorites, 20).unwrap();
+        assert!(limited.use_update(&favorites, 30).is_err());
+
+        assert!(session(&favorites, 2)
+            .use_update(&favorites, 1_000)
+            .is_err());
+
+        // Handing the favorites to someone else ends the old owner's sessions
+        let mut transferred = favorites.clone();
+        transferred.authority = Pubkey::new_unique();
+        assert!(session(&favorites, 2).use_update(&transferred, 10).is_err());
+
+        // So does closing them and creating them again
+        let mut recreated = favorites.clone();
+        recreated.created_slot += 1;
+        assert!(session(&favorites, 2).use_update(&recreated, 10).is_err());
+    }
+
+    #[test]
+    fn validates_new_sessions() {
+        let now = 1_000;
+        Session::validate(SESSION_PERMISSIONS, now + 60, 5, now).unwrap();
+        assert!(Session::validate(0, now + 60, 5, now).is_err());
+        assert!(Session::validate(crate::ALL_PERMISSIONS, now + 60, 5, now).is_err());
+        assert!(Session::validate(SESSION_PERMISSIONS, now, 5, now).is_err());
+        assert!(
+            Session::validate(SESSION_PERMISSIONS, now + MAX_SESSION_SECONDS + 1, 5, now).is_err()
+        );
+        assert!(Session::validate(SESSION_PERMISSIONS, now + 60, 0, now).is_err());
+    }
+}
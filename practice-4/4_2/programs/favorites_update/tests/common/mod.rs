@@ -0,0 +1,213 @@
+//! Bank setup and instructions shared by the integration tests of the
+//! favorites program.
+#![allow(dead_code)]
+
+use anchor_lang::{
+    prelude::AccountInfo, solana_program::entrypoint::ProgramResult, AccountDeserialize,
+    InstructionData, ToAccountMetas,
+};
+use anchor_spl::{associated_token::get_associated_token_address, token::spl_token};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+fn favorites_entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor's entrypoint wants the account slice to outlive the call.
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    favorites_update::entry(program_id, accounts, data)
+}
+
+pub fn favorites_address(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"favorites", user.as_ref()], &favorites_update::ID).0
+}
+
+pub fn reward_mint() -> Pubkey {
+    Pubkey::find_program_address(&[favorites_update::REWARD_MINT_SEED], &favorites_update::ID).0
+}
+
+pub fn reward_authority() -> Pubkey {
+    Pubkey::find_program_address(
+        &[favorites_update::REWARD_AUTHORITY_SEED],
+        &favorites_update::ID,
+    )
+    .0
+}
+
+pub fn reward_account(owner: &Pubkey) -> Pubkey {
+    get_associated_token_address(owner, &reward_mint())
+}
+
+pub fn reward_claim(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[favorites_update::REWARD_CLAIM_SEED, user.as_ref()],
+        &favorites_update::ID,
+    )
+    .0
+}
+
+/// The favorites program, for tests that add more programs or accounts.
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new(
+        "favorites_update",
+        favorites_update::ID,
+        processor!(favorites_entry),
+    )
+}
+
+/// Starts a bank with the reward mint created and a funded user.
+pub async fn start() -> (ProgramTestContext, Keypair) {
+    let (mut context, user) = start_without_reward_mint().await;
+    let payer = context.payer.insecure_clone();
+    send(&mut context, &[init_reward_mint(&payer.pubkey())], &payer)
+        .await
+        .unwrap();
+    (context, user)
+}
+
+/// Starts a bank with a funded user, before anyone ran `init_reward_mint`.
+pub async fn start_without_reward_mint() -> (ProgramTestContext, Keypair) {
+    start_with_accounts(|_, _| {}).await
+}
+
+/// Like `start_without_reward_mint`, with the user's favorites account
+/// already holding `favorites(user)`.
+pub async fn start_with_favorites(
+    favorites: impl FnOnce(&Pubkey) -> Account,
+) -> (ProgramTestContext, Keypair) {
+    start_with_accounts(|program_test, user| {
+        program_test.add_account(favorites_address(user), favorites(user));
+    })
+    .await
+}
+
+async fn start_with_accounts(
+    add_accounts: impl FnOnce(&mut ProgramTest, &Pubkey),
+) -> (ProgramTestContext, Keypair) {
+    let user = Keypair::new();
+    let mut program_test = program_test();
+    program_test.add_account(
+        user.pubkey(),
+        Account::new(1_000_000_000, 0, &solana_sdk::system_program::ID),
+    );
+    add_accounts(&mut program_test, &user.pubkey());
+    (program_test.start_with_context().await, user)
+}
+
+/// Sends `instructions` paid by the bank's payer and signed by `signer`.
+pub async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signer: &Keypair,
+) -> Result<(), BanksClientError> {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &[&context.payer, signer],
+        blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+pub fn init_reward_mint(payer: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::InitRewardMint {
+            payer: *payer,
+            reward_authority: reward_authority(),
+            reward_mint: reward_mint(),
+            token_program: spl_token::ID,
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::InitRewardMint {}.data(),
+    }
+}
+
+/// `set_favorites` with every reward account passed.
+pub fn set_instruction(user: &Pubkey, number: u64, color: &str) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::SetFavorites {
+            user: *user,
+            favorites: favorites_address(user),
+            system_program: anchor_lang::system_program::ID,
+            reward_authority: Some(reward_authority()),
+            reward_mint: Some(reward_mint()),
+            user_reward_account: Some(reward_account(user)),
+            associated_token_program: Some(anchor_spl::associated_token::ID),
+            token_program: Some(spl_token::ID),
+            reward_claim: Some(reward_claim(user)),
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::SetFavorites {
+            number,
+            color: color.to_string(),
+        }
+        .data(),
+    }
+}
+
+/// `update_favorites` of `user`'s favorites, signed by `authority`.
+pub fn update_instruction(
+    user: &Pubkey,
+    authority: &Pubkey,
+    number: u64,
+    color: &str,
+) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::UpdateFavorites {
+            favorites: favorites_address(user),
+            user: *user,
+            authority: *authority,
+            system_program: anchor_lang::system_program::ID,
+            history: None,
+            session: None,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::UpdateFavorites {
+            number,
+            color: color.to_string(),
+        }
+        .data(),
+    }
+}
+
+/// `close_favorites` by the owner, refunding the rent to themselves.
+pub fn close_instruction(user: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::CloseFavorites {
+            authority: *user,
+            favorites: favorites_address(user),
+            user: *user,
+            recipient: *user,
+            history: None,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::CloseFavorites {}.data(),
+    }
+}
+
+pub async fn get<T: AccountDeserialize>(context: &mut ProgramTestContext, address: Pubkey) -> T {
+    let account = context
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .unwrap();
+    T::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+pub fn custom_error(result: Result<(), BanksClientError>) -> u32 {
+    match result.unwrap_err().unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => code,
+        err => panic!("unexpected error {err:?}"),
+    }
+}
@@ -0,0 +1,171 @@
+//! Reads favorites through `get_favorites`, both from another program via CPI
+//! and from a client simulating the instruction.
+
+use anchor_lang::{
+    prelude::{AccountInfo, ProgramError},
+    solana_program::{
+        entrypoint::ProgramResult,
+        program::{get_return_data, invoke, set_return_data},
+    },
+    AnchorDeserialize, InstructionData, ToAccountMetas,
+};
+use favorites_update::Favorites;
+use solana_program_test::{processor, ProgramTestContext};
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+mod common;
+
+use common::{favorites_address, set_instruction};
+
+const CONSUMER_ID: Pubkey = Pubkey::new_from_array([7; 32]);
+
+/// Stands in for another program: reads the favorites of `user` through CPI,
+/// fails unless the number matches its instruction data and passes the color
+/// on as its own return data.
+fn consumer_entry(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let [favorites_program, favorites, user] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    let expected_number = u64::from_le_bytes(
+        data.try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?,
+    );
+
+    let instruction = Instruction {
+        program_id: *favorites_program.key,
+        accounts: favorites_update::accounts::GetFavorites {
+            favorites: *favorites.key,
+            user: *user.key,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::GetFavorites {}.data(),
+    };
+    invoke(&instruction, &[favorites.clone(), user.clone()])?;
+
+    let (program_id, return_data) = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+    if program_id != favorites_update::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let read = Favorites::deserialize(&mut return_data.as_slice())
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if read.number != expected_number {
+        return Err(ProgramError::Custom(0));
+    }
+    set_return_data(read.color.as_bytes());
+    Ok(())
+}
+
+async fn start() -> ProgramTestContext {
+    let mut program_test = common::program_test();
+    program_test.add_program("consumer", CONSUMER_ID, processor!(consumer_entry));
+    program_test.start_with_context().await
+}
+
+// The payer doubles as the user, it already has lamports. Processed on the
+// bank directly like the reads after it: `send` can return while the queued
+// transaction still holds its account locks.
+async fn set_favorites(context: &mut ProgramTestContext, user: &Keypair, number: u64, color: &str) {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[set_instruction(&user.pubkey(), number, color)],
+        Some(&user.pubkey()),
+        &[user],
+        blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap()
+        .result
+        .unwrap();
+}
+
+fn consumer_instruction(user: &Pubkey, expected_number: u64) -> Instruction {
+    Instruction::new_with_bytes(
+        CONSUMER_ID,
+        &expected_number.to_le_bytes(),
+        vec![
+            solana_sdk::instruction::AccountMeta::new_readonly(favorites_update::ID, false),
+            solana_sdk::instruction::AccountMeta::new_readonly(favorites_address(user), false),
+            solana_sdk::instruction::AccountMeta::new_readonly(*user, false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn another_program_reads_favorites_through_cpi() {
+    let mut context = start().await;
+    let user = context.payer.insecure_clone();
+    set_favorites(&mut context, &user, 42, "Teal").await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[consumer_instruction(&user.pubkey(), 42)],
+        Some(&user.pubkey()),
+        &[&user],
+        context.last_blockhash,
+    );
+    let result = context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    result.result.unwrap();
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, CONSUMER_ID);
+    assert_eq!(return_data.data, b"teal");
+
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[consumer_instruction(&user.pubkey(), 41)],
+        Some(&user.pubkey()),
+        &[&user],
+        blockhash,
+    );
+    assert!(context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn clients_read_favorites_by_simulating() {
+    let mut context = start().await;
+    let user = context.payer.insecure_clone();
+    set_favorites(&mut context, &user, 7, "#00FF7F").await;
+
+    let instruction = Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::GetFavorites {
+            favorites: favorites_address(&user.pubkey()),
+            user: user.pubkey(),
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::GetFavorites {}.data(),
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&user.pubkey()),
+        &[&user],
+        context.last_blockhash,
+    );
+    let simulation = context
+        .banks_client
+        .simulate_transaction(transaction)
+        .await
+        .unwrap();
+    simulation.result.unwrap().unwrap();
+
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, favorites_update::ID);
+    let favorites = Favorites::deserialize(&mut return_data.data.as_slice()).unwrap();
+    assert_eq!(favorites.number, 7);
+    assert_eq!(favorites.color, "#00ff7f");
+    assert_eq!(favorites.authority, user.pubkey());
+}
@@ -0,0 +1,114 @@
+//! The cooldown and daily quota of delegates apply to every handler that
+//! changes the favorites, not only `update_favorites`.
+
+mod common;
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use common::{custom_error, favorites_address, get, send, set_instruction, start};
+use favorites_update::{Entry, ErrorCode, Favorites, PERMISSION_UPDATE_ENTRIES};
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+fn set_limits(user: &Pubkey, cooldown_slots: u64, delegate_daily_quota: u16) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::SetAuthority {
+            authority: *user,
+            favorites: favorites_address(user),
+            user: *user,
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::SetLimits {
+            cooldown_slots,
+            delegate_daily_quota,
+        }
+        .data(),
+    }
+}
+
+fn set_delegate(user: &Pubkey, delegate: &Pubkey, permissions: u8) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::ManageDelegates {
+            favorites: favorites_address(user),
+            user: *user,
+            authority: *user,
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::SetDelegate {
+            delegate: *delegate,
+            permissions,
+            expires_at: None,
+        }
+        .data(),
+    }
+}
+
+fn add_entry(user: &Pubkey, authority: &Pubkey, entry: Entry) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::UpdateFavorites {
+            favorites: favorites_address(user),
+            user: *user,
+            authority: *authority,
+            system_program: anchor_lang::system_program::ID,
+            history: None,
+            session: None,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::AddEntry { entry }.data(),
+    }
+}
+
+#[tokio::test]
+async fn delegates_hit_the_daily_quota_through_add_entry() {
+    let (mut context, user) = start().await;
+    let delegate = Keypair::new();
+    let owner = user.pubkey();
+    let setup = [
+        set_instruction(&owner, 1, "red"),
+        set_limits(&owner, 0, 2),
+        set_delegate(&owner, &delegate.pubkey(), PERMISSION_UPDATE_ENTRIES),
+    ];
+    send(&mut context, &setup, &user).await.unwrap();
+    // The delegate pays for the entries it adds
+    let fund = solana_sdk::system_instruction::transfer(&owner, &delegate.pubkey(), 100_000_000);
+    send(&mut context, &[fund], &user).await.unwrap();
+
+    for number in 0..2 {
+        send(
+            &mut context,
+            &[add_entry(&owner, &delegate.pubkey(), Entry::Number(number))],
+            &delegate,
+        )
+        .await
+        .unwrap();
+    }
+    let result = send(
+        &mut context,
+        &[add_entry(&owner, &delegate.pubkey(), Entry::Number(2))],
+        &delegate,
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        u32::from(ErrorCode::DailyQuotaExceeded)
+    );
+
+    // The owner is never limited
+    send(
+        &mut context,
+        &[add_entry(&owner, &owner, Entry::Number(3))],
+        &user,
+    )
+    .await
+    .unwrap();
+    let favorites: Favorites = get(&mut context, favorites_address(&owner)).await;
+    assert_eq!(favorites.entries.len(), 3);
+    assert_eq!(favorites.delegates[0].updates_today, 2);
+}
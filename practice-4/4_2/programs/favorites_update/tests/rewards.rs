@@ -0,0 +1,172 @@
+//! Mints the reward token on the first `set_favorites` of a user. The claim
+//! outlives the favorites, closing keeps the reward and setting them again
+//! doesn't mint another one.
+
+mod common;
+
+use anchor_spl::token::{spl_token, Mint, TokenAccount};
+use common::{
+    close_instruction, favorites_address, get, init_reward_mint, reward_account, reward_authority,
+    reward_claim, reward_mint, send, set_instruction, start, start_without_reward_mint,
+};
+use favorites_update::{Favorites, RewardClaim, REWARD_AMOUNT};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    program_pack::Pack,
+    signature::{Keypair, Signer},
+};
+
+async fn supply(context: &mut ProgramTestContext) -> u64 {
+    get::<Mint>(context, reward_mint()).await.supply
+}
+
+#[tokio::test]
+async fn set_favorites_mints_the_reward_once() {
+    let (mut context, user) = start().await;
+
+    send(
+        &mut context,
+        &[set_instruction(&user.pubkey(), 7, "teal")],
+        &user,
+    )
+    .await
+    .unwrap();
+    let favorites: Favorites = get(&mut context, favorites_address(&user.pubkey())).await;
+    assert!(favorites.reward_claimed);
+    let tokens: TokenAccount = get(&mut context, reward_account(&user.pubkey())).await;
+    assert_eq!(tokens.amount, REWARD_AMOUNT);
+    let mint: Mint = get(&mut context, reward_mint()).await;
+    assert_eq!(mint.supply, REWARD_AMOUNT);
+    assert_eq!(mint.mint_authority, Some(reward_authority()).into());
+    let claim: RewardClaim = get(&mut context, reward_claim(&user.pubkey())).await;
+    assert!(claim.claimed);
+
+    // The account exists now, setting it again fails
+    assert!(send(
+        &mut context,
+        &[set_instruction(&user.pubkey(), 8, "red")],
+        &user
+    )
+    .await
+    .is_err());
+
+    // Closing and setting them again works, but the claim is still there
+    send(&mut context, &[close_instruction(&user.pubkey())], &user)
+        .await
+        .unwrap();
+    send(
+        &mut context,
+        &[set_instruction(&user.pubkey(), 9, "red")],
+        &user,
+    )
+    .await
+    .unwrap();
+    let favorites: Favorites = get(&mut context, favorites_address(&user.pubkey())).await;
+    assert!(!favorites.reward_claimed);
+    let tokens: TokenAccount = get(&mut context, reward_account(&user.pubkey())).await;
+    assert_eq!(tokens.amount, REWARD_AMOUNT);
+    assert_eq!(supply(&mut context).await, REWARD_AMOUNT);
+}
+
+#[tokio::test]
+async fn closing_after_spending_the_reward_keeps_it_spent() {
+    let (mut context, user) = start().await;
+    send(
+        &mut context,
+        &[set_instruction(&user.pubkey(), 7, "teal")],
+        &user,
+    )
+    .await
+    .unwrap();
+
+    let other = Keypair::new();
+    let other_account = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        solana_sdk::system_instruction::create_account(
+            &context.payer.pubkey(),
+            &other_account.pubkey(),
+            rent.minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_account3(
+            &spl_token::ID,
+            &other_account.pubkey(),
+            &reward_mint(),
+            &other.pubkey(),
+        )
+        .unwrap(),
+    ];
+    send(&mut context, &instructions, &other_account)
+        .await
+        .unwrap();
+    let transfer = spl_token::instruction::transfer(
+        &spl_token::ID,
+        &reward_account(&user.pubkey()),
+        &other_account.pubkey(),
+        &user.pubkey(),
+        &[],
+        REWARD_AMOUNT,
+    )
+    .unwrap();
+    send(&mut context, &[transfer], &user).await.unwrap();
+
+    send(&mut context, &[close_instruction(&user.pubkey())], &user)
+        .await
+        .unwrap();
+    assert!(context
+        .banks_client
+        .get_account(favorites_address(&user.pubkey()))
+        .await
+        .unwrap()
+        .is_none());
+    let tokens: TokenAccount = get(&mut context, other_account.pubkey()).await;
+    assert_eq!(tokens.amount, REWARD_AMOUNT);
+    assert_eq!(supply(&mut context).await, REWARD_AMOUNT);
+}
+
+#[tokio::test]
+async fn favorites_are_set_without_a_reward_mint() {
+    let (mut context, user) = start_without_reward_mint().await;
+
+    // The mint doesn't exist yet, nothing is minted or claimed
+    send(
+        &mut context,
+        &[set_instruction(&user.pubkey(), 7, "teal")],
+        &user,
+    )
+    .await
+    .unwrap();
+    let favorites: Favorites = get(&mut context, favorites_address(&user.pubkey())).await;
+    assert!(!favorites.reward_claimed);
+    let claim: RewardClaim = get(&mut context, reward_claim(&user.pubkey())).await;
+    assert!(!claim.claimed);
+    send(&mut context, &[close_instruction(&user.pubkey())], &user)
+        .await
+        .unwrap();
+
+    // Callers from before the reward pass only the first three accounts
+    let payer = context.payer.insecure_clone();
+    send(&mut context, &[init_reward_mint(&payer.pubkey())], &payer)
+        .await
+        .unwrap();
+    let mut bare = set_instruction(&user.pubkey(), 8, "red");
+    bare.accounts.truncate(3);
+    send(&mut context, &[bare], &user).await.unwrap();
+    assert_eq!(supply(&mut context).await, 0);
+    send(&mut context, &[close_instruction(&user.pubkey())], &user)
+        .await
+        .unwrap();
+
+    // The reward wasn't claimed, so the next set with the accounts mints it
+    send(
+        &mut context,
+        &[set_instruction(&user.pubkey(), 9, "tan")],
+        &user,
+    )
+    .await
+    .unwrap();
+    let tokens: TokenAccount = get(&mut context, reward_account(&user.pubkey())).await;
+    assert_eq!(tokens.amount, REWARD_AMOUNT);
+}
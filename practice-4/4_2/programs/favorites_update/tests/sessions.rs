@@ -0,0 +1,422 @@
+//! Registers session keys and signs `update_favorites` with them until they
+//! run out of updates, expire or are closed.
+
+mod common;
+
+use anchor_lang::{prelude::Clock, InstructionData, ToAccountMetas};
+use common::{close_instruction, custom_error, favorites_address, get, send, set_instruction};
+use favorites_update::{
+    ErrorCode, Favorites, Session, PERMISSION_UPDATE_COLOR, PERMISSION_UPDATE_NUMBER,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+fn session_address(user: &Pubkey, session_key: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"session",
+            favorites_address(user).as_ref(),
+            session_key.as_ref(),
+        ],
+        &favorites_update::ID,
+    )
+    .0
+}
+
+/// Starts a bank where a funded user has set their favorites to 1 and red.
+async fn start() -> (ProgramTestContext, Keypair) {
+    let (mut context, user) = common::start().await;
+    send(
+        &mut context,
+        &[set_instruction(&user.pubkey(), 1, "red")],
+        &user,
+    )
+    .await
+    .unwrap();
+    (context, user)
+}
+
+fn create_session(
+    user: &Pubkey,
+    session_key: &Pubkey,
+    expires_at: i64,
+    max_updates: u16,
+) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::CreateSession {
+            authority: *user,
+            favorites: favorites_address(user),
+            user: *user,
+            session: session_address(user, session_key),
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::CreateSession {
+            session_key: *session_key,
+            permissions: PERMISSION_UPDATE_NUMBER | PERMISSION_UPDATE_COLOR,
+            expires_at,
+            max_updates,
+        }
+        .data(),
+    }
+}
+
+/// An update signed by `session_key`, which holds no lamports.
+fn update_with_session(
+    user: &Pubkey,
+    session_key: &Pubkey,
+    number: u64,
+    color: &str,
+    with_session: bool,
+) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::UpdateFavorites {
+            favorites: favorites_address(user),
+            user: *user,
+            authority: *session_key,
+            system_program: anchor_lang::system_program::ID,
+            history: None,
+            session: with_session.then(|| session_address(user, session_key)),
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::UpdateFavorites {
+            number,
+            color: color.to_string(),
+        }
+        .data(),
+    }
+}
+
+/// `close_session` signed by `signer`, the authority or the session key.
+fn close_session(user: &Pubkey, session_key: &Pubkey, signer: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::CloseSession {
+            signer: *signer,
+            session: session_address(user, session_key),
+            creator: *user,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::CloseSession {}.data(),
+    }
+}
+
+async fn now(context: &mut ProgramTestContext) -> i64 {
+    context
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap()
+        .unix_timestamp
+}
+
+#[tokio::test]
+async fn session_keys_update_until_the_budget_is_used() {
+    let (mut context, user) = start().await;
+    let session_key = Keypair::new();
+    let expires_at = now(&mut context).await + 3_600;
+    send(
+        &mut context,
+        &[create_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            expires_at,
+            2,
+        )],
+        &user,
+    )
+    .await
+    .unwrap();
+
+    // Without its session account the key is nobody
+    let result = send(
+        &mut context,
+        &[update_with_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            2,
+            "tan",
+            false,
+        )],
+        &session_key,
+    )
+    .await;
+    assert_eq!(custom_error(result), u32::from(ErrorCode::Unauthorized));
+
+    for (number, color) in [(2, "tan"), (3, "red")] {
+        send(
+            &mut context,
+            &[update_with_session(
+                &user.pubkey(),
+                &session_key.pubkey(),
+                number,
+                color,
+                true,
+            )],
+            &session_key,
+        )
+        .await
+        .unwrap();
+    }
+    let favorites: Favorites = get(&mut context, favorites_address(&user.pubkey())).await;
+    assert_eq!(favorites.number, 3);
+    let session: Session = get(
+        &mut context,
+        session_address(&user.pubkey(), &session_key.pubkey()),
+    )
+    .await;
+    assert_eq!(session.updates_left, 0);
+
+    let result = send(
+        &mut context,
+        &[update_with_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            4,
+            "tan",
+            true,
+        )],
+        &session_key,
+    )
+    .await;
+    assert_eq!(custom_error(result), u32::from(ErrorCode::SessionExhausted));
+}
+
+#[tokio::test]
+async fn session_keys_never_pay_for_growth() {
+    let (mut context, user) = start().await;
+    let session_key = Keypair::new();
+    let expires_at = now(&mut context).await + 3_600;
+    send(
+        &mut context,
+        &[create_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            expires_at,
+            10,
+        )],
+        &user,
+    )
+    .await
+    .unwrap();
+
+    let result = send(
+        &mut context,
+        &[update_with_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            2,
+            "#00ff7f",
+            true,
+        )],
+        &session_key,
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        u32::from(ErrorCode::SessionCannotGrow)
+    );
+
+    // Rent the owner put in stays in the account when a session shrinks it,
+    // so the session can grow it back that far
+    send(
+        &mut context,
+        &[update_with_session(
+            &user.pubkey(),
+            &user.pubkey(),
+            2,
+            "#00ff7f",
+            false,
+        )],
+        &user,
+    )
+    .await
+    .unwrap();
+    for color in ["tan", "#00ff7f"] {
+        send(
+            &mut context,
+            &[update_with_session(
+                &user.pubkey(),
+                &session_key.pubkey(),
+                3,
+                color,
+                true,
+            )],
+            &session_key,
+        )
+        .await
+        .unwrap();
+    }
+    let favorites: Favorites = get(&mut context, favorites_address(&user.pubkey())).await;
+    assert_eq!(favorites.color, "#00ff7f");
+    assert_eq!(
+        context
+            .banks_client
+            .get_balance(session_key.pubkey())
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn sessions_expire_with_the_clock() {
+    let (mut context, user) = start().await;
+    let session_key = Keypair::new();
+    let expires_at = now(&mut context).await + 60;
+    send(
+        &mut context,
+        &[create_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            expires_at,
+            10,
+        )],
+        &user,
+    )
+    .await
+    .unwrap();
+
+    send(
+        &mut context,
+        &[update_with_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            2,
+            "tan",
+            true,
+        )],
+        &session_key,
+    )
+    .await
+    .unwrap();
+
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = expires_at;
+    context.set_sysvar(&clock);
+    let result = send(
+        &mut context,
+        &[update_with_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            3,
+            "red",
+            true,
+        )],
+        &session_key,
+    )
+    .await;
+    assert_eq!(custom_error(result), u32::from(ErrorCode::SessionExpired));
+}
+
+#[tokio::test]
+async fn sessions_end_with_the_favorites_they_were_created_for() {
+    let (mut context, user) = start().await;
+    let session_key = Keypair::new();
+    let expires_at = now(&mut context).await + 3_600;
+    let create = || create_session(&user.pubkey(), &session_key.pubkey(), expires_at, 10);
+    send(&mut context, &[create()], &user).await.unwrap();
+
+    send(&mut context, &[close_instruction(&user.pubkey())], &user)
+        .await
+        .unwrap();
+    let slot = context.banks_client.get_root_slot().await.unwrap();
+    context.warp_to_slot(slot + 2).unwrap();
+    send(
+        &mut context,
+        &[set_instruction(&user.pubkey(), 5, "red")],
+        &user,
+    )
+    .await
+    .unwrap();
+
+    let update = || update_with_session(&user.pubkey(), &session_key.pubkey(), 6, "tan", true);
+    let result = send(&mut context, &[update()], &session_key).await;
+    assert_eq!(custom_error(result), u32::from(ErrorCode::Unauthorized));
+
+    // The stale session is still closed the usual way, then the key can get
+    // a new one
+    let close = close_session(&user.pubkey(), &session_key.pubkey(), &user.pubkey());
+    send(&mut context, &[close, create()], &user).await.unwrap();
+    send(&mut context, &[update()], &session_key).await.unwrap();
+    let favorites: Favorites = get(&mut context, favorites_address(&user.pubkey())).await;
+    assert_eq!(favorites.number, 6);
+}
+
+#[tokio::test]
+async fn closing_a_session_refunds_the_authority() {
+    let (mut context, user) = start().await;
+    let session_key = Keypair::new();
+    let expires_at = now(&mut context).await + 3_600;
+
+    // Sessions can't outlive a week
+    let result = send(
+        &mut context,
+        &[create_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            expires_at + favorites_update::MAX_SESSION_SECONDS,
+            10,
+        )],
+        &user,
+    )
+    .await;
+    assert_eq!(custom_error(result), u32::from(ErrorCode::InvalidSession));
+
+    let before = context
+        .banks_client
+        .get_balance(user.pubkey())
+        .await
+        .unwrap();
+    send(
+        &mut context,
+        &[create_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            expires_at,
+            10,
+        )],
+        &user,
+    )
+    .await
+    .unwrap();
+
+    // The session key gives it up itself, the rent goes to the authority
+    let session = session_address(&user.pubkey(), &session_key.pubkey());
+    let close = close_session(&user.pubkey(), &session_key.pubkey(), &session_key.pubkey());
+    send(&mut context, &[close], &session_key).await.unwrap();
+    assert!(context
+        .banks_client
+        .get_account(session)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        context
+            .banks_client
+            .get_balance(user.pubkey())
+            .await
+            .unwrap(),
+        before
+    );
+
+    assert!(send(
+        &mut context,
+        &[update_with_session(
+            &user.pubkey(),
+            &session_key.pubkey(),
+            2,
+            "tan",
+            true
+        )],
+        &session_key,
+    )
+    .await
+    .is_err());
+}
@@ -0,0 +1,163 @@
+//! Loads favorites accounts written by older versions of the program and
+//! upgrades them to the current layout with `upgrade_favorites`.
+
+mod common;
+
+use anchor_lang::{
+    AccountDeserialize, AnchorSerialize, Discriminator, InstructionData, ToAccountMetas,
+};
+use common::{favorites_address, send, start_with_favorites, update_instruction};
+use favorites_update::{Favorites, FavoritesLayout, FavoritesV0, FavoritesV1};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    account::Account,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+
+/// An account as the old program left it: allocated at the layout's
+/// `INIT_SPACE`, with the unused tail zeroed.
+fn fixture(layout: &impl AnchorSerialize, space: usize) -> Account {
+    let mut data = Favorites::DISCRIMINATOR.to_vec();
+    layout.serialize(&mut data).unwrap();
+    data.resize(space, 0);
+    Account {
+        lamports: Rent::default().minimum_balance(space),
+        data,
+        owner: favorites_update::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn upgrade_instruction(user: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: favorites_update::ID,
+        accounts: favorites_update::accounts::UpgradeFavorites {
+            user: *user,
+            favorites: favorites_address(user),
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: favorites_update::instruction::UpgradeFavorites {}.data(),
+    }
+}
+
+async fn read(context: &mut ProgramTestContext, user: &Pubkey) -> (Account, Favorites) {
+    let account = context
+        .banks_client
+        .get_account(favorites_address(user))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        Favorites::layout_of(&account.data).unwrap(),
+        FavoritesLayout::CURRENT
+    );
+    let favorites = Favorites::try_deserialize(&mut account.data.as_slice()).unwrap();
+    (account, favorites)
+}
+
+#[tokio::test]
+async fn upgrades_accounts_of_the_first_program() {
+    let (mut context, user) = start_with_favorites(|_| {
+        fixture(
+            &FavoritesV0 {
+                number: 23,
+                color: "red".to_string(),
+            },
+            Favorites::V0_SPACE,
+        )
+    })
+    .await;
+
+    // Old accounts don't deserialize until they are upgraded
+    assert!(send(
+        &mut context,
+        &[update_instruction(
+            &user.pubkey(),
+            &user.pubkey(),
+            24,
+            "red"
+        )],
+        &user,
+    )
+    .await
+    .is_err());
+
+    send(&mut context, &[upgrade_instruction(&user.pubkey())], &user)
+        .await
+        .unwrap();
+    let (account, favorites) = read(&mut context, &user.pubkey()).await;
+    assert_eq!(account.data.len(), favorites.space());
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(favorites.space())
+    );
+    assert_eq!(favorites.number, 23);
+    assert_eq!(favorites.color, "red");
+    assert_eq!(favorites.authority, user.pubkey());
+
+    send(
+        &mut context,
+        &[update_instruction(
+            &user.pubkey(),
+            &user.pubkey(),
+            24,
+            "blue",
+        )],
+        &user,
+    )
+    .await
+    .unwrap();
+    let (_, favorites) = read(&mut context, &user.pubkey()).await;
+    assert_eq!(favorites.number, 24);
+    assert_eq!(favorites.color, "blue");
+}
+
+#[tokio::test]
+async fn upgrades_accounts_with_a_single_delegate() {
+    let delegate = Keypair::new();
+    let delegate_key = delegate.pubkey();
+    let (mut context, user) = start_with_favorites(|user| {
+        fixture(
+            &FavoritesV1 {
+                number: 5,
+                color: "green".to_string(),
+                authority: *user,
+                delegate: Some(delegate_key),
+            },
+            Favorites::V1_SPACE,
+        )
+    })
+    .await;
+
+    send(&mut context, &[upgrade_instruction(&user.pubkey())], &user)
+        .await
+        .unwrap();
+    let (account, favorites) = read(&mut context, &user.pubkey()).await;
+    assert_eq!(account.data.len(), favorites.space());
+    assert_eq!(favorites.authority, user.pubkey());
+    assert_eq!(favorites.delegates.len(), 1);
+    assert_eq!(favorites.delegates[0].key, delegate_key);
+
+    // The delegate keeps what it could do before
+    send(
+        &mut context,
+        &[update_instruction(&user.pubkey(), &delegate_key, 6, "teal")],
+        &delegate,
+    )
+    .await
+    .unwrap();
+    let (_, favorites) = read(&mut context, &user.pubkey()).await;
+    assert_eq!(favorites.number, 6);
+
+    // Running it again is refused
+    assert!(
+        send(&mut context, &[upgrade_instruction(&user.pubkey())], &user)
+            .await
+            .is_err()
+    );
+}
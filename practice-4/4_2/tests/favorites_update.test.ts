@@ -2,11 +2,58 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program, web3 } from "@coral-xyz/anchor";
 import { FavoritesUpdate } from "../target/types/favorites_update";
 import { airdropIfRequired, getCustomErrorMessage } from "@solana-developers/helpers";
-import { expect, describe, test, it } from '@jest/globals';
+import { expect, describe, test, it, beforeAll } from '@jest/globals';
 import { systemProgramErrors } from "./system-program-errors";
 
 jest.setTimeout(300000);
 
+const TOKEN_PROGRAM_ID = anchor.utils.token.TOKEN_PROGRAM_ID;
+
+const rewardMintFor = (programId: web3.PublicKey) =>
+  web3.PublicKey.findProgramAddressSync([Buffer.from("reward-mint")], programId)[0];
+
+// The reward accounts of `set_favorites` that can't be derived from the IDL.
+// Without them no reward is minted.
+const rewardAccountsFor = (owner: web3.PublicKey, programId: web3.PublicKey) => {
+  const rewardMint = rewardMintFor(programId);
+  const userRewardAccount = anchor.utils.token.associatedAddress({ mint: rewardMint, owner });
+  return { userRewardAccount, tokenProgram: TOKEN_PROGRAM_ID };
+};
+
+// The first `set_favorites` of a user mints the reward, the mint is created
+// once per validator
+beforeAll(async () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+  const rewardMint = rewardMintFor(program.programId);
+  if (!(await program.provider.connection.getAccountInfo(rewardMint))) {
+    await program.methods.initRewardMint().accounts({ tokenProgram: TOKEN_PROGRAM_ID }).rpc();
+  }
+});
+
+const fundedUser = async (): Promise<web3.Keypair> => {
+  const user = web3.Keypair.generate();
+  await airdropIfRequired(
+    anchor.getProvider().connection,
+    user.publicKey,
+    0.5 * web3.LAMPORTS_PER_SOL,
+    1 * web3.LAMPORTS_PER_SOL
+  );
+  return user;
+};
+
+const pdasFor = (user: web3.PublicKey, programId: web3.PublicKey) => {
+  const [legacyPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("favorites-legacy"), user.toBuffer()],
+    programId
+  );
+  const [favoritesPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("favorites"), user.toBuffer()],
+    programId
+  );
+  return { legacyPda, favoritesPda };
+};
+
 describe("favorites", () => {
   // Configure the client to use the local cluster.
   anchor.setProvider(anchor.AnchorProvider.env());
@@ -36,8 +83,11 @@ describe("favorites", () => {
         .setFavorites(favoriteNumber, favoriteColor)
         .accounts({
           user: user.publicKey,
-          // Note that both `favorites` and `system_program` are added
-          // automatically.
+          // The reward token account and the token program owning the
+          // reward mint
+          ...rewardAccountsFor(user.publicKey, program.programId),
+          // Note that `favorites`, `system_program`, the reward mint and
+          // claim are added automatically.
         })
         // Sign the transaction
         .signers([user])
@@ -116,7 +166,7 @@ describe("favorites", () => {
     // Створюємо акаунт favorites
     await program.methods
       .setFavorites(new anchor.BN(1), "yellow")
-      .accounts({ user: user.publicKey })
+      .accounts({ user: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
       .signers([user])
       .rpc();
 
@@ -130,6 +180,7 @@ describe("favorites", () => {
       .setAuthority(delegate.publicKey)
       .accounts({
         authority: user.publicKey,
+        user: user.publicKey,
       })
       .signers([user])
       .rpc();
@@ -149,6 +200,1467 @@ describe("favorites", () => {
     const updated = await program.account.favorites.fetch(favoritesPda);
     expect(updated.number.toNumber()).toBe(99);
     expect(updated.color).toBe("green");
-    expect(updated.delegate.toBase58()).toBe(delegate.publicKey.toBase58());
+    expect(updated.delegates.map((d) => d.key.toBase58())).toEqual([
+      delegate.publicKey.toBase58(),
+    ]);
+  });
+
+  it("Migrates legacy favorites and closes the legacy account", async () => {
+    const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+    const connection = anchor.getProvider().connection;
+    const user = await fundedUser();
+    const { legacyPda, favoritesPda } = pdasFor(user.publicKey, program.programId);
+
+    await program.methods
+      .setFavoritesLegacy(new anchor.BN(7), "purple")
+      .accounts({ user: user.publicKey })
+      .signers([user])
+      .rpc();
+
+    await program.methods
+      .migrateFavorites()
+      .accounts({ user: user.publicKey })
+      .signers([user])
+      .rpc();
+
+    const migrated = await program.account.favorites.fetch(favoritesPda);
+    expect(migrated.number.toNumber()).toBe(7);
+    expect(migrated.color).toBe("purple");
+    expect(migrated.authority.toBase58()).toBe(user.publicKey.toBase58());
+    expect(migrated.delegates).toEqual([]);
+    expect(await connection.getAccountInfo(legacyPda)).toBeNull();
+
+    // A second migration has nothing to read and must leave the data alone
+    await expect(
+      program.methods
+        .migrateFavorites()
+        .accounts({ user: user.publicKey })
+        .signers([user])
+        .rpc()
+    ).rejects.toThrow();
+    const afterRetry = await program.account.favorites.fetch(favoritesPda);
+    expect(afterRetry.number.toNumber()).toBe(7);
+    expect(afterRetry.color).toBe("purple");
+  });
+
+  it("Does not let another user migrate someone else's legacy favorites", async () => {
+    const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+    const owner = await fundedUser();
+    const attacker = await fundedUser();
+    const { legacyPda } = pdasFor(owner.publicKey, program.programId);
+    const attackerPdas = pdasFor(attacker.publicKey, program.programId);
+
+    await program.methods
+      .setFavoritesLegacy(new anchor.BN(3), "orange")
+      .accounts({ user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    await expect(
+      program.methods
+        .migrateFavorites()
+        .accountsPartial({
+          user: attacker.publicKey,
+          legacyFavorites: legacyPda,
+          favorites: attackerPdas.favoritesPda,
+        })
+        .signers([attacker])
+        .rpc()
+    ).rejects.toThrow("ConstraintSeeds");
+
+    const legacy = await program.account.favoritesLegacy.fetch(legacyPda);
+    expect(legacy.number.toNumber()).toBe(3);
+    expect(legacy.color).toBe("orange");
+  });
+});
+
+describe("update_favorites authorization", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+
+  const createFavorites = async (owner: web3.Keypair) => {
+    await program.methods
+      .setFavorites(new anchor.BN(5), "white")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    return pdasFor(owner.publicKey, program.programId).favoritesPda;
+  };
+
+  const expectUnchanged = async (favoritesPda: web3.PublicKey) => {
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.number.toNumber()).toBe(5);
+    expect(favorites.color).toBe("white");
+  };
+
+  it("Rejects a signer that only names the owner as user", async () => {
+    const owner = await fundedUser();
+    const attacker = await fundedUser();
+    const favoritesPda = await createFavorites(owner);
+
+    await expect(
+      program.methods
+        .updateFavorites(new anchor.BN(666), "black")
+        .accounts({
+          user: owner.publicKey,
+          authority: attacker.publicKey,
+        })
+        .signers([attacker])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+    await expectUnchanged(favoritesPda);
+  });
+
+  it("Rejects a favorites account that does not belong to user", async () => {
+    const owner = await fundedUser();
+    const attacker = await fundedUser();
+    const favoritesPda = await createFavorites(owner);
+
+    await expect(
+      program.methods
+        .updateFavorites(new anchor.BN(666), "black")
+        .accountsPartial({
+          favorites: favoritesPda,
+          user: attacker.publicKey,
+          authority: attacker.publicKey,
+        })
+        .signers([attacker])
+        .rpc()
+    ).rejects.toThrow("ConstraintSeeds");
+    await expectUnchanged(favoritesPda);
+  });
+
+  it("Rejects a delegate after the owner removes it", async () => {
+    const owner = await fundedUser();
+    const delegate = await fundedUser();
+    const favoritesPda = await createFavorites(owner);
+
+    await program.methods
+      .setAuthority(delegate.publicKey)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .setAuthority(null)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    await expect(
+      program.methods
+        .updateFavorites(new anchor.BN(666), "black")
+        .accounts({
+          user: owner.publicKey,
+          authority: delegate.publicKey,
+        })
+        .signers([delegate])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+    await expectUnchanged(favoritesPda);
+  });
+});
+
+describe("delegates", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+  const connection = anchor.getProvider().connection;
+
+  const UPDATE_NUMBER = 1;
+  const UPDATE_COLOR = 2;
+  const MANAGE_DELEGATES = 4;
+
+  const createFavorites = async (owner: web3.Keypair) => {
+    await program.methods
+      .setFavorites(new anchor.BN(5), "white")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    return pdasFor(owner.publicKey, program.programId).favoritesPda;
+  };
+
+  const eventsOf = async (signature: string) => {
+    await connection.confirmTransaction(signature, "confirmed");
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    return [...parser.parseLogs(tx?.meta?.logMessages ?? [])];
+  };
+
+  it("Limits a delegate to the permissions it was granted", async () => {
+    const owner = await fundedUser();
+    const delegate = await fundedUser();
+    const favoritesPda = await createFavorites(owner);
+
+    await program.methods
+      .setDelegate(delegate.publicKey, UPDATE_COLOR, null)
+      .accounts({ user: owner.publicKey, authority: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    // Changing only the color is allowed
+    await program.methods
+      .updateFavorites(new anchor.BN(5), "teal")
+      .accounts({ user: owner.publicKey, authority: delegate.publicKey })
+      .signers([delegate])
+      .rpc();
+
+    await expect(
+      program.methods
+        .updateFavorites(new anchor.BN(6), "teal")
+        .accounts({ user: owner.publicKey, authority: delegate.publicKey })
+        .signers([delegate])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+
+    // Nor can it hand out anything, not even to itself
+    await expect(
+      program.methods
+        .setDelegate(delegate.publicKey, UPDATE_COLOR | UPDATE_NUMBER, null)
+        .accounts({ user: owner.publicKey, authority: delegate.publicKey })
+        .signers([delegate])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.number.toNumber()).toBe(5);
+    expect(favorites.color).toBe("teal");
+  });
+
+  it("Stops honouring a delegate once it expires", async () => {
+    const owner = await fundedUser();
+    const delegate = await fundedUser();
+    await createFavorites(owner);
+
+    const slot = await connection.getSlot();
+    const now = (await connection.getBlockTime(slot)) ?? Math.floor(Date.now() / 1000);
+    await program.methods
+      .setDelegate(delegate.publicKey, UPDATE_NUMBER, new anchor.BN(now - 1))
+      .accounts({ user: owner.publicKey, authority: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    await expect(
+      program.methods
+        .updateFavorites(new anchor.BN(6), "white")
+        .accounts({ user: owner.publicKey, authority: delegate.publicKey })
+        .signers([delegate])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+  });
+
+  it("Lets a managing delegate grant a subset of its own permissions", async () => {
+    const owner = await fundedUser();
+    const manager = await fundedUser();
+    const helper = await fundedUser();
+    const favoritesPda = await createFavorites(owner);
+
+    await program.methods
+      .setDelegate(manager.publicKey, MANAGE_DELEGATES | UPDATE_NUMBER, null)
+      .accounts({ user: owner.publicKey, authority: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    await expect(
+      program.methods
+        .setDelegate(helper.publicKey, UPDATE_COLOR, null)
+        .accounts({ user: owner.publicKey, authority: manager.publicKey })
+        .signers([manager])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+
+    const signature = await program.methods
+      .setDelegate(helper.publicKey, UPDATE_NUMBER, null)
+      .accounts({ user: owner.publicKey, authority: manager.publicKey })
+      .signers([manager])
+      .rpc();
+
+    const [event] = await eventsOf(signature);
+    expect(event.name).toBe("delegateChanged");
+    expect(event.data.delegate.toBase58()).toBe(helper.publicKey.toBase58());
+    expect(event.data.permissions).toBe(UPDATE_NUMBER);
+    expect(event.data.changedBy.toBase58()).toBe(manager.publicKey.toBase58());
+
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.delegates.map((d) => d.key.toBase58())).toEqual([
+      manager.publicKey.toBase58(),
+      helper.publicKey.toBase58(),
+    ]);
+  });
+
+  // A manager holding the number and delegate management for an hour
+  const managedFavorites = async () => {
+    const owner = await fundedUser();
+    const manager = await fundedUser();
+    const favoritesPda = await createFavorites(owner);
+    const slot = await connection.getSlot();
+    const now = (await connection.getBlockTime(slot)) ?? Math.floor(Date.now() / 1000);
+    const expiresAt = now + 3600;
+    await program.methods
+      .setDelegate(manager.publicKey, MANAGE_DELEGATES | UPDATE_NUMBER, new anchor.BN(expiresAt))
+      .accounts({ user: owner.publicKey, authority: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    return { owner, manager, favoritesPda, expiresAt };
+  };
+
+  it("Keeps a managing delegate from editing its own entry", async () => {
+    const { owner, manager, favoritesPda, expiresAt } = await managedFavorites();
+
+    await expect(
+      program.methods
+        .setDelegate(manager.publicKey, MANAGE_DELEGATES | UPDATE_NUMBER, null)
+        .accounts({ user: owner.publicKey, authority: manager.publicKey })
+        .signers([manager])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.delegates[0].expiresAt.toNumber()).toBe(expiresAt);
+  });
+
+  it("Caps what a managing delegate hands out at its own expiry", async () => {
+    const { owner, manager, favoritesPda, expiresAt } = await managedFavorites();
+    const forever = web3.Keypair.generate().publicKey;
+    const later = web3.Keypair.generate().publicKey;
+
+    await program.methods
+      .setDelegate(forever, UPDATE_NUMBER, null)
+      .accounts({ user: owner.publicKey, authority: manager.publicKey })
+      .signers([manager])
+      .rpc();
+    await program.methods
+      .setDelegate(later, UPDATE_NUMBER, new anchor.BN(expiresAt + 3600))
+      .accounts({ user: owner.publicKey, authority: manager.publicKey })
+      .signers([manager])
+      .rpc();
+
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.delegates.slice(1).map((d) => d.expiresAt.toNumber())).toEqual([
+      expiresAt,
+      expiresAt,
+    ]);
+  });
+
+  it("Keeps a managing delegate away from delegates with more permissions", async () => {
+    const { owner, manager, favoritesPda } = await managedFavorites();
+    const stronger = web3.Keypair.generate().publicKey;
+    await program.methods
+      .setDelegate(stronger, UPDATE_COLOR | UPDATE_NUMBER, null)
+      .accounts({ user: owner.publicKey, authority: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    await expect(
+      program.methods
+        .setDelegate(stronger, UPDATE_NUMBER, null)
+        .accounts({ user: owner.publicKey, authority: manager.publicKey })
+        .signers([manager])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+    await expect(
+      program.methods
+        .removeDelegate(stronger)
+        .accounts({ user: owner.publicKey, authority: manager.publicKey })
+        .signers([manager])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+
+    // Delegates within its own permissions are fair game
+    const weaker = web3.Keypair.generate().publicKey;
+    await program.methods
+      .setDelegate(weaker, UPDATE_NUMBER, null)
+      .accounts({ user: owner.publicKey, authority: manager.publicKey })
+      .signers([manager])
+      .rpc();
+    await program.methods
+      .removeDelegate(weaker)
+      .accounts({ user: owner.publicKey, authority: manager.publicKey })
+      .signers([manager])
+      .rpc();
+
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.delegates.map((d) => d.key.toBase58())).toEqual([
+      manager.publicKey.toBase58(),
+      stronger.toBase58(),
+    ]);
+  });
+
+  it("Removes delegates and caps how many there are", async () => {
+    const owner = await fundedUser();
+    const favoritesPda = await createFavorites(owner);
+    const delegates = [1, 2, 3, 4].map(() => web3.Keypair.generate().publicKey);
+
+    for (const delegate of delegates) {
+      await program.methods
+        .setDelegate(delegate, UPDATE_NUMBER, null)
+        .accounts({ user: owner.publicKey, authority: owner.publicKey })
+        .signers([owner])
+        .rpc();
+    }
+    await expect(
+      program.methods
+        .setDelegate(web3.Keypair.generate().publicKey, UPDATE_NUMBER, null)
+        .accounts({ user: owner.publicKey, authority: owner.publicKey })
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("TooManyDelegates");
+
+    const signature = await program.methods
+      .removeDelegate(delegates[0])
+      .accounts({ user: owner.publicKey, authority: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    const [event] = await eventsOf(signature);
+    expect(event.data.delegate.toBase58()).toBe(delegates[0].toBase58());
+    expect(event.data.permissions).toBe(0);
+
+    await expect(
+      program.methods
+        .removeDelegate(delegates[0])
+        .accounts({ user: owner.publicKey, authority: owner.publicKey })
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("DelegateNotFound");
+
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.delegates).toHaveLength(3);
+  });
+});
+
+describe("ownership transfer", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+
+  const createFavorites = async (owner: web3.Keypair) => {
+    await program.methods
+      .setFavorites(new anchor.BN(5), "white")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    return pdasFor(owner.publicKey, program.programId).favoritesPda;
+  };
+
+  it("Hands the account to the new owner once they accept", async () => {
+    const owner = await fundedUser();
+    const newOwner = await fundedUser();
+    const delegate = await fundedUser();
+    const favoritesPda = await createFavorites(owner);
+
+    await program.methods
+      .setAuthority(delegate.publicKey)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .proposeAuthority(newOwner.publicKey)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    // Proposing alone changes nothing
+    const proposed = await program.account.favorites.fetch(favoritesPda);
+    expect(proposed.authority.toBase58()).toBe(owner.publicKey.toBase58());
+    expect(proposed.pendingAuthority?.toBase58()).toBe(newOwner.publicKey.toBase58());
+
+    await program.methods
+      .acceptAuthority()
+      .accounts({ newAuthority: newOwner.publicKey, user: owner.publicKey })
+      .signers([newOwner])
+      .rpc();
+
+    const transferred = await program.account.favorites.fetch(favoritesPda);
+    expect(transferred.authority.toBase58()).toBe(newOwner.publicKey.toBase58());
+    expect(transferred.pendingAuthority).toBeNull();
+    expect(transferred.delegates).toEqual([]);
+
+    // The PDA keeps its address, the new owner updates it through `user`
+    await program.methods
+      .updateFavorites(new anchor.BN(8), "navy")
+      .accounts({ user: owner.publicKey, authority: newOwner.publicKey })
+      .signers([newOwner])
+      .rpc();
+    await program.methods
+      .setAuthority(delegate.publicKey)
+      .accounts({ authority: newOwner.publicKey, user: owner.publicKey })
+      .signers([newOwner])
+      .rpc();
+
+    // While the previous owner has lost access
+    await expect(
+      program.methods
+        .updateFavorites(new anchor.BN(1), "white")
+        .accounts({ user: owner.publicKey, authority: owner.publicKey })
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+    await expect(
+      program.methods
+        .proposeAuthority(owner.publicKey)
+        .accounts({ authority: owner.publicKey, user: owner.publicKey })
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.number.toNumber()).toBe(8);
+    expect(favorites.color).toBe("navy");
+  });
+
+  it("Only lets the proposed wallet accept, and not after a cancel", async () => {
+    const owner = await fundedUser();
+    const newOwner = await fundedUser();
+    const stranger = await fundedUser();
+    const favoritesPda = await createFavorites(owner);
+
+    await program.methods
+      .proposeAuthority(newOwner.publicKey)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    await expect(
+      program.methods
+        .acceptAuthority()
+        .accounts({ newAuthority: stranger.publicKey, user: owner.publicKey })
+        .signers([stranger])
+        .rpc()
+    ).rejects.toThrow("NotPendingAuthority");
+
+    await program.methods
+      .proposeAuthority(null)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    await expect(
+      program.methods
+        .acceptAuthority()
+        .accounts({ newAuthority: newOwner.publicKey, user: owner.publicKey })
+        .signers([newOwner])
+        .rpc()
+    ).rejects.toThrow("NotPendingAuthority");
+
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.authority.toBase58()).toBe(owner.publicKey.toBase58());
+  });
+});
+
+describe("close_favorites", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+  const connection = anchor.getProvider().connection;
+
+  it("Refunds the full rent to the recipient and frees the PDA", async () => {
+    const owner = await fundedUser();
+    const recipient = web3.Keypair.generate();
+    const { favoritesPda } = pdasFor(owner.publicKey, program.programId);
+
+    await program.methods
+      .setFavorites(new anchor.BN(5), "white")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+
+    const account = await connection.getAccountInfo(favoritesPda);
+    const rent = await connection.getMinimumBalanceForRentExemption(account.data.length);
+    expect(account.lamports).toBe(rent);
+
+    await program.methods
+      .closeFavorites()
+      .accountsPartial({
+        authority: owner.publicKey,
+        favorites: favoritesPda,
+        user: owner.publicKey,
+        recipient: recipient.publicKey,
+        history: null,
+      })
+      .signers([owner])
+      .rpc();
+
+    expect(await connection.getAccountInfo(favoritesPda)).toBeNull();
+    expect(await connection.getBalance(recipient.publicKey)).toBe(rent);
+
+    // Re-creating costs the same rent again (the provider wallet pays the
+    // fee) and starts from a clean slate
+    const before = await connection.getBalance(owner.publicKey);
+    await program.methods
+      .setFavorites(new anchor.BN(6), "black")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    expect(before - (await connection.getBalance(owner.publicKey))).toBe(rent);
+
+    const recreated = await program.account.favorites.fetch(favoritesPda);
+    expect(recreated.number.toNumber()).toBe(6);
+    expect(recreated.delegates).toEqual([]);
+    expect(recreated.pendingAuthority).toBeNull();
+  });
+
+  it("Only lets the authority close", async () => {
+    const owner = await fundedUser();
+    const delegate = await fundedUser();
+    const { favoritesPda } = pdasFor(owner.publicKey, program.programId);
+
+    await program.methods
+      .setFavorites(new anchor.BN(5), "white")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .setAuthority(delegate.publicKey)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    await expect(
+      program.methods
+        .closeFavorites()
+        .accounts({
+          authority: delegate.publicKey,
+          user: owner.publicKey,
+          recipient: delegate.publicKey,
+        })
+        .signers([delegate])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+    expect(await connection.getAccountInfo(favoritesPda)).not.toBeNull();
+  });
+});
+
+describe("account size", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+  const connection = anchor.getProvider().connection;
+
+  // discriminator + number + color length + authority + delegates length +
+  // pending_authority tag + entries length + history_enabled + rate limits +
+  // reward_claimed + created_slot
+  const FIXED_SPACE = 8 + 8 + 4 + 32 + 4 + 1 + 4 + 1 + 8 + 8 + 2 + 1 + 8;
+
+  const accountOf = async (pda: web3.PublicKey) => {
+    const account = await connection.getAccountInfo(pda);
+    const rent = await connection.getMinimumBalanceForRentExemption(account.data.length);
+    expect(account.lamports).toBe(rent);
+    return account;
+  };
+
+  it("Sizes the account to its color and settles rent on update", async () => {
+    const owner = await fundedUser();
+    const { favoritesPda } = pdasFor(owner.publicKey, program.programId);
+
+    await program.methods
+      .setFavorites(new anchor.BN(1), "red")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    const small = await accountOf(favoritesPda);
+    expect(small.data.length).toBe(FIXED_SPACE + 3);
+
+    const longColor = "lightgoldenrodyellow";
+    let before = await connection.getBalance(owner.publicKey);
+    await program.methods
+      .updateFavorites(new anchor.BN(1), longColor)
+      .accounts({ user: owner.publicKey, authority: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    const grown = await accountOf(favoritesPda);
+    expect(grown.data.length).toBe(FIXED_SPACE + longColor.length);
+    expect(before - (await connection.getBalance(owner.publicKey))).toBe(
+      grown.lamports - small.lamports
+    );
+
+    before = await connection.getBalance(owner.publicKey);
+    await program.methods
+      .updateFavorites(new anchor.BN(1), "red")
+      .accounts({ user: owner.publicKey, authority: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    const shrunk = await accountOf(favoritesPda);
+    expect(shrunk.data.length).toBe(FIXED_SPACE + 3);
+    expect((await connection.getBalance(owner.publicKey)) - before).toBe(
+      grown.lamports - shrunk.lamports
+    );
+  });
+
+  it("Keeps surplus rent in the account when a delegate shrinks it", async () => {
+    const owner = await fundedUser();
+    const delegate = await fundedUser();
+    const { favoritesPda } = pdasFor(owner.publicKey, program.programId);
+
+    await program.methods
+      .setFavorites(new anchor.BN(1), "lightgoldenrodyellow")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .setAuthority(delegate.publicKey)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    const withDelegate = await accountOf(favoritesPda);
+
+    const before = await connection.getBalance(delegate.publicKey);
+    await program.methods
+      .updateFavorites(new anchor.BN(1), "red")
+      .accounts({ user: owner.publicKey, authority: delegate.publicKey })
+      .signers([delegate])
+      .rpc();
+
+    const account = await connection.getAccountInfo(favoritesPda);
+    expect(account.data.length).toBe(withDelegate.data.length - "lightgoldenrodyellow".length + 3);
+    expect(account.lamports).toBe(withDelegate.lamports);
+    expect(await connection.getBalance(delegate.publicKey)).toBe(before);
+  });
+
+  it("Rejects colors over the limit with a clear error", async () => {
+    const owner = await fundedUser();
+    const tooLong = "x".repeat(51);
+
+    await expect(
+      program.methods
+        .setFavorites(new anchor.BN(1), tooLong)
+        .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("ColorTooLong");
+
+    await program.methods
+      .setFavorites(new anchor.BN(1), "red")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    await expect(
+      program.methods
+        .updateFavorites(new anchor.BN(1), tooLong)
+        .accounts({ user: owner.publicKey, authority: owner.publicKey })
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("ColorTooLong");
+  });
+});
+
+describe("entries", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+
+  const createFavorites = async (owner: web3.Keypair) => {
+    await program.methods
+      .setFavorites(new anchor.BN(5), "white")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    return pdasFor(owner.publicKey, program.programId).favoritesPda;
+  };
+
+  it("Adds, replaces and removes individual entries", async () => {
+    const owner = await fundedUser();
+    const favoritesPda = await createFavorites(owner);
+    const accounts = { user: owner.publicKey, authority: owner.publicKey };
+
+    await program.methods
+      .addEntry({ color: { 0: "teal" } })
+      .accounts(accounts)
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .addEntry({ number: { 0: new anchor.BN(7) } })
+      .accounts(accounts)
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .addEntry({ url: { 0: "https://solana.com" } })
+      .accounts(accounts)
+      .signers([owner])
+      .rpc();
+
+    await program.methods
+      .replaceEntry(1, { tag: { 0: "gm" } })
+      .accounts(accounts)
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .removeEntry(0)
+      .accounts(accounts)
+      .signers([owner])
+      .rpc();
+
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.entries).toEqual([
+      { tag: { 0: "gm" } },
+      { url: { 0: "https://solana.com" } },
+    ]);
+    // The main favorites are untouched
+    expect(favorites.number.toNumber()).toBe(5);
+    expect(favorites.color).toBe("white");
+
+    await expect(
+      program.methods
+        .removeEntry(2)
+        .accounts(accounts)
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("EntryNotFound");
+  });
+
+  it("Enforces the entry limits", async () => {
+    const owner = await fundedUser();
+    await createFavorites(owner);
+    const accounts = { user: owner.publicKey, authority: owner.publicKey };
+
+    await expect(
+      program.methods
+        .addEntry({ url: { 0: "solana.com" } })
+        .accounts(accounts)
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("InvalidEntry");
+    await expect(
+      program.methods
+        .addEntry({ tag: { 0: "x".repeat(65) } })
+        .accounts(accounts)
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("EntryTooLong");
+
+    for (let i = 0; i < 8; i++) {
+      await program.methods
+        .addEntry({ number: { 0: new anchor.BN(i) } })
+        .accounts(accounts)
+        .signers([owner])
+        .rpc();
+    }
+    await expect(
+      program.methods
+        .addEntry({ number: { 0: new anchor.BN(8) } })
+        .accounts(accounts)
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("TooManyEntries");
+  });
+
+  it("Needs the entries permission", async () => {
+    const owner = await fundedUser();
+    const delegate = await fundedUser();
+    await createFavorites(owner);
+
+    // `set_authority` only grants number and color
+    await program.methods
+      .setAuthority(delegate.publicKey)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    await expect(
+      program.methods
+        .addEntry({ tag: { 0: "gm" } })
+        .accounts({ user: owner.publicKey, authority: delegate.publicKey })
+        .signers([delegate])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+  });
+});
+
+describe("color validation", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+
+  it("Stores colors lowercase", async () => {
+    const owner = await fundedUser();
+    const { favoritesPda } = pdasFor(owner.publicKey, program.programId);
+
+    await program.methods
+      .setFavorites(new anchor.BN(1), "RebeccaPurple")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    expect((await program.account.favorites.fetch(favoritesPda)).color).toBe("rebeccapurple");
+
+    await program.methods
+      .updateFavorites(new anchor.BN(1), "#FFAA00")
+      .accounts({ user: owner.publicKey, authority: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    expect((await program.account.favorites.fetch(favoritesPda)).color).toBe("#ffaa00");
+  });
+
+  it("Rejects anything that is not a color", async () => {
+    const owner = await fundedUser();
+
+    for (const color of ["blurple", "#fff", "#12345g", ""]) {
+      await expect(
+        program.methods
+          .setFavorites(new anchor.BN(1), color)
+          .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+          .signers([owner])
+          .rpc()
+      ).rejects.toThrow("InvalidColor");
+    }
+  });
+});
+
+describe("get_favorites", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+
+  it("Returns the account through simulation", async () => {
+    const owner = await fundedUser();
+    await program.methods
+      .setFavorites(new anchor.BN(11), "olive")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+
+    const favorites = await program.methods
+      .getFavorites()
+      .accounts({ user: owner.publicKey })
+      .view();
+    expect(favorites.number.toNumber()).toBe(11);
+    expect(favorites.color).toBe("olive");
+    expect(favorites.authority.toBase58()).toBe(owner.publicKey.toBase58());
+  });
+});
+
+describe("history", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+  const connection = anchor.getProvider().connection;
+  const CAPACITY = 8;
+
+  const historyFor = (favoritesPda: web3.PublicKey) =>
+    web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("history"), favoritesPda.toBuffer()],
+      program.programId
+    )[0];
+
+  const createWithHistory = async (owner: web3.Keypair) => {
+    const { favoritesPda } = pdasFor(owner.publicKey, program.programId);
+    await program.methods
+      .setFavorites(new anchor.BN(0), "white")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .initHistory()
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    return { favoritesPda, historyPda: historyFor(favoritesPda) };
+  };
+
+  it("Keeps only the last changes once the ring is full", async () => {
+    const owner = await fundedUser();
+    const delegate = await fundedUser();
+    const { favoritesPda, historyPda } = await createWithHistory(owner);
+    await program.methods
+      .setAuthority(delegate.publicKey)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    const colors = ["red", "green", "blue"];
+    const updates = CAPACITY + 3;
+    for (let number = 1; number <= updates; number++) {
+      // Every third update comes from the delegate
+      const actor = number % 3 === 0 ? delegate : owner;
+      await program.methods
+        .updateFavorites(new anchor.BN(number), colors[number % colors.length])
+        .accountsPartial({
+          favorites: favoritesPda,
+          user: owner.publicKey,
+          authority: actor.publicKey,
+          history: historyPda,
+        })
+        .signers([actor])
+        .rpc();
+    }
+
+    const history = await program.account.favoritesHistory.fetch(historyPda);
+    expect(history.favorites.toBase58()).toBe(favoritesPda.toBase58());
+    expect(history.changes).toHaveLength(CAPACITY);
+
+    // Oldest first, starting at `next`
+    const ordered = [
+      ...history.changes.slice(history.next),
+      ...history.changes.slice(0, history.next),
+    ];
+    const numbers = ordered.map((change) => change.newNumber.toNumber());
+    expect(numbers).toEqual([4, 5, 6, 7, 8, 9, 10, 11]);
+
+    for (const change of ordered) {
+      const number = change.newNumber.toNumber();
+      expect(change.oldNumber.toNumber()).toBe(number - 1);
+      expect(change.oldColor).toBe(colors[(number - 1) % colors.length]);
+      expect(change.newColor).toBe(colors[number % colors.length]);
+      const actor = number % 3 === 0 ? delegate : owner;
+      expect(change.actor.toBase58()).toBe(actor.publicKey.toBase58());
+    }
+    const slots = ordered.map((change) => change.slot.toNumber());
+    expect([...slots].sort((a, b) => a - b)).toEqual(slots);
+  });
+
+  it("Refuses updates that skip the history", async () => {
+    const owner = await fundedUser();
+    const { favoritesPda } = await createWithHistory(owner);
+
+    await expect(
+      program.methods
+        .updateFavorites(new anchor.BN(1), "red")
+        .accountsPartial({
+          favorites: favoritesPda,
+          user: owner.publicKey,
+          authority: owner.publicKey,
+          history: null,
+        })
+        .signers([owner])
+        .rpc()
+    ).rejects.toThrow("HistoryRequired");
+  });
+
+  it("Closes the history together with the favorites", async () => {
+    const owner = await fundedUser();
+    const recipient = web3.Keypair.generate();
+    const { favoritesPda, historyPda } = await createWithHistory(owner);
+    const rent =
+      (await connection.getBalance(favoritesPda)) + (await connection.getBalance(historyPda));
+
+    await program.methods
+      .closeFavorites()
+      .accountsPartial({
+        authority: owner.publicKey,
+        favorites: favoritesPda,
+        user: owner.publicKey,
+        recipient: recipient.publicKey,
+        history: historyPda,
+      })
+      .signers([owner])
+      .rpc();
+
+    expect(await connection.getAccountInfo(historyPda)).toBeNull();
+    expect(await connection.getBalance(recipient.publicKey)).toBe(rent);
+  });
+});
+
+describe("rate limits", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+
+  const setup = async (cooldownSlots: number, dailyQuota: number) => {
+    const owner = await fundedUser();
+    const delegate = await fundedUser();
+    await program.methods
+      .setFavorites(new anchor.BN(0), "white")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .setAuthority(delegate.publicKey)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .setLimits(new anchor.BN(cooldownSlots), dailyQuota)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    return { owner, delegate };
+  };
+
+  const update = (owner: web3.Keypair, signer: web3.Keypair, number: number) =>
+    program.methods
+      .updateFavorites(new anchor.BN(number), "white")
+      .accounts({ user: owner.publicKey, authority: signer.publicKey })
+      .signers([signer])
+      .rpc();
+
+  it("Makes delegates wait for the cooldown, but not the owner", async () => {
+    const { owner, delegate } = await setup(1_000, 0);
+
+    await update(owner, delegate, 1);
+    await expect(update(owner, delegate, 2)).rejects.toThrow("CooldownActive");
+
+    // The owner is never rate limited
+    await update(owner, owner, 3);
+    await update(owner, owner, 4);
+  });
+
+  it("Caps how often each delegate updates per day", async () => {
+    const { owner, delegate } = await setup(0, 2);
+
+    await update(owner, delegate, 1);
+    await update(owner, delegate, 2);
+    await expect(update(owner, delegate, 3)).rejects.toThrow("DailyQuotaExceeded");
+
+    // Re-granting the delegate does not reset its count
+    await program.methods
+      .setDelegate(delegate.publicKey, 1, null)
+      .accounts({ user: owner.publicKey, authority: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    await expect(update(owner, delegate, 3)).rejects.toThrow("DailyQuotaExceeded");
+  });
+
+  it("Only lets the authority change the limits", async () => {
+    const { owner, delegate } = await setup(0, 0);
+
+    await expect(
+      program.methods
+        .setLimits(new anchor.BN(0), 0)
+        .accounts({ authority: delegate.publicKey, user: owner.publicKey })
+        .signers([delegate])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+  });
+});
+
+describe("events", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+  const parser = new anchor.EventParser(program.programId, program.coder);
+
+  const eventsOf = async (tx: string) => {
+    const confirmed = await program.provider.connection.getTransaction(tx, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    return Array.from(parser.parseLogs(confirmed?.meta?.logMessages ?? []));
+  };
+
+  it("Emits an event from every change", async () => {
+    const owner = await fundedUser();
+    const delegate = await fundedUser();
+    const { favoritesPda } = pdasFor(owner.publicKey, program.programId);
+
+    const setTx = await program.methods
+      .setFavorites(new anchor.BN(1), "Red")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc({ commitment: "confirmed" });
+    const [set] = await eventsOf(setTx);
+    expect(set.name).toEqual("favoritesSet");
+    expect(set.data.favorites).toEqual(favoritesPda);
+    expect(set.data.color).toEqual("red");
+
+    const delegateTx = await program.methods
+      .setAuthority(delegate.publicKey)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc({ commitment: "confirmed" });
+    const [changed] = await eventsOf(delegateTx);
+    expect(changed.name).toEqual("delegateChanged");
+    expect(changed.data.delegate).toEqual(delegate.publicKey);
+
+    const updateTx = await program.methods
+      .updateFavorites(new anchor.BN(2), "blue")
+      .accounts({ user: owner.publicKey, authority: delegate.publicKey })
+      .signers([delegate])
+      .rpc({ commitment: "confirmed" });
+    const [updated] = await eventsOf(updateTx);
+    expect(updated.name).toEqual("favoritesUpdated");
+    expect(updated.data.actor).toEqual(delegate.publicKey);
+    expect(updated.data.number.toNumber()).toEqual(2);
+  });
+
+  it("Emits Migrated when moving a legacy account", async () => {
+    const user = await fundedUser();
+    const { legacyPda, favoritesPda } = pdasFor(user.publicKey, program.programId);
+
+    await program.methods
+      .setFavoritesLegacy(new anchor.BN(5), "green")
+      .accounts({ user: user.publicKey })
+      .signers([user])
+      .rpc();
+    const tx = await program.methods
+      .migrateFavorites()
+      .accounts({ user: user.publicKey })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+
+    const [migrated] = await eventsOf(tx);
+    expect(migrated.name).toEqual("migrated");
+    expect(migrated.data.legacyFavorites).toEqual(legacyPda);
+    expect(migrated.data.favorites).toEqual(favoritesPda);
+    expect(migrated.data.color).toEqual("green");
+  });
+});
+
+describe("rewards", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+  const connection = anchor.getProvider().connection;
+
+  it("Mints one token on the first set and keeps it through close", async () => {
+    const owner = await fundedUser();
+    const { favoritesPda } = pdasFor(owner.publicKey, program.programId);
+    const reward = rewardAccountsFor(owner.publicKey, program.programId);
+    const [rewardClaimPda] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("reward-claimed"), owner.publicKey.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .setFavorites(new anchor.BN(3), "green")
+      .accounts({ user: owner.publicKey, ...reward })
+      .signers([owner])
+      .rpc();
+    const balance = await connection.getTokenAccountBalance(reward.userRewardAccount);
+    expect(balance.value.amount).toEqual("1000000");
+    expect((await program.account.favorites.fetch(favoritesPda)).rewardClaimed).toBe(true);
+    expect((await program.account.rewardClaim.fetch(rewardClaimPda)).claimed).toBe(true);
+
+    await program.methods
+      .closeFavorites()
+      .accountsPartial({
+        authority: owner.publicKey,
+        favorites: favoritesPda,
+        user: owner.publicKey,
+        recipient: owner.publicKey,
+        history: null,
+      })
+      .signers([owner])
+      .rpc();
+    const after = await connection.getTokenAccountBalance(reward.userRewardAccount);
+    expect(after.value.amount).toEqual("1000000");
+
+    // The claim survived the close, setting them again mints nothing
+    await program.methods
+      .setFavorites(new anchor.BN(4), "red")
+      .accounts({ user: owner.publicKey, ...reward })
+      .signers([owner])
+      .rpc();
+    const again = await connection.getTokenAccountBalance(reward.userRewardAccount);
+    expect(again.value.amount).toEqual("1000000");
+    expect((await program.account.favorites.fetch(favoritesPda)).rewardClaimed).toBe(false);
+  });
+
+  it("Sets favorites without the reward accounts", async () => {
+    const owner = await fundedUser();
+    const { favoritesPda } = pdasFor(owner.publicKey, program.programId);
+
+    await program.methods
+      .setFavorites(new anchor.BN(5), "blue")
+      .accounts({ user: owner.publicKey, tokenProgram: null, userRewardAccount: null })
+      .signers([owner])
+      .rpc();
+    expect((await program.account.favorites.fetch(favoritesPda)).rewardClaimed).toBe(false);
+  });
+});
+
+describe("session keys", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+  // Update the number and the color
+  const SESSION_PERMISSIONS = 0b0011;
+
+  const sessionPdaFor = (favoritesPda: web3.PublicKey, sessionKey: web3.PublicKey) =>
+    web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("session"), favoritesPda.toBuffer(), sessionKey.toBuffer()],
+      program.programId
+    )[0];
+
+  it("Lets a session key update until its budget is used", async () => {
+    const owner = await fundedUser();
+    const sessionKey = web3.Keypair.generate();
+    const { favoritesPda } = pdasFor(owner.publicKey, program.programId);
+    const session = sessionPdaFor(favoritesPda, sessionKey.publicKey);
+
+    await program.methods
+      .setFavorites(new anchor.BN(1), "red")
+      .accounts({ user: owner.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([owner])
+      .rpc();
+    const expiresAt = Math.floor(Date.now() / 1000) + 3600;
+    await program.methods
+      .createSession(sessionKey.publicKey, SESSION_PERMISSIONS, new anchor.BN(expiresAt), 1)
+      .accounts({ authority: owner.publicKey, user: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    // The provider wallet pays the fee, the session key holds no SOL
+    const update = (number: number, color: string) =>
+      program.methods
+        .updateFavorites(new anchor.BN(number), color)
+        .accountsPartial({
+          favorites: favoritesPda,
+          user: owner.publicKey,
+          authority: sessionKey.publicKey,
+          history: null,
+          session,
+        })
+        .signers([sessionKey])
+        .rpc();
+
+    await update(2, "tan");
+    const favorites = await program.account.favorites.fetch(favoritesPda);
+    expect(favorites.number.toNumber()).toBe(2);
+    expect(favorites.color).toEqual("tan");
+
+    await expect(update(3, "red")).rejects.toThrow("SessionExhausted");
+
+    await program.methods
+      .closeSession()
+      .accounts({ signer: owner.publicKey, session, creator: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    expect(await program.provider.connection.getAccountInfo(session)).toBeNull();
+  });
+});
+
+describe("group favorites", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.FavoritesUpdate as Program<FavoritesUpdate>;
+
+  const groupPda = (groupId: anchor.BN) =>
+    web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("group"), groupId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+
+  const pendingChangePda = (group: web3.PublicKey, id: anchor.BN) =>
+    web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("pending-change"), group.toBuffer(), id.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+
+  // An admin and two members, any two of the three approve changes
+  const setup = async () => {
+    const admin = await fundedUser();
+    const alice = await fundedUser();
+    const bob = await fundedUser();
+    const groupId = new anchor.BN(web3.Keypair.generate().publicKey.toBuffer().readUInt32LE(0));
+    await program.methods
+      .createGroup(
+        groupId,
+        new anchor.BN(1),
+        "red",
+        [
+          { key: alice.publicKey, role: { member: {} } },
+          { key: bob.publicKey, role: { member: {} } },
+        ],
+        2
+      )
+      .accounts({ creator: admin.publicKey })
+      .signers([admin])
+      .rpc();
+    return { admin, alice, bob, group: groupPda(groupId) };
+  };
+
+  const propose = async (group: web3.PublicKey, proposer: web3.Keypair, number: number, color: string) => {
+    const { nextChangeId } = await program.account.groupFavorites.fetch(group);
+    await program.methods
+      .proposeGroupChange(new anchor.BN(number), color)
+      .accounts({ proposer: proposer.publicKey, group })
+      .signers([proposer])
+      .rpc();
+    return pendingChangePda(group, nextChangeId);
+  };
+
+  const apply = (
+    group: web3.PublicKey,
+    signer: web3.Keypair,
+    number: number,
+    color: string,
+    pendingChange: web3.PublicKey | null,
+    proposer: web3.PublicKey | null
+  ) =>
+    program.methods
+      .updateGroupFavorites(new anchor.BN(number), color)
+      .accountsPartial({ signer: signer.publicKey, group, pendingChange, proposer })
+      .signers([signer])
+      .rpc();
+
+  it("Lets admins update directly, but not members", async () => {
+    const { admin, alice, group } = await setup();
+
+    await apply(group, admin, 2, "blue", null, null);
+    await expect(apply(group, alice, 3, "green", null, null)).rejects.toThrow("Unauthorized");
+
+    const favorites = await program.account.groupFavorites.fetch(group);
+    expect(favorites.number.toNumber()).toEqual(2);
+    expect(favorites.color).toEqual("blue");
+  });
+
+  it("Applies a change once enough members approved it", async () => {
+    const { alice, bob, group } = await setup();
+    const pendingChange = await propose(group, alice, 7, "Teal");
+
+    await expect(apply(group, alice, 7, "teal", pendingChange, alice.publicKey)).rejects.toThrow(
+      "NotEnoughApprovals"
+    );
+
+    await program.methods
+      .approveGroupChange()
+      .accounts({ approver: bob.publicKey, group, pendingChange })
+      .signers([bob])
+      .rpc();
+    await expect(apply(group, bob, 8, "teal", pendingChange, alice.publicKey)).rejects.toThrow(
+      "ChangeMismatch"
+    );
+    await apply(group, bob, 7, "teal", pendingChange, alice.publicKey);
+
+    const favorites = await program.account.groupFavorites.fetch(group);
+    expect(favorites.number.toNumber()).toEqual(7);
+    expect(favorites.color).toEqual("teal");
+    // Applying the change closed it
+    expect(await program.provider.connection.getAccountInfo(pendingChange)).toBeNull();
+  });
+
+  it("Stops counting approvals of removed members", async () => {
+    const { admin, alice, bob, group } = await setup();
+    const pendingChange = await propose(group, alice, 9, "white");
+    await program.methods
+      .approveGroupChange()
+      .accounts({ approver: bob.publicKey, group, pendingChange })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .removeMember(bob.publicKey)
+      .accounts({ admin: admin.publicKey, group })
+      .signers([admin])
+      .rpc();
+
+    await expect(apply(group, alice, 9, "white", pendingChange, alice.publicKey)).rejects.toThrow(
+      "NotEnoughApprovals"
+    );
+  });
+
+  it("Keeps at least one admin and a reachable threshold", async () => {
+    const { admin, alice, group } = await setup();
+
+    await expect(
+      program.methods
+        .removeMember(admin.publicKey)
+        .accounts({ admin: admin.publicKey, group })
+        .signers([admin])
+        .rpc()
+    ).rejects.toThrow("LastAdmin");
+    await expect(
+      program.methods
+        .setThreshold(4)
+        .accounts({ admin: admin.publicKey, group })
+        .signers([admin])
+        .rpc()
+    ).rejects.toThrow("InvalidThreshold");
+    await expect(
+      program.methods
+        .setMember(alice.publicKey, { admin: {} })
+        .accounts({ admin: alice.publicKey, group })
+        .signers([alice])
+        .rpc()
+    ).rejects.toThrow("Unauthorized");
+  });
+
+  it("Refunds the proposer when a change is cancelled", async () => {
+    const { admin, alice, group } = await setup();
+    const pendingChange = await propose(group, alice, 5, "black");
+
+    await program.methods
+      .cancelGroupChange()
+      .accountsPartial({ signer: admin.publicKey, group, pendingChange, proposer: alice.publicKey })
+      .signers([admin])
+      .rpc();
+    expect(await program.provider.connection.getAccountInfo(pendingChange)).toBeNull();
   });
 });
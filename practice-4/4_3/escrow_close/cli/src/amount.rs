@@ -0,0 +1,49 @@
+use anyhow::{bail, Context, Result};
+
+/// Renders a raw token amount with the mint's decimals, e.g. `1500000` with
+/// 6 decimals becomes `1.5`.
+pub fn format_amount(amount: u64, decimals: u8) -> String {
+    let scale = 10u128.pow(decimals as u32);
+    let whole = amount as u128 / scale;
+    let fraction = amount as u128 % scale;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{fraction:0width$}", width = decimals as usize);
+    format!("{whole}.{}", fraction.trim_end_matches('0'))
+}
+
+/// Parses a human amount such as `1.5` into raw units of a mint with
+/// `decimals` decimals.
+pub fn parse_amount(text: &str, decimals: u8) -> Result<u64> {
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if fraction.len() > decimals as usize {
+        bail!("{text} has more than {decimals} decimals");
+    }
+    let digits = format!("{whole}{fraction:0<width$}", width = decimals as usize);
+    digits
+        .parse::<u64>()
+        .with_context(|| format!("{text} is not a valid token amount"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_with_decimals() {
+        assert_eq!(format_amount(1_500_000, 6), "1.5");
+        assert_eq!(format_amount(100_000_000, 6), "100");
+        assert_eq!(format_amount(1, 6), "0.000001");
+        assert_eq!(format_amount(42, 0), "42");
+    }
+
+    #[test]
+    fn parses_with_decimals() {
+        assert_eq!(parse_amount("1.5", 6).unwrap(), 1_500_000);
+        assert_eq!(parse_amount("100", 6).unwrap(), 100_000_000);
+        assert_eq!(parse_amount("0.000001", 6).unwrap(), 1);
+        assert!(parse_amount("0.0000001", 6).is_err());
+        assert!(parse_amount("abc", 6).is_err());
+    }
+}
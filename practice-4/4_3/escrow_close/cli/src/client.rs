@@ -0,0 +1,479 @@
+use anchor_lang::{Discriminator, InstructionData, ToAccountMetas};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id,
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    metadata::mpl_token_metadata::accounts::Metadata,
+};
+use anyhow::{bail, Context, Result};
+use escrow::{DutchAuction, NftSide, NftTerms, Offer, Royalties, VestingSchedule};
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    account::from_account,
+    clock::Clock,
+    commitment_config::CommitmentConfig,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+/// Byte offsets of the `Offer` fields used to filter `list` on the RPC side.
+const MAKER_OFFSET: usize = 8 + 1 + 8;
+const TOKEN_MINT_A_OFFSET: usize = MAKER_OFFSET + 32;
+const TOKEN_MINT_B_OFFSET: usize = TOKEN_MINT_A_OFFSET + 32;
+
+pub struct MintInfo {
+    pub token_program: Pubkey,
+    pub decimals: u8,
+    pub supply: u64,
+}
+
+impl MintInfo {
+    /// Mirrors the escrow's check: no decimals and a single token minted.
+    pub fn is_nft(&self) -> bool {
+        self.decimals == 0 && self.supply == 1
+    }
+}
+
+pub struct EscrowClient {
+    pub rpc: RpcClient,
+    pub payer: Keypair,
+    pub dry_run: bool,
+}
+
+impl EscrowClient {
+    pub fn new(url: &str, payer: Keypair, dry_run: bool) -> Self {
+        EscrowClient {
+            rpc: RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed()),
+            payer,
+            dry_run,
+        }
+    }
+
+    /// Reads the decimals of `mint` and the token program that owns it.
+    pub fn mint_info(&self, mint: &Pubkey) -> Result<MintInfo> {
+        let account = self
+            .rpc
+            .get_account(mint)
+            .with_context(|| format!("mint {mint} not found"))?;
+        // Both token programs keep `supply` and `decimals` at the same
+        // offsets.
+        let (supply, decimals) = account
+            .data
+            .get(36..45)
+            .map(|data| (u64::from_le_bytes(data[..8].try_into().unwrap()), data[8]))
+            .with_context(|| format!("{mint} is not a mint"))?;
+        Ok(MintInfo {
+            token_program: account.owner,
+            decimals,
+            supply,
+        })
+    }
+
+    pub fn fetch_offer(&self, address: &Pubkey) -> Result<Offer> {
+        let account = self
+            .rpc
+            .get_account(address)
+            .with_context(|| format!("offer {address} not found"))?;
+        if account.owner != escrow::ID {
+            bail!("{address} is not owned by the escrow program");
+        }
+        Ok(Offer::try_from_any_layout(&account.data)?)
+    }
+
+    /// Reads the Metaplex metadata of `mint`, `None` if it has none.
+    pub fn fetch_metadata(&self, mint: &Pubkey) -> Result<Option<Metadata>> {
+        let address = Metadata::find_pda(mint).0;
+        let Some(account) = self
+            .rpc
+            .get_account_with_commitment(&address, self.rpc.commitment())?
+            .value
+        else {
+            return Ok(None);
+        };
+        let metadata = Metadata::safe_deserialize(&account.data)
+            .with_context(|| format!("invalid metadata for {mint}"))?;
+        Ok(Some(metadata))
+    }
+
+    /// The cluster's clock, which is what `take_offer` prices auctions with.
+    pub fn cluster_time(&self) -> Result<i64> {
+        let account = self.rpc.get_account(&solana_sdk::sysvar::clock::ID)?;
+        let clock: Clock = from_account(&account).context("invalid clock sysvar")?;
+        Ok(clock.unix_timestamp)
+    }
+
+    /// Token B taking `offer` costs right now. Auctions keep falling, so the
+    /// price may be lower by the time the transaction lands, never higher.
+    pub fn current_price(&self, offer: &Offer) -> Result<u64> {
+        match offer.auction {
+            Some(_) => Ok(offer.wanted_amount_at(self.cluster_time()?)),
+            None => Ok(offer.token_b_wanted_amount),
+        }
+    }
+
+    pub fn vault_balance(&self, address: &Pubkey, offer: &Offer) -> Result<u64> {
+        let mint = self.mint_info(&offer.token_mint_a)?;
+        let vault = get_associated_token_address_with_program_id(
+            address,
+            &offer.token_mint_a,
+            &mint.token_program,
+        );
+        let balance = self.rpc.get_token_account_balance(&vault)?;
+        Ok(balance.amount.parse()?)
+    }
+
+    /// Lists current-layout offers, optionally narrowed down by maker and mints.
+    pub fn list_offers(
+        &self,
+        maker: Option<Pubkey>,
+        token_mint_a: Option<Pubkey>,
+        token_mint_b: Option<Pubkey>,
+    ) -> Result<Vec<(Pubkey, Offer)>> {
+        let mut filters = vec![
+            RpcFilterType::DataSize(Offer::SPACE as u64),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, Offer::DISCRIMINATOR.to_vec())),
+        ];
+        for (offset, key) in [
+            (MAKER_OFFSET, maker),
+            (TOKEN_MINT_A_OFFSET, token_mint_a),
+            (TOKEN_MINT_B_OFFSET, token_mint_b),
+        ] {
+            if let Some(key) = key {
+                filters.push(RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    offset,
+                    key.to_bytes().to_vec(),
+                )));
+            }
+        }
+        let accounts = self.rpc.get_program_accounts_with_config(
+            &escrow::ID,
+            RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig::default(),
+                ..RpcProgramAccountsConfig::default()
+            },
+        )?;
+        accounts
+            .into_iter()
+            .map(|(address, account)| Ok((address, Offer::try_from_any_layout(&account.data)?)))
+            .collect()
+    }
+
+    /// Sends `instructions` signed by the payer, or only simulates them when
+    /// running with `--dry-run`.
+    pub fn send(&self, instructions: &[Instruction]) -> Result<()> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &[&self.payer],
+            blockhash,
+        );
+
+        if !self.dry_run {
+            let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+            println!("Signature: {signature}");
+            return Ok(());
+        }
+
+        let simulation = self.rpc.simulate_transaction(&transaction)?.value;
+        for line in simulation.logs.unwrap_or_default() {
+            println!("  {line}");
+        }
+        if let Some(units) = simulation.units_consumed {
+            println!("Compute units: {units}");
+        }
+        match simulation.err {
+            Some(err) => bail!("simulation failed: {err}"),
+            None => {
+                println!("Simulation succeeded, nothing was sent");
+                Ok(())
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn make_offer_ix(
+        &self,
+        id: u64,
+        token_mint_a: &Pubkey,
+        token_a_offered_amount: u64,
+        token_mint_b: &Pubkey,
+        token_b_wanted_amount: u64,
+        vesting: Option<VestingSchedule>,
+        nft: Option<NftTerms>,
+        auction: Option<DutchAuction>,
+    ) -> Result<Instruction> {
+        let mint_a = self.mint_info(token_mint_a)?;
+        let token_program = mint_a.token_program;
+        let maker = self.payer.pubkey();
+        let offer = offer_address(&maker, id);
+        let nft_metadata = match nft {
+            Some(nft) => match nft.side {
+                NftSide::TokenA => Some(Metadata::find_pda(token_mint_a).0),
+                // A collection bid names the collection as token B
+                NftSide::TokenB | NftSide::AnyInCollection => {
+                    Some(Metadata::find_pda(token_mint_b).0)
+                }
+            },
+            // The escrow checks an NFT traded without NFT terms has no
+            // metadata, so it owes no royalties
+            None if mint_a.is_nft() => Some(Metadata::find_pda(token_mint_a).0),
+            None if self.mint_info(token_mint_b)?.is_nft() => {
+                Some(Metadata::find_pda(token_mint_b).0)
+            }
+            None => None,
+        };
+        Ok(Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::MakeOffer {
+                maker,
+                token_mint_a: *token_mint_a,
+                token_mint_b: *token_mint_b,
+                maker_token_account_a: get_associated_token_address_with_program_id(
+                    &maker,
+                    token_mint_a,
+                    &token_program,
+                ),
+                offer,
+                vault: get_associated_token_address_with_program_id(
+                    &offer,
+                    token_mint_a,
+                    &token_program,
+                ),
+                associated_token_program: anchor_spl::associated_token::ID,
+                token_program,
+                system_program: anchor_lang::system_program::ID,
+                nft_metadata,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::MakeOffer {
+                id,
+                token_a_offered_amount,
+                token_b_wanted_amount,
+                vesting,
+                nft,
+                auction,
+            }
+            .data(),
+        })
+    }
+
+    /// Takes `offer`, going through `take_offer_vested` when the offer locks
+    /// token A. NFT offers first create the creators' token accounts their
+    /// royalties are paid into.
+    pub fn take_offer_ixs(&self, address: &Pubkey, offer: &Offer) -> Result<Vec<Instruction>> {
+        let token_program = self.mint_info(&offer.token_mint_a)?.token_program;
+        let taker = self.payer.pubkey();
+        let ata = |owner: &Pubkey, mint: &Pubkey| {
+            get_associated_token_address_with_program_id(owner, mint, &token_program)
+        };
+
+        if offer.vesting.is_some() {
+            let vesting = vesting_address(address, &taker);
+            return Ok(vec![Instruction {
+                program_id: escrow::ID,
+                accounts: escrow::accounts::TakeOfferVested {
+                    taker,
+                    maker: offer.maker,
+                    token_mint_a: offer.token_mint_a,
+                    token_mint_b: offer.token_mint_b,
+                    taker_token_account_b: ata(&taker, &offer.token_mint_b),
+                    maker_token_account_b: ata(&offer.maker, &offer.token_mint_b),
+                    offer: *address,
+                    vault: ata(address, &offer.token_mint_a),
+                    vesting,
+                    vesting_vault: ata(&vesting, &offer.token_mint_a),
+                    associated_token_program: anchor_spl::associated_token::ID,
+                    token_program,
+                    system_program: anchor_lang::system_program::ID,
+                }
+                .to_account_metas(None),
+                data: escrow::instruction::TakeOfferVested {}.data(),
+            }]);
+        }
+
+        let mut instructions = Vec::new();
+        let mut royalty_accounts = Vec::new();
+        let mut nft_metadata = None;
+        if let Some(nft) = offer.nft {
+            let (nft_mint, payment_mint, price) = match nft.side {
+                NftSide::TokenA => (
+                    offer.token_mint_a,
+                    offer.token_mint_b,
+                    self.current_price(offer)?,
+                ),
+                NftSide::TokenB => (
+                    offer.token_mint_b,
+                    offer.token_mint_a,
+                    self.vault_balance(address, offer)?,
+                ),
+                NftSide::AnyInCollection => {
+                    bail!("{address} is a collection bid, pass the NFT to sell with --nft")
+                }
+            };
+            nft_metadata = Some(Metadata::find_pda(&nft_mint).0);
+            if let Some(metadata) = self.fetch_metadata(&nft_mint)? {
+                let royalties = Royalties::from_metadata(nft.side, &metadata, price);
+                (instructions, royalty_accounts) =
+                    royalty_recipients(&taker, &royalties, &payment_mint, &token_program);
+            }
+        }
+
+        let mut accounts = escrow::accounts::TakeOffer {
+            taker,
+            maker: offer.maker,
+            token_mint_a: offer.token_mint_a,
+            token_mint_b: offer.token_mint_b,
+            taker_token_account_a: ata(&taker, &offer.token_mint_a),
+            taker_token_account_b: ata(&taker, &offer.token_mint_b),
+            maker_token_account_b: ata(&offer.maker, &offer.token_mint_b),
+            offer: *address,
+            vault: ata(address, &offer.token_mint_a),
+            associated_token_program: anchor_spl::associated_token::ID,
+            token_program,
+            system_program: anchor_lang::system_program::ID,
+            nft_metadata,
+        }
+        .to_account_metas(None);
+        accounts.extend(royalty_accounts);
+        instructions.push(Instruction {
+            program_id: escrow::ID,
+            accounts,
+            data: escrow::instruction::TakeOffer {}.data(),
+        });
+        Ok(instructions)
+    }
+
+    /// Sells `nft_mint` into the collection bid at `address`, creating the
+    /// creators' token A accounts for their royalties first.
+    pub fn take_collection_bid_ixs(
+        &self,
+        address: &Pubkey,
+        offer: &Offer,
+        nft_mint: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        let token_program = self.mint_info(&offer.token_mint_a)?.token_program;
+        let taker = self.payer.pubkey();
+        let ata = |owner: &Pubkey, mint: &Pubkey| {
+            get_associated_token_address_with_program_id(owner, mint, &token_program)
+        };
+        let metadata = self
+            .fetch_metadata(nft_mint)?
+            .with_context(|| format!("{nft_mint} has no metadata"))?;
+        let royalties = Royalties::from_metadata(
+            NftSide::AnyInCollection,
+            &metadata,
+            self.vault_balance(address, offer)?,
+        );
+        let (mut instructions, royalty_accounts) =
+            royalty_recipients(&taker, &royalties, &offer.token_mint_a, &token_program);
+
+        let mut accounts = escrow::accounts::TakeCollectionBid {
+            taker,
+            maker: offer.maker,
+            token_mint_a: offer.token_mint_a,
+            nft_mint: *nft_mint,
+            taker_token_account_a: ata(&taker, &offer.token_mint_a),
+            taker_nft_account: ata(&taker, nft_mint),
+            maker_nft_account: ata(&offer.maker, nft_mint),
+            offer: *address,
+            vault: ata(address, &offer.token_mint_a),
+            nft_metadata: Metadata::find_pda(nft_mint).0,
+            associated_token_program: anchor_spl::associated_token::ID,
+            token_program,
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None);
+        accounts.extend(royalty_accounts);
+        instructions.push(Instruction {
+            program_id: escrow::ID,
+            accounts,
+            data: escrow::instruction::TakeCollectionBid {}.data(),
+        });
+        Ok(instructions)
+    }
+
+    pub fn close_offer_ix(&self, id: u64) -> Result<Instruction> {
+        let maker = self.payer.pubkey();
+        let address = offer_address(&maker, id);
+        let offer = self.fetch_offer(&address)?;
+        let token_program = self.mint_info(&offer.token_mint_a)?.token_program;
+        Ok(Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::CloseOffer {
+                maker,
+                offer: address,
+                token_mint_a: offer.token_mint_a,
+                vault: get_associated_token_address_with_program_id(
+                    &address,
+                    &offer.token_mint_a,
+                    &token_program,
+                ),
+                maker_ata_a: get_associated_token_address_with_program_id(
+                    &maker,
+                    &offer.token_mint_a,
+                    &token_program,
+                ),
+                token_program,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::CloseOffer { id }.data(),
+        })
+    }
+}
+
+/// Instructions creating each creator's token account for `payment_mint`, and
+/// those accounts in the order `take_offer` expects them.
+fn royalty_recipients(
+    taker: &Pubkey,
+    royalties: &Royalties,
+    payment_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> (Vec<Instruction>, Vec<AccountMeta>) {
+    royalties
+        .shares
+        .iter()
+        .map(|(creator, _)| {
+            (
+                create_associated_token_account_idempotent(
+                    taker,
+                    creator,
+                    payment_mint,
+                    token_program,
+                ),
+                AccountMeta::new(
+                    get_associated_token_address_with_program_id(
+                        creator,
+                        payment_mint,
+                        token_program,
+                    ),
+                    false,
+                ),
+            )
+        })
+        .unzip()
+}
+
+pub fn offer_address(maker: &Pubkey, id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"offer", maker.as_ref(), id.to_le_bytes().as_ref()],
+        &escrow::ID,
+    )
+    .0
+}
+
+pub fn vesting_address(offer: &Pubkey, beneficiary: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"vesting", offer.as_ref(), beneficiary.as_ref()],
+        &escrow::ID,
+    )
+    .0
+}
@@ -0,0 +1,309 @@
+//! Command line client for the escrow program.
+//!
+//! ```text
+//! escrow --url http://127.0.0.1:8899 make --id 1 --mint-a <MINT> --amount-a 10 --mint-b <MINT> --amount-b 100
+//! escrow make --id 2 --mint-a <NFT> --amount-a 1 --mint-b <MINT> --amount-b 5 --nft a --collection <KEY>
+//! escrow make --id 4 --mint-a <MINT> --amount-a 10 --mint-b <MINT> --amount-b 100 --floor 40 --auction-duration 3600
+//! escrow bid --id 3 --mint-a <MINT> --amount-a 50 --collection <COLLECTION_MINT>
+//! escrow take <BID> --nft <NFT>
+//! escrow list --maker <PUBKEY>
+//! escrow show <OFFER>
+//! escrow take <OFFER> --dry-run
+//! escrow close --id 1
+//! ```
+
+mod amount;
+mod client;
+
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, Subcommand};
+use escrow::{DutchAuction, NftSide, NftTerms, Offer, VestingSchedule};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Signer},
+};
+
+use amount::{format_amount, parse_amount};
+use client::{offer_address, EscrowClient};
+
+#[derive(Parser)]
+#[command(name = "escrow", about = "Make, take and inspect escrow offers")]
+struct Cli {
+    /// RPC endpoint, defaults to a local test validator.
+    #[arg(
+        long,
+        global = true,
+        env = "ESCROW_RPC_URL",
+        default_value = "http://127.0.0.1:8899"
+    )]
+    url: String,
+
+    /// Keypair paying for and signing transactions.
+    #[arg(
+        long,
+        global = true,
+        env = "ESCROW_KEYPAIR",
+        default_value = "~/.config/solana/id.json"
+    )]
+    keypair: String,
+
+    /// Simulate transactions instead of sending them.
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Lock token A in a new offer asking for token B.
+    Make {
+        #[arg(long)]
+        id: u64,
+        #[arg(long)]
+        mint_a: Pubkey,
+        /// Offered amount of token A, in whole tokens (e.g. `1.5`).
+        #[arg(long)]
+        amount_a: String,
+        #[arg(long)]
+        mint_b: Pubkey,
+        /// Wanted amount of token B, in whole tokens. The start price of an
+        /// auction.
+        #[arg(long)]
+        amount_b: String,
+        /// Lowest price of token B a Dutch auction falls to.
+        #[arg(long, requires = "auction_duration")]
+        floor: Option<String>,
+        /// Seconds from now until the auction reaches its floor.
+        #[arg(long, requires = "floor")]
+        auction_duration: Option<i64>,
+        /// Lower the price once every this many seconds instead of every
+        /// second.
+        #[arg(long, requires = "floor", default_value_t = 0)]
+        auction_step: i64,
+        /// Seconds after taking before any token A unlocks.
+        #[arg(long, requires = "vesting_duration")]
+        vesting_cliff: Option<i64>,
+        /// Seconds after taking until all token A is unlocked.
+        #[arg(long)]
+        vesting_duration: Option<i64>,
+        /// Which token is an NFT, `a` or `b`. Royalties are paid on taking.
+        #[arg(long, value_parser = parse_nft_side)]
+        nft: Option<NftSide>,
+        /// Collection the NFT must be a verified member of.
+        #[arg(long, requires = "nft")]
+        collection: Option<Pubkey>,
+    },
+    /// Lock token A as a bid for any NFT of a collection.
+    Bid {
+        #[arg(long)]
+        id: u64,
+        #[arg(long)]
+        mint_a: Pubkey,
+        /// Offered amount of token A, in whole tokens.
+        #[arg(long)]
+        amount_a: String,
+        /// Mint of the collection's NFT.
+        #[arg(long)]
+        collection: Pubkey,
+    },
+    /// Pay token B for an offer and receive its token A.
+    Take {
+        offer: Pubkey,
+        /// NFT to sell when the offer is a collection bid.
+        #[arg(long)]
+        nft: Option<Pubkey>,
+    },
+    /// Close one of your offers and get token A back.
+    Close {
+        #[arg(long)]
+        id: u64,
+    },
+    /// List open offers.
+    List {
+        #[arg(long)]
+        maker: Option<Pubkey>,
+        #[arg(long)]
+        mint_a: Option<Pubkey>,
+        #[arg(long)]
+        mint_b: Option<Pubkey>,
+    },
+    /// Print a single offer.
+    Show { offer: Pubkey },
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{home}/{rest}"),
+        _ => path.to_string(),
+    }
+}
+
+fn parse_nft_side(side: &str) -> Result<NftSide> {
+    match side {
+        "a" | "A" => Ok(NftSide::TokenA),
+        "b" | "B" => Ok(NftSide::TokenB),
+        _ => bail!("expected `a` or `b`, got `{side}`"),
+    }
+}
+
+fn print_offer(client: &EscrowClient, address: &Pubkey, offer: &Offer) -> Result<()> {
+    let mint_a = client.mint_info(&offer.token_mint_a)?;
+    let mint_b = client.mint_info(&offer.token_mint_b)?;
+    let offered = client.vault_balance(address, offer)?;
+
+    println!("Offer     {address}");
+    println!("Id        {}", offer.id);
+    println!("Maker     {}", offer.maker);
+    println!(
+        "Offers    {} of {}",
+        format_amount(offered, mint_a.decimals),
+        offer.token_mint_a
+    );
+    match offer.nft {
+        Some(nft) if nft.side == NftSide::AnyInCollection => {
+            println!("Wants     any NFT of collection {}", offer.token_mint_b)
+        }
+        // Auctions show the price they have fallen to by the cluster's clock
+        _ => println!(
+            "Wants     {} of {}",
+            format_amount(client.current_price(offer)?, mint_b.decimals),
+            offer.token_mint_b
+        ),
+    }
+    if let Some(auction) = offer.auction {
+        println!(
+            "Auction   from {} down to {} between {} and {}, step {}s",
+            format_amount(offer.token_b_wanted_amount, mint_b.decimals),
+            format_amount(auction.floor_price, mint_b.decimals),
+            auction.start_time,
+            auction.end_time,
+            auction.step_seconds
+        );
+    }
+    match offer.vesting {
+        Some(vesting) => println!(
+            "Vesting   cliff {}s, fully unlocked after {}s",
+            vesting.cliff_seconds, vesting.duration_seconds
+        ),
+        None => println!("Vesting   none"),
+    }
+    if let Some(nft) = offer.nft {
+        let side = match nft.side {
+            NftSide::TokenA => "token A",
+            NftSide::TokenB => "token B",
+            NftSide::AnyInCollection => "collection bid",
+        };
+        match nft.collection {
+            Some(collection) => println!("NFT       {side}, collection {collection}"),
+            None => println!("NFT       {side}"),
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let keypair_path = expand_home(&cli.keypair);
+    let payer = read_keypair_file(&keypair_path)
+        .map_err(|err| anyhow!("failed to read keypair {keypair_path}: {err}"))?;
+    let client = EscrowClient::new(&cli.url, payer, cli.dry_run);
+
+    match cli.command {
+        Command::Make {
+            id,
+            mint_a,
+            amount_a,
+            mint_b,
+            amount_b,
+            floor,
+            auction_duration,
+            auction_step,
+            vesting_cliff,
+            vesting_duration,
+            nft,
+            collection,
+        } => {
+            let offered = parse_amount(&amount_a, client.mint_info(&mint_a)?.decimals)?;
+            let decimals_b = client.mint_info(&mint_b)?.decimals;
+            let wanted = parse_amount(&amount_b, decimals_b)?;
+            let auction = match (floor, auction_duration) {
+                (Some(floor), Some(duration)) => {
+                    let start_time = client.cluster_time()?;
+                    Some(DutchAuction {
+                        floor_price: parse_amount(&floor, decimals_b)?,
+                        start_time,
+                        end_time: start_time + duration,
+                        step_seconds: auction_step,
+                    })
+                }
+                _ => None,
+            };
+            let vesting = vesting_duration.map(|duration_seconds| VestingSchedule {
+                cliff_seconds: vesting_cliff.unwrap_or(0),
+                duration_seconds,
+            });
+            let nft = nft.map(|side| NftTerms { side, collection });
+            let instruction = client
+                .make_offer_ix(id, &mint_a, offered, &mint_b, wanted, vesting, nft, auction)?;
+            client.send(&[instruction])?;
+            println!("Offer: {}", offer_address(&client.payer.pubkey(), id));
+        }
+        Command::Bid {
+            id,
+            mint_a,
+            amount_a,
+            collection,
+        } => {
+            let offered = parse_amount(&amount_a, client.mint_info(&mint_a)?.decimals)?;
+            let nft = NftTerms {
+                side: NftSide::AnyInCollection,
+                collection: Some(collection),
+            };
+            let instruction = client.make_offer_ix(
+                id,
+                &mint_a,
+                offered,
+                &collection,
+                1,
+                None,
+                Some(nft),
+                None,
+            )?;
+            client.send(&[instruction])?;
+            println!("Offer: {}", offer_address(&client.payer.pubkey(), id));
+        }
+        Command::Take { offer, nft } => {
+            let state = client.fetch_offer(&offer)?;
+            let instructions = match nft {
+                Some(nft_mint) => client.take_collection_bid_ixs(&offer, &state, &nft_mint)?,
+                None => client.take_offer_ixs(&offer, &state)?,
+            };
+            client.send(&instructions)?;
+        }
+        Command::Close { id } => {
+            let instruction = client.close_offer_ix(id)?;
+            client.send(&[instruction])?;
+        }
+        Command::List {
+            maker,
+            mint_a,
+            mint_b,
+        } => {
+            let offers = client.list_offers(maker, mint_a, mint_b)?;
+            if offers.is_empty() {
+                println!("No offers found");
+            }
+            for (address, offer) in offers {
+                print_offer(&client, &address, &offer)?;
+                println!();
+            }
+        }
+        Command::Show { offer } => {
+            let state = client.fetch_offer(&offer)?;
+            print_offer(&client, &offer, &state)?;
+        }
+    }
+    Ok(())
+}
@@ -3,3 +3,9 @@ use anchor_lang::prelude::*;
 #[constant]
 pub const SEED: &str = "anchor";
 pub const ANCHOR_DISCRIMINATOR: usize = 8;
+
+/// Layout version written into every new `Offer`.
+pub const OFFER_VERSION: u8 = 1;
+/// Bytes kept free at the end of `Offer` for future fields. Started at 128,
+/// `nft` took 35 of them and `auction` 33.
+pub const OFFER_RESERVED_SPACE: usize = 60;
@@ -4,4 +4,44 @@ use anchor_lang::prelude::*;
 pub enum ErrorCode {
     #[msg("Custom error message")]
     CustomError,
+    #[msg("Vesting duration must be positive and the cliff must fall inside it")]
+    InvalidVestingSchedule,
+    #[msg("This offer delivers token A through vesting, use take_offer_vested")]
+    OfferIsVested,
+    #[msg("This offer has no vesting schedule, use take_offer")]
+    OfferIsNotVested,
+    #[msg("No vested tokens are available to claim yet")]
+    NothingToClaim,
+    #[msg("Account data does not match any known Offer layout")]
+    UnknownOfferLayout,
+    #[msg("Offer already uses the current layout")]
+    OfferAlreadyMigrated,
+    #[msg("NFT offers need a mint with 0 decimals and a supply of 1, traded as a single token")]
+    NotAnNft,
+    #[msg("Pass the metadata account of the NFT")]
+    NftMetadataRequired,
+    #[msg("Not the Metaplex metadata account of this NFT")]
+    InvalidNftMetadata,
+    #[msg("The NFT is not a verified member of the collection")]
+    NotInCollection,
+    #[msg("NFT offers can't vest")]
+    NftOfferCannotVest,
+    #[msg("Pass a token account of every creator owed royalties, in metadata order")]
+    InvalidRoyaltyAccount,
+    #[msg("Collection bids want a single NFT and name token B's mint as their collection")]
+    InvalidCollectionBid,
+    #[msg("This offer is a collection bid, use take_collection_bid")]
+    OfferIsCollectionBid,
+    #[msg("This offer is not a collection bid, use take_offer")]
+    OfferIsNotCollectionBid,
+    #[msg("Auctions need a floor at or below the start price and steps that fit their window")]
+    InvalidAuction,
+    #[msg("Only fungible token B can be auctioned")]
+    AuctionNeedsFungiblePrice,
+    #[msg("This NFT has metadata, offer it with NFT terms so its royalties are paid")]
+    NftTermsRequired,
+    #[msg("Collection bids need the metadata of a collection NFT as token B")]
+    NotACollection,
+    #[msg("NFTs can only be traded for fungible tokens")]
+    NftForNftSwap,
 }
@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+use crate::{error::ErrorCode, Vesting};
+
+#[derive(Accounts)]
+pub struct ClaimVested<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = beneficiary,
+        has_one = token_mint,
+        seeds = [b"vesting", vesting.offer.as_ref(), beneficiary.key().as_ref()],
+        bump = vesting.bump,
+    )]
+    pub vesting: Account<'info, Vesting>,
+
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = vesting,
+        associated_token::token_program = token_program,
+    )]
+    pub vesting_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        associated_token::mint = token_mint,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program,
+    )]
+    pub beneficiary_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn release_vested_tokens(ctx: Context<ClaimVested>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let amount = ctx.accounts.vesting.claimable_amount(now);
+    require!(amount > 0, ErrorCode::NothingToClaim);
+
+    let offer_key = ctx.accounts.vesting.offer;
+    let beneficiary_key = ctx.accounts.beneficiary.key();
+    let signer_seeds: [&[&[u8]]; 1] = [&[
+        b"vesting",
+        offer_key.as_ref(),
+        beneficiary_key.as_ref(),
+        &[ctx.accounts.vesting.bump],
+    ]];
+
+    let accounts = TransferChecked {
+        from: ctx.accounts.vesting_vault.to_account_info(),
+        mint: ctx.accounts.token_mint.to_account_info(),
+        to: ctx.accounts.beneficiary_token_account.to_account_info(),
+        authority: ctx.accounts.vesting.to_account_info(),
+    };
+    let cpi_context = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        accounts,
+        &signer_seeds,
+    );
+    transfer_checked(cpi_context, amount, ctx.accounts.token_mint.decimals)?;
+
+    let vesting = &mut ctx.accounts.vesting;
+    vesting.claimed_amount += amount;
+    if vesting.claimed_amount < vesting.total_amount {
+        return Ok(());
+    }
+
+    // Fully claimed: give the rent of the vesting vault and account back.
+    let accounts = CloseAccount {
+        account: ctx.accounts.vesting_vault.to_account_info(),
+        destination: ctx.accounts.beneficiary.to_account_info(),
+        authority: ctx.accounts.vesting.to_account_info(),
+    };
+    let cpi_context = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        accounts,
+        &signer_seeds,
+    );
+    close_account(cpi_context)?;
+
+    ctx.accounts
+        .vesting
+        .close(ctx.accounts.beneficiary.to_account_info())
+}
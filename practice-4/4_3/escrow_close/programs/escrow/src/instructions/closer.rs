@@ -1,11 +1,7 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::Mint;
-use anchor_spl::{
-    associated_token::AssociatedToken,
-    token_interface::{
-        close_account, transfer_checked, CloseAccount, TokenAccount, TokenInterface,
-        TransferChecked,
-    },
+use anchor_spl::token_interface::{
+    close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+    TransferChecked,
 };
 
 use crate::state::Offer;
@@ -88,4 +84,4 @@ pub fn close_offer(context: Context<CloseOffer>, _id: u64) -> Result<()> {
     );
 
     close_account(close_vault_ctx)
-}
\ No newline at end of file
+}
@@ -5,7 +5,11 @@ use anchor_spl::{
     token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
 };
 
-use crate::{Offer, ANCHOR_DISCRIMINATOR};
+use crate::{
+    error::ErrorCode, is_nft_mint, read_metadata, require_collection_member, require_nft_mint,
+    DutchAuction, NftSide, NftTerms, Offer, VestingSchedule, ANCHOR_DISCRIMINATOR,
+    OFFER_RESERVED_SPACE, OFFER_VERSION,
+};
 #[derive(Accounts)]
 #[instruction(id: u64)]
 pub struct MakeOffer<'info> {
@@ -46,7 +50,115 @@ pub struct MakeOffer<'info> {
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+
+    /// CHECK: Metaplex metadata of the NFT, or of the collection a bid names.
+    /// Needed to verify a collection or that an NFT offered without NFT
+    /// terms has none. `read_metadata` checks the address and the owner.
+    pub nft_metadata: Option<UncheckedAccount<'info>>,
+}
+
+/// Offers without NFT terms pay no royalties, so they can't trade an NFT
+/// that has metadata. The maker passes the metadata account of an NFT mint
+/// to show it has none. There is only one such account, so at most one side
+/// can be an NFT.
+pub fn reject_nfts_with_metadata(context: &Context<MakeOffer>) -> Result<()> {
+    require!(
+        !(is_nft_mint(&context.accounts.token_mint_a)
+            && is_nft_mint(&context.accounts.token_mint_b)),
+        ErrorCode::NftForNftSwap
+    );
+    for mint in [
+        &context.accounts.token_mint_a,
+        &context.accounts.token_mint_b,
+    ] {
+        if !is_nft_mint(mint) {
+            continue;
+        }
+        let metadata = context
+            .accounts
+            .nft_metadata
+            .as_ref()
+            .ok_or(ErrorCode::NftMetadataRequired)?;
+        require!(
+            read_metadata(metadata, &mint.key())?.is_none(),
+            ErrorCode::NftTermsRequired
+        );
+    }
+    Ok(())
+}
+
+/// Checks the NFT side of an offer is a single NFT paid for with a fungible
+/// token and, when the maker names a collection, that the NFT is a verified
+/// member of it. Collection bids need token B's metadata to show it is a
+/// collection.
+pub fn check_nft_terms(
+    context: &Context<MakeOffer>,
+    nft: &NftTerms,
+    token_a_offered_amount: u64,
+    token_b_wanted_amount: u64,
+    vesting: Option<VestingSchedule>,
+) -> Result<()> {
+    require!(vesting.is_none(), ErrorCode::NftOfferCannotVest);
+    let mint = match nft.side {
+        NftSide::TokenA => {
+            require_nft_mint(&context.accounts.token_mint_a, token_a_offered_amount)?;
+            require!(
+                !is_nft_mint(&context.accounts.token_mint_b),
+                ErrorCode::NftForNftSwap
+            );
+            &context.accounts.token_mint_a
+        }
+        NftSide::TokenB => {
+            require_nft_mint(&context.accounts.token_mint_b, token_b_wanted_amount)?;
+            require!(
+                !is_nft_mint(&context.accounts.token_mint_a),
+                ErrorCode::NftForNftSwap
+            );
+            &context.accounts.token_mint_b
+        }
+        NftSide::AnyInCollection => {
+            // The NFT is only known once the bid is taken
+            require!(
+                token_b_wanted_amount == 1
+                    && nft.collection == Some(context.accounts.token_mint_b.key()),
+                ErrorCode::InvalidCollectionBid
+            );
+            require!(
+                !is_nft_mint(&context.accounts.token_mint_a),
+                ErrorCode::NftForNftSwap
+            );
+            return require_collection(context);
+        }
+    };
+
+    if let Some(collection) = nft.collection {
+        let metadata = context
+            .accounts
+            .nft_metadata
+            .as_ref()
+            .ok_or(ErrorCode::NftMetadataRequired)?;
+        let metadata = read_metadata(metadata, &mint.key())?.ok_or(ErrorCode::NotInCollection)?;
+        require_collection_member(&metadata, &collection)?;
+    }
+    Ok(())
+}
+
+/// Only a collection NFT carries `collection_details`, so a bid can't name
+/// an ordinary NFT no other NFT could ever be verified against.
+fn require_collection(context: &Context<MakeOffer>) -> Result<()> {
+    let metadata = context
+        .accounts
+        .nft_metadata
+        .as_ref()
+        .ok_or(ErrorCode::NftMetadataRequired)?;
+    let metadata = read_metadata(metadata, &context.accounts.token_mint_b.key())?;
+    require!(
+        metadata.is_some_and(|metadata| metadata.collection_details.is_some()),
+        ErrorCode::NotACollection
+    );
+    Ok(())
 }
+
 pub fn send_offered_tokens_to_vault(
     context: &Context<MakeOffer>,
     token_a_offered_amount: u64,
@@ -69,14 +181,40 @@ pub fn send_offered_tokens_to_vault(
         context.accounts.token_mint_a.decimals,
     )
 }
-pub fn save_offer(context: Context<MakeOffer>, id: u64, token_b_wanted_amount: u64) -> Result<()> {
+pub fn save_offer(
+    context: Context<MakeOffer>,
+    id: u64,
+    token_b_wanted_amount: u64,
+    vesting: Option<VestingSchedule>,
+    nft: Option<NftTerms>,
+    auction: Option<DutchAuction>,
+) -> Result<()> {
+    if let Some(schedule) = vesting {
+        require!(schedule.is_valid(), ErrorCode::InvalidVestingSchedule);
+    }
+    if let Some(auction) = auction {
+        require!(
+            auction.is_valid(token_b_wanted_amount),
+            ErrorCode::InvalidAuction
+        );
+        // The price decays in token B, which must not be the NFT
+        require!(
+            !matches!(nft, Some(NftTerms { side, .. }) if side != NftSide::TokenA),
+            ErrorCode::AuctionNeedsFungiblePrice
+        );
+    }
     context.accounts.offer.set_inner(Offer {
+        version: OFFER_VERSION,
         id,
         maker: context.accounts.maker.key(),
         token_mint_a: context.accounts.token_mint_a.key(),
         token_mint_b: context.accounts.token_mint_b.key(),
         token_b_wanted_amount,
         bump: context.bumps.offer,
+        vesting,
+        nft,
+        auction,
+        reserved: [0; OFFER_RESERVED_SPACE],
     });
     Ok(())
 }
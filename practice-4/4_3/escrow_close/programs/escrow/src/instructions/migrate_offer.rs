@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::{error::ErrorCode, Offer, OfferLayout};
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct MigrateOffer<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    /// CHECK: legacy offers do not deserialize as `Offer`, the layout is
+    /// checked by hand in `upgrade_offer_layout`.
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"offer", maker.key().as_ref(), id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub offer: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn upgrade_offer_layout(ctx: Context<MigrateOffer>, _id: u64) -> Result<()> {
+    let offer_info = ctx.accounts.offer.to_account_info();
+
+    let offer = {
+        let data = offer_info.try_borrow_data()?;
+        require!(
+            Offer::layout_of(&data)? != OfferLayout::V1,
+            ErrorCode::OfferAlreadyMigrated
+        );
+        Offer::try_from_any_layout(&data)?
+    };
+
+    // The maker pays for the extra bytes of the new layout.
+    let required_lamports = Rent::get()?.minimum_balance(Offer::SPACE);
+    let missing_lamports = required_lamports.saturating_sub(offer_info.lamports());
+    if missing_lamports > 0 {
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.maker.to_account_info(),
+                to: offer_info.clone(),
+            },
+        );
+        transfer(cpi_context, missing_lamports)?;
+    }
+
+    offer_info.realloc(Offer::SPACE, false)?;
+
+    let mut data = offer_info.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    offer.try_serialize(&mut writer)
+}
@@ -4,3 +4,11 @@ pub mod take_offer;
 pub use take_offer::*;
 pub mod closer;
 pub use closer::*;
+pub mod take_offer_vested;
+pub use take_offer_vested::*;
+pub mod claim_vested;
+pub use claim_vested::*;
+pub mod migrate_offer;
+pub use migrate_offer::*;
+pub mod take_collection_bid;
+pub use take_collection_bid::*;
@@ -0,0 +1,141 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::ErrorCode, pay_royalties, read_metadata, require_collection_member, require_nft_mint,
+    withdraw_and_close_vault_to, NftSide, Offer, Royalties,
+};
+
+/// Sells any NFT of the bid's collection to the maker. The creators' token
+/// accounts for token A follow as remaining accounts, see `pay_royalties`.
+#[derive(Accounts)]
+pub struct TakeCollectionBid<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+
+    pub token_mint_a: Box<InterfaceAccount<'info, Mint>>,
+
+    /// The NFT the taker sells, checked against the collection by
+    /// `check_collection_member`.
+    pub nft_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program,
+    )]
+    pub taker_token_account_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = nft_mint,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program,
+    )]
+    pub taker_nft_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = nft_mint,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_nft_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        has_one = token_mint_a,
+        constraint = offer.nft.is_some_and(|nft| nft.side == NftSide::AnyInCollection)
+            @ ErrorCode::OfferIsNotCollectionBid,
+    )]
+    pub offer: Box<Account<'info, Offer>>,
+    #[account(
+        mut,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = offer,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Metaplex metadata of `nft_mint`. `read_metadata` checks the
+    /// address and the owner.
+    pub nft_metadata: UncheckedAccount<'info>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Checks the taker's NFT is a verified member of the bid's collection and
+/// returns the royalties its creators are owed out of the bid.
+pub fn check_collection_member(ctx: &Context<TakeCollectionBid>) -> Result<Royalties> {
+    require_nft_mint(&ctx.accounts.nft_mint, 1)?;
+    let metadata = read_metadata(&ctx.accounts.nft_metadata, &ctx.accounts.nft_mint.key())?
+        .ok_or(ErrorCode::NotInCollection)?;
+    require_collection_member(&metadata, &ctx.accounts.offer.token_mint_b)?;
+    Ok(Royalties::from_metadata(
+        NftSide::AnyInCollection,
+        &metadata,
+        ctx.accounts.vault.amount,
+    ))
+}
+
+pub fn send_nft_to_maker(ctx: &Context<TakeCollectionBid>) -> Result<()> {
+    let transfer_accounts = TransferChecked {
+        from: ctx.accounts.taker_nft_account.to_account_info(),
+        mint: ctx.accounts.nft_mint.to_account_info(),
+        to: ctx.accounts.maker_nft_account.to_account_info(),
+        authority: ctx.accounts.taker.to_account_info(),
+    };
+
+    let cpi_context = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        transfer_accounts,
+    );
+    transfer_checked(cpi_context, 1, ctx.accounts.nft_mint.decimals)
+}
+
+/// Pays the royalties out of the vault and hands the rest of the bid to the
+/// taker.
+pub fn withdraw_and_close_bid_vault<'info>(
+    ctx: Context<'_, '_, '_, 'info, TakeCollectionBid<'info>>,
+    royalties: &Royalties,
+) -> Result<()> {
+    let offer = &ctx.accounts.offer;
+    let signer_seeds: [&[&[u8]]; 1] = [&[
+        b"offer",
+        offer.maker.as_ref(),
+        &offer.id.to_le_bytes()[..],
+        &[offer.bump],
+    ]];
+    pay_royalties(
+        royalties,
+        ctx.remaining_accounts,
+        ctx.accounts.vault.to_account_info(),
+        &ctx.accounts.token_mint_a,
+        offer.to_account_info(),
+        &signer_seeds,
+        &ctx.accounts.token_program,
+    )?;
+    ctx.accounts.vault.reload()?;
+
+    withdraw_and_close_vault_to(
+        &ctx.accounts.offer,
+        &ctx.accounts.vault,
+        &ctx.accounts.token_mint_a,
+        ctx.accounts.taker_token_account_a.to_account_info(),
+        ctx.accounts.taker.to_account_info(),
+        &ctx.accounts.token_program,
+    )
+}
@@ -8,7 +8,7 @@ use anchor_spl::{
     },
 };
 
-use crate::Offer;
+use crate::{error::ErrorCode, read_metadata, NftSide, Offer, Royalties};
 #[derive(Accounts)]
 pub struct TakeOffer<'info> {
     #[account(mut)]
@@ -66,8 +66,111 @@ pub struct TakeOffer<'info> {
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+
+    /// CHECK: Metaplex metadata of the NFT, required for NFT offers.
+    /// `read_metadata` checks the address and the owner.
+    pub nft_metadata: Option<UncheckedAccount<'info>>,
+}
+
+/// Royalties owed when an NFT offer settles, `None` for other offers. The
+/// royalty is taken from whatever pays for the NFT: token B sent by the taker
+/// or token A released from the vault.
+pub fn nft_royalties(ctx: &Context<TakeOffer>) -> Result<Option<Royalties>> {
+    let Some(nft) = ctx.accounts.offer.nft else {
+        return Ok(None);
+    };
+    let metadata = ctx
+        .accounts
+        .nft_metadata
+        .as_ref()
+        .ok_or(ErrorCode::NftMetadataRequired)?;
+    let (nft_mint, price) = match nft.side {
+        NftSide::TokenA => (
+            ctx.accounts.token_mint_a.key(),
+            ctx.accounts
+                .offer
+                .wanted_amount_at(Clock::get()?.unix_timestamp),
+        ),
+        NftSide::TokenB => (ctx.accounts.token_mint_b.key(), ctx.accounts.vault.amount),
+        NftSide::AnyInCollection => return err!(ErrorCode::OfferIsCollectionBid),
+    };
+    let royalties = match read_metadata(metadata, &nft_mint)? {
+        Some(metadata) => Royalties::from_metadata(nft.side, &metadata, price),
+        None => Royalties {
+            side: nft.side,
+            shares: Vec::new(),
+        },
+    };
+    Ok(Some(royalties))
 }
-pub fn send_wanted_tokens_to_maker(ctx: &Context<TakeOffer>) -> Result<()> {
+
+/// Pays every creator its royalty out of `from`. The creators' token accounts
+/// for `mint` are passed as remaining accounts, one for each creator owed a
+/// share, in the order of the metadata.
+#[allow(clippy::too_many_arguments)]
+pub fn pay_royalties<'info>(
+    royalties: &Royalties,
+    recipients: &[AccountInfo<'info>],
+    from: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    require!(
+        recipients.len() == royalties.shares.len(),
+        ErrorCode::InvalidRoyaltyAccount
+    );
+    for ((creator, amount), recipient) in royalties.shares.iter().zip(recipients) {
+        require_keys_eq!(
+            *recipient.owner,
+            token_program.key(),
+            ErrorCode::InvalidRoyaltyAccount
+        );
+        let account = TokenAccount::try_deserialize(&mut &recipient.try_borrow_data()?[..])
+            .map_err(|_| error!(ErrorCode::InvalidRoyaltyAccount))?;
+        require!(
+            account.mint == mint.key() && account.owner == *creator,
+            ErrorCode::InvalidRoyaltyAccount
+        );
+        if *amount == 0 {
+            continue;
+        }
+
+        let accounts = TransferChecked {
+            from: from.clone(),
+            mint: mint.to_account_info(),
+            to: recipient.clone(),
+            authority: authority.clone(),
+        };
+        let cpi_context =
+            CpiContext::new_with_signer(token_program.to_account_info(), accounts, signer_seeds);
+        transfer_checked(cpi_context, *amount, mint.decimals)?;
+    }
+    Ok(())
+}
+
+pub fn send_wanted_tokens_to_maker<'info>(
+    ctx: &Context<'_, '_, '_, 'info, TakeOffer<'info>>,
+    royalties: Option<&Royalties>,
+) -> Result<()> {
+    let mut amount = ctx
+        .accounts
+        .offer
+        .wanted_amount_at(Clock::get()?.unix_timestamp);
+    if let Some(royalties) = royalties.filter(|royalties| royalties.side == NftSide::TokenA) {
+        pay_royalties(
+            royalties,
+            ctx.remaining_accounts,
+            ctx.accounts.taker_token_account_b.to_account_info(),
+            &ctx.accounts.token_mint_b,
+            ctx.accounts.taker.to_account_info(),
+            &[],
+            &ctx.accounts.token_program,
+        )?;
+        amount -= royalties.total();
+    }
+
     let transfer_accounts = TransferChecked {
         from: ctx.accounts.taker_token_account_b.to_account_info(),
         mint: ctx.accounts.token_mint_b.to_account_info(),
@@ -79,48 +182,82 @@ pub fn send_wanted_tokens_to_maker(ctx: &Context<TakeOffer>) -> Result<()> {
         ctx.accounts.token_program.to_account_info(),
         transfer_accounts,
     );
-    transfer_checked(
-        cpi_context,
-        ctx.accounts.offer.token_b_wanted_amount,
-        ctx.accounts.token_mint_b.decimals,
+    transfer_checked(cpi_context, amount, ctx.accounts.token_mint_b.decimals)
+}
+pub fn withdraw_and_close_vault<'info>(
+    ctx: Context<'_, '_, '_, 'info, TakeOffer<'info>>,
+    royalties: Option<&Royalties>,
+) -> Result<()> {
+    require!(
+        ctx.accounts.offer.vesting.is_none(),
+        ErrorCode::OfferIsVested
+    );
+
+    if let Some(royalties) = royalties.filter(|royalties| royalties.side == NftSide::TokenB) {
+        let offer = &ctx.accounts.offer;
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"offer",
+            offer.maker.as_ref(),
+            &offer.id.to_le_bytes()[..],
+            &[offer.bump],
+        ]];
+        pay_royalties(
+            royalties,
+            ctx.remaining_accounts,
+            ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.token_mint_a,
+            offer.to_account_info(),
+            &signer_seeds,
+            &ctx.accounts.token_program,
+        )?;
+        ctx.accounts.vault.reload()?;
+    }
+
+    withdraw_and_close_vault_to(
+        &ctx.accounts.offer,
+        &ctx.accounts.vault,
+        &ctx.accounts.token_mint_a,
+        ctx.accounts.taker_token_account_a.to_account_info(),
+        ctx.accounts.taker.to_account_info(),
+        &ctx.accounts.token_program,
     )
 }
-pub fn withdraw_and_close_vault(ctx: Context<TakeOffer>) -> Result<()> {
+
+/// Moves everything held by the offer's vault into `destination` and closes
+/// the vault, sending its rent to `rent_receiver`.
+pub fn withdraw_and_close_vault_to<'info>(
+    offer: &Account<'info, Offer>,
+    vault: &InterfaceAccount<'info, TokenAccount>,
+    token_mint_a: &InterfaceAccount<'info, Mint>,
+    destination: AccountInfo<'info>,
+    rent_receiver: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
     let signer_seeds: [&[&[u8]]; 1] = [&[
         b"offer",
-        ctx.accounts.maker.to_account_info().key.as_ref(),
-        &ctx.accounts.offer.id.to_le_bytes()[..],
-        &[ctx.accounts.offer.bump],
+        offer.maker.as_ref(),
+        &offer.id.to_le_bytes()[..],
+        &[offer.bump],
     ]];
 
     let accounts = TransferChecked {
-        from: ctx.accounts.vault.to_account_info(),
-        mint: ctx.accounts.token_mint_a.to_account_info(),
-        to: ctx.accounts.taker_token_account_a.to_account_info(),
-        authority: ctx.accounts.offer.to_account_info(),
+        from: vault.to_account_info(),
+        mint: token_mint_a.to_account_info(),
+        to: destination,
+        authority: offer.to_account_info(),
     };
 
-    let cpi_context = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        accounts,
-        &signer_seeds,
-    );
-    transfer_checked(
-        cpi_context,
-        ctx.accounts.vault.amount,
-        ctx.accounts.token_mint_a.decimals,
-    )?;
+    let cpi_context =
+        CpiContext::new_with_signer(token_program.to_account_info(), accounts, &signer_seeds);
+    transfer_checked(cpi_context, vault.amount, token_mint_a.decimals)?;
     let accounts = CloseAccount {
-        account: ctx.accounts.vault.to_account_info(),
-        destination: ctx.accounts.taker.to_account_info(),
-        authority: ctx.accounts.offer.to_account_info(),
+        account: vault.to_account_info(),
+        destination: rent_receiver,
+        authority: offer.to_account_info(),
     };
 
-    let cpi_context = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        accounts,
-        &signer_seeds,
-    );
+    let cpi_context =
+        CpiContext::new_with_signer(token_program.to_account_info(), accounts, &signer_seeds);
 
     close_account(cpi_context)
 }
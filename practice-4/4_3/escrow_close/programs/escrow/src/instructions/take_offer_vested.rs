@@ -0,0 +1,133 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{error::ErrorCode, withdraw_and_close_vault_to, Offer, Vesting, ANCHOR_DISCRIMINATOR};
+
+#[derive(Accounts)]
+pub struct TakeOfferVested<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+
+    pub token_mint_a: InterfaceAccount<'info, Mint>,
+
+    pub token_mint_b: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint_b,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program,
+    )]
+    pub taker_token_account_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = token_mint_b,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_token_account_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        has_one = token_mint_a,
+        has_one = token_mint_b,
+    )]
+    pub offer: Box<Account<'info, Offer>>,
+    #[account(
+        mut,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = offer,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init,
+        payer = taker,
+        space = ANCHOR_DISCRIMINATOR + Vesting::INIT_SPACE,
+        seeds = [b"vesting", offer.key().as_ref(), taker.key().as_ref()],
+        bump
+    )]
+    pub vesting: Box<Account<'info, Vesting>>,
+    /// Anyone can create the associated token account of the vesting PDA
+    /// ahead of the taker, so it may already exist.
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = vesting,
+        associated_token::token_program = token_program,
+    )]
+    pub vesting_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn send_wanted_tokens_to_maker_vested(ctx: &Context<TakeOfferVested>) -> Result<()> {
+    let transfer_accounts = TransferChecked {
+        from: ctx.accounts.taker_token_account_b.to_account_info(),
+        mint: ctx.accounts.token_mint_b.to_account_info(),
+        to: ctx.accounts.maker_token_account_b.to_account_info(),
+        authority: ctx.accounts.taker.to_account_info(),
+    };
+
+    let cpi_context = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        transfer_accounts,
+    );
+    transfer_checked(
+        cpi_context,
+        ctx.accounts
+            .offer
+            .wanted_amount_at(Clock::get()?.unix_timestamp),
+        ctx.accounts.token_mint_b.decimals,
+    )
+}
+
+pub fn lock_vault_in_vesting(ctx: Context<TakeOfferVested>) -> Result<()> {
+    let schedule = ctx
+        .accounts
+        .offer
+        .vesting
+        .ok_or(ErrorCode::OfferIsNotVested)?;
+    let start_ts = Clock::get()?.unix_timestamp;
+    let cliff_ts = start_ts
+        .checked_add(schedule.cliff_seconds)
+        .ok_or(ErrorCode::InvalidVestingSchedule)?;
+    let end_ts = start_ts
+        .checked_add(schedule.duration_seconds)
+        .ok_or(ErrorCode::InvalidVestingSchedule)?;
+
+    ctx.accounts.vesting.set_inner(Vesting {
+        beneficiary: ctx.accounts.taker.key(),
+        token_mint: ctx.accounts.token_mint_a.key(),
+        offer: ctx.accounts.offer.key(),
+        total_amount: ctx.accounts.vault.amount,
+        claimed_amount: 0,
+        start_ts,
+        cliff_ts,
+        end_ts,
+        bump: ctx.bumps.vesting,
+    });
+
+    withdraw_and_close_vault_to(
+        &ctx.accounts.offer,
+        &ctx.accounts.vault,
+        &ctx.accounts.token_mint_a,
+        ctx.accounts.vesting_vault.to_account_info(),
+        ctx.accounts.taker.to_account_info(),
+        &ctx.accounts.token_program,
+    )
+}
@@ -1,3 +1,5 @@
+#![allow(unexpected_cfgs)]
+
 use anchor_lang::prelude::*;
 
 pub mod constants;
@@ -6,6 +8,8 @@ pub mod instructions;
 pub mod state;
 
 pub use constants::*;
+// `closer::close_offer` shares its name with the `close_offer` handler.
+#[allow(ambiguous_glob_reexports)]
 pub use instructions::*;
 pub use state::*;
 
@@ -20,19 +24,52 @@ pub mod escrow {
         id: u64,
         token_a_offered_amount: u64,
         token_b_wanted_amount: u64,
+        vesting: Option<VestingSchedule>,
+        nft: Option<NftTerms>,
+        auction: Option<DutchAuction>,
     ) -> Result<()> {
+        match &nft {
+            Some(terms) => instructions::make_offer::check_nft_terms(
+                &ctx,
+                terms,
+                token_a_offered_amount,
+                token_b_wanted_amount,
+                vesting,
+            )?,
+            None => instructions::make_offer::reject_nfts_with_metadata(&ctx)?,
+        }
         instructions::make_offer::send_offered_tokens_to_vault(&ctx, token_a_offered_amount)?;
-        instructions::make_offer::save_offer(ctx, id, token_b_wanted_amount)
+        instructions::make_offer::save_offer(ctx, id, token_b_wanted_amount, vesting, nft, auction)
+    }
+
+    pub fn take_offer<'info>(ctx: Context<'_, '_, '_, 'info, TakeOffer<'info>>) -> Result<()> {
+        let royalties = instructions::take_offer::nft_royalties(&ctx)?;
+        instructions::take_offer::send_wanted_tokens_to_maker(&ctx, royalties.as_ref())?;
+        instructions::take_offer::withdraw_and_close_vault(ctx, royalties.as_ref())
+    }
+
+    pub fn take_collection_bid<'info>(
+        ctx: Context<'_, '_, '_, 'info, TakeCollectionBid<'info>>,
+    ) -> Result<()> {
+        let royalties = instructions::take_collection_bid::check_collection_member(&ctx)?;
+        instructions::take_collection_bid::send_nft_to_maker(&ctx)?;
+        instructions::take_collection_bid::withdraw_and_close_bid_vault(ctx, &royalties)
     }
 
-    pub fn take_offer(ctx: Context<TakeOffer>) -> Result<()> {
-        instructions::take_offer::send_wanted_tokens_to_maker(&ctx)?;
-        instructions::take_offer::withdraw_and_close_vault(ctx)
+    pub fn take_offer_vested(ctx: Context<TakeOfferVested>) -> Result<()> {
+        instructions::take_offer_vested::send_wanted_tokens_to_maker_vested(&ctx)?;
+        instructions::take_offer_vested::lock_vault_in_vesting(ctx)
     }
 
+    pub fn claim_vested(ctx: Context<ClaimVested>) -> Result<()> {
+        instructions::claim_vested::release_vested_tokens(ctx)
+    }
 
     pub fn close_offer(ctx: Context<CloseOffer>, id: u64) -> Result<()> {
+        instructions::closer::close_offer(ctx, id)
+    }
 
-        instructions::closer::close_offer(ctx,id)
+    pub fn migrate_offer(ctx: Context<MigrateOffer>, id: u64) -> Result<()> {
+        instructions::migrate_offer::upgrade_offer_layout(ctx, id)
     }
 }
@@ -0,0 +1,99 @@
+use anchor_lang::prelude::*;
+
+/// Dutch auction on token B. The ask starts at the offer's
+/// `token_b_wanted_amount` and falls to `floor_price` between `start_time`
+/// and `end_time`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct DutchAuction {
+    pub floor_price: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    /// `0` lowers the ask every second. Otherwise it drops once every
+    /// `step_seconds`, by the same amount each time.
+    pub step_seconds: i64,
+}
+
+impl DutchAuction {
+    pub fn is_valid(&self, start_price: u64) -> bool {
+        let window = self.end_time.checked_sub(self.start_time).unwrap_or(0);
+        self.floor_price <= start_price
+            && window > 0
+            && self.step_seconds >= 0
+            && self.step_seconds <= window
+    }
+
+    /// The ask at `now`. Before the window it is the start price, after it
+    /// the floor.
+    pub fn price_at(&self, start_price: u64, now: i64) -> u64 {
+        if now >= self.end_time {
+            return self.floor_price;
+        }
+        let mut elapsed = now.max(self.start_time) - self.start_time;
+        if self.step_seconds > 0 {
+            elapsed -= elapsed % self.step_seconds;
+        }
+        let window = self.end_time - self.start_time;
+        let drop = u128::from(start_price - self.floor_price) * elapsed as u128 / window as u128;
+        start_price - drop as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auction(step_seconds: i64) -> DutchAuction {
+        DutchAuction {
+            floor_price: 400,
+            start_time: 1_000,
+            end_time: 1_600,
+            step_seconds,
+        }
+    }
+
+    #[test]
+    fn linear_prices_fall_every_second() {
+        let linear = auction(0);
+        assert_eq!(linear.price_at(1_000, 0), 1_000);
+        assert_eq!(linear.price_at(1_000, 1_000), 1_000);
+        assert_eq!(linear.price_at(1_000, 1_001), 999);
+        assert_eq!(linear.price_at(1_000, 1_300), 700);
+        assert_eq!(linear.price_at(1_000, 1_600), 400);
+        assert_eq!(linear.price_at(1_000, i64::MAX), 400);
+        assert_eq!(linear.price_at(u64::MAX, 1_300), u64::MAX / 2 + 201);
+    }
+
+    #[test]
+    fn stepwise_prices_hold_between_steps() {
+        let stepwise = auction(200);
+        assert_eq!(stepwise.price_at(1_000, 1_199), 1_000);
+        assert_eq!(stepwise.price_at(1_000, 1_200), 800);
+        assert_eq!(stepwise.price_at(1_000, 1_599), 600);
+        assert_eq!(stepwise.price_at(1_000, 1_600), 400);
+
+        // A last step shorter than the others still ends on the floor
+        let uneven = auction(250);
+        assert_eq!(uneven.price_at(1_000, 1_599), 500);
+        assert_eq!(uneven.price_at(1_000, 1_600), 400);
+    }
+
+    #[test]
+    fn validates_auctions() {
+        assert!(auction(0).is_valid(1_000));
+        assert!(auction(600).is_valid(400));
+        assert!(!auction(0).is_valid(399));
+        assert!(!auction(-1).is_valid(1_000));
+        assert!(!auction(601).is_valid(1_000));
+        let backwards = DutchAuction {
+            end_time: 1_000,
+            ..auction(0)
+        };
+        assert!(!backwards.is_valid(1_000));
+        let overflowing = DutchAuction {
+            start_time: i64::MIN,
+            end_time: i64::MAX,
+            ..auction(0)
+        };
+        assert!(!overflowing.is_valid(1_000));
+    }
+}
@@ -1,2 +1,8 @@
 pub mod offer;
 pub use offer::*;
+pub mod vesting;
+pub use vesting::*;
+pub mod nft;
+pub use nft::*;
+pub mod auction;
+pub use auction::*;
@@ -0,0 +1,187 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{metadata::mpl_token_metadata::accounts::Metadata, token_interface::Mint};
+
+use crate::error::ErrorCode;
+
+/// Which token of an offer is the NFT. The other one pays for it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum NftSide {
+    /// The maker sells the NFT locked in the vault.
+    TokenA,
+    /// The maker buys the NFT with the tokens locked in the vault.
+    TokenB,
+    /// The maker bids the tokens locked in the vault for any NFT verified as
+    /// a member of `collection`. `token_mint_b` holds the collection mint
+    /// instead of the NFT's, the taker picks the NFT.
+    AnyInCollection,
+}
+
+/// Terms of an offer trading one NFT against fungible tokens.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct NftTerms {
+    pub side: NftSide,
+    /// Collection the NFT was verified to belong to when the offer was made.
+    pub collection: Option<Pubkey>,
+}
+
+/// Royalty owed to each creator of the NFT when an offer settles.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Royalties {
+    pub side: NftSide,
+    pub shares: Vec<(Pubkey, u64)>,
+}
+
+impl Royalties {
+    /// Splits `seller_fee_basis_points` of `price` between the creators by
+    /// their shares. Rounding dust stays with the seller.
+    pub fn from_metadata(side: NftSide, metadata: &Metadata, price: u64) -> Self {
+        let basis_points = metadata.seller_fee_basis_points.min(10_000);
+        let royalty = u128::from(price) * u128::from(basis_points) / 10_000;
+        let shares = metadata
+            .creators
+            .iter()
+            .flatten()
+            .filter(|creator| creator.share > 0)
+            .map(|creator| {
+                let amount = royalty * u128::from(creator.share) / 100;
+                (creator.address, amount as u64)
+            })
+            .collect();
+        Royalties { side, shares }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.shares.iter().map(|(_, amount)| amount).sum()
+    }
+}
+
+/// An NFT mint has no decimals and exactly one token in circulation.
+pub fn is_nft_mint(mint: &InterfaceAccount<Mint>) -> bool {
+    mint.decimals == 0 && mint.supply == 1
+}
+
+pub fn require_nft_mint(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<()> {
+    require!(is_nft_mint(mint) && amount == 1, ErrorCode::NotAnNft);
+    Ok(())
+}
+
+/// Reads the Metaplex metadata of `mint`. `metadata` must be the metadata PDA
+/// of the mint; `None` means the NFT never got metadata.
+pub fn read_metadata(metadata: &AccountInfo, mint: &Pubkey) -> Result<Option<Metadata>> {
+    require_keys_eq!(
+        metadata.key(),
+        Metadata::find_pda(mint).0,
+        ErrorCode::InvalidNftMetadata
+    );
+    if metadata.data_is_empty() {
+        return Ok(None);
+    }
+    require_keys_eq!(
+        *metadata.owner,
+        anchor_spl::metadata::ID,
+        ErrorCode::InvalidNftMetadata
+    );
+    let metadata = Metadata::safe_deserialize(&metadata.try_borrow_data()?)
+        .map_err(|_| error!(ErrorCode::InvalidNftMetadata))?;
+    Ok(Some(metadata))
+}
+
+/// Only the collection's update authority can verify a member, so a verified
+/// collection entry can't be forged by the NFT's owner.
+pub fn require_collection_member(metadata: &Metadata, collection: &Pubkey) -> Result<()> {
+    require!(
+        metadata
+            .collection
+            .as_ref()
+            .is_some_and(|member| member.verified && member.key == *collection),
+        ErrorCode::NotInCollection
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_spl::metadata::mpl_token_metadata::types::{Collection, Creator, Key};
+
+    fn metadata(seller_fee_basis_points: u16, creators: Vec<(Pubkey, u8)>) -> Metadata {
+        Metadata {
+            key: Key::MetadataV1,
+            update_authority: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            name: "Escrowed".to_string(),
+            symbol: "ESC".to_string(),
+            uri: String::new(),
+            seller_fee_basis_points,
+            creators: Some(
+                creators
+                    .into_iter()
+                    .map(|(address, share)| Creator {
+                        address,
+                        verified: true,
+                        share,
+                    })
+                    .collect(),
+            ),
+            primary_sale_happened: true,
+            is_mutable: true,
+            edition_nonce: None,
+            token_standard: None,
+            collection: None,
+            uses: None,
+            collection_details: None,
+            programmable_config: None,
+        }
+    }
+
+    #[test]
+    fn splits_royalties_by_share() {
+        let (first, second, idle) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let metadata = metadata(500, vec![(first, 70), (idle, 0), (second, 30)]);
+
+        let royalties = Royalties::from_metadata(NftSide::TokenA, &metadata, 1_001);
+        // 5% of 1_001 is 50, split 35 / 15
+        assert_eq!(royalties.shares, vec![(first, 35), (second, 15)]);
+        assert_eq!(royalties.total(), 50);
+
+        let royalties = Royalties::from_metadata(NftSide::TokenB, &metadata, u64::MAX);
+        assert!(royalties.total() < u64::MAX / 10);
+    }
+
+    #[test]
+    fn no_creators_means_no_royalties() {
+        let mut metadata = metadata(1_000, Vec::new());
+        assert_eq!(
+            Royalties::from_metadata(NftSide::TokenA, &metadata, 100).total(),
+            0
+        );
+        metadata.creators = None;
+        assert!(Royalties::from_metadata(NftSide::TokenA, &metadata, 100)
+            .shares
+            .is_empty());
+    }
+
+    #[test]
+    fn only_verified_collections_count() {
+        let collection = Pubkey::new_unique();
+        let mut metadata = metadata(0, Vec::new());
+        assert!(require_collection_member(&metadata, &collection).is_err());
+
+        metadata.collection = Some(Collection {
+            verified: false,
+            key: collection,
+        });
+        assert!(require_collection_member(&metadata, &collection).is_err());
+
+        metadata.collection = Some(Collection {
+            verified: true,
+            key: collection,
+        });
+        require_collection_member(&metadata, &collection).unwrap();
+        assert!(require_collection_member(&metadata, &Pubkey::new_unique()).is_err());
+    }
+}
@@ -1,8 +1,37 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 
+use crate::{
+    error::ErrorCode, DutchAuction, NftTerms, ANCHOR_DISCRIMINATOR, OFFER_RESERVED_SPACE,
+    OFFER_VERSION,
+};
+
+/// Current offer layout. New fields must be carved out of `reserved` so the
+/// account size stays the same across versions.
 #[account]
 #[derive(InitSpace)]
 pub struct Offer {
+    pub version: u8,
+    pub id: u64,
+    pub maker: Pubkey,
+    pub token_mint_a: Pubkey,
+    pub token_mint_b: Pubkey,
+    pub token_b_wanted_amount: u64,
+    pub bump: u8,
+    pub vesting: Option<VestingSchedule>,
+    /// Set when one of the tokens is an NFT. Offers written before it
+    /// existed read zeroed reserved bytes here, which decode as `None`.
+    pub nft: Option<NftTerms>,
+    /// Set when the ask falls over time, `token_b_wanted_amount` is then the
+    /// start price.
+    pub auction: Option<DutchAuction>,
+    pub reserved: [u8; OFFER_RESERVED_SPACE],
+}
+
+/// Original layout, written before offers could vest or carried a version
+/// byte. Only read by `migrate_offer` and the decode helpers below.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct OfferV0 {
     pub id: u64,
     pub maker: Pubkey,
     pub token_mint_a: Pubkey,
@@ -10,3 +39,184 @@ pub struct Offer {
     pub token_b_wanted_amount: u64,
     pub bump: u8,
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OfferLayout {
+    V0,
+    V1,
+}
+
+impl Offer {
+    pub const V0_SPACE: usize = ANCHOR_DISCRIMINATOR + OfferV0::INIT_SPACE;
+    pub const SPACE: usize = ANCHOR_DISCRIMINATOR + Offer::INIT_SPACE;
+
+    /// Tells the layouts apart by size, then by version byte.
+    pub fn layout_of(data: &[u8]) -> Result<OfferLayout> {
+        require!(
+            data.len() >= ANCHOR_DISCRIMINATOR
+                && data[..ANCHOR_DISCRIMINATOR] == *Offer::DISCRIMINATOR,
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        match data.len() {
+            Offer::V0_SPACE => Ok(OfferLayout::V0),
+            Offer::SPACE if data[ANCHOR_DISCRIMINATOR] == OFFER_VERSION => Ok(OfferLayout::V1),
+            _ => err!(ErrorCode::UnknownOfferLayout),
+        }
+    }
+
+    /// Token B the taker pays at `now`.
+    pub fn wanted_amount_at(&self, now: i64) -> u64 {
+        match self.auction {
+            Some(auction) => auction.price_at(self.token_b_wanted_amount, now),
+            None => self.token_b_wanted_amount,
+        }
+    }
+
+    /// Decodes an offer account written with any of the layouts.
+    pub fn try_from_any_layout(data: &[u8]) -> Result<Offer> {
+        let mut fields = &data[ANCHOR_DISCRIMINATOR..];
+        match Offer::layout_of(data)? {
+            OfferLayout::V0 => Ok(OfferV0::deserialize(&mut fields)?.into()),
+            OfferLayout::V1 => Offer::try_deserialize(&mut &data[..]),
+        }
+    }
+}
+
+impl From<OfferV0> for Offer {
+    fn from(legacy: OfferV0) -> Self {
+        Offer {
+            version: OFFER_VERSION,
+            id: legacy.id,
+            maker: legacy.maker,
+            token_mint_a: legacy.token_mint_a,
+            token_mint_b: legacy.token_mint_b,
+            token_b_wanted_amount: legacy.token_b_wanted_amount,
+            bump: legacy.bump,
+            vesting: None,
+            nft: None,
+            auction: None,
+            reserved: [0; OFFER_RESERVED_SPACE],
+        }
+    }
+}
+
+/// Lockup applied to token A when the offer is taken. Both values are
+/// relative to the moment `take_offer_vested` runs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct VestingSchedule {
+    pub cliff_seconds: i64,
+    pub duration_seconds: i64,
+}
+
+impl VestingSchedule {
+    pub fn is_valid(&self) -> bool {
+        self.duration_seconds > 0
+            && self.cliff_seconds >= 0
+            && self.cliff_seconds <= self.duration_seconds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy_offer() -> OfferV0 {
+        OfferV0 {
+            id: 7,
+            maker: Pubkey::new_unique(),
+            token_mint_a: Pubkey::new_unique(),
+            token_mint_b: Pubkey::new_unique(),
+            token_b_wanted_amount: 1_000,
+            bump: 254,
+        }
+    }
+
+    fn legacy_account_data(legacy: &OfferV0) -> Vec<u8> {
+        let mut data = Offer::DISCRIMINATOR.to_vec();
+        legacy.serialize(&mut data).unwrap();
+        data.resize(Offer::V0_SPACE, 0);
+        data
+    }
+
+    #[test]
+    fn decodes_a_hand_built_original_account() {
+        let maker = Pubkey::new_unique();
+        let mut data = Offer::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.extend_from_slice(maker.as_ref());
+        data.extend_from_slice(&[1; 32]);
+        data.extend_from_slice(&[2; 32]);
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.push(254);
+        assert_eq!(data.len(), Offer::V0_SPACE);
+        assert_eq!(Offer::V0_SPACE, 8 + 8 + 32 * 3 + 8 + 1);
+
+        assert_eq!(Offer::layout_of(&data).unwrap(), OfferLayout::V0);
+        let offer = Offer::try_from_any_layout(&data).unwrap();
+        assert_eq!(offer.version, OFFER_VERSION);
+        assert_eq!(offer.id, 7);
+        assert_eq!(offer.maker, maker);
+        assert_eq!(offer.token_mint_a, Pubkey::new_from_array([1; 32]));
+        assert_eq!(offer.token_mint_b, Pubkey::new_from_array([2; 32]));
+        assert_eq!(offer.token_b_wanted_amount, 1_000);
+        assert_eq!(offer.bump, 254);
+        assert_eq!(offer.vesting, None);
+    }
+
+    #[test]
+    fn decodes_legacy_layout() {
+        let legacy = legacy_offer();
+        let data = legacy_account_data(&legacy);
+
+        assert_eq!(Offer::layout_of(&data).unwrap(), OfferLayout::V0);
+        let offer = Offer::try_from_any_layout(&data).unwrap();
+        assert_eq!(offer.version, OFFER_VERSION);
+        assert_eq!(offer.id, legacy.id);
+        assert_eq!(offer.maker, legacy.maker);
+        assert_eq!(offer.token_b_wanted_amount, legacy.token_b_wanted_amount);
+        assert_eq!(offer.bump, legacy.bump);
+    }
+
+    #[test]
+    fn decodes_current_layout() {
+        let offer: Offer = legacy_offer().into();
+        let mut data = Vec::new();
+        offer.try_serialize(&mut data).unwrap();
+        data.resize(Offer::SPACE, 0);
+
+        assert_eq!(Offer::layout_of(&data).unwrap(), OfferLayout::V1);
+        let decoded = Offer::try_from_any_layout(&data).unwrap();
+        assert_eq!(decoded.maker, offer.maker);
+        assert_eq!(decoded.reserved, [0; OFFER_RESERVED_SPACE]);
+    }
+
+    #[test]
+    fn new_fields_keep_the_offer_size() {
+        assert_eq!(Offer::SPACE, 8 + 1 + 8 + 32 * 3 + 8 + 1 + 17 + 128);
+
+        // A V1 offer saved before `nft` and `auction` existed, with vesting
+        // so the reserved bytes start late
+        let mut offer: Offer = legacy_offer().into();
+        offer.vesting = Some(VestingSchedule {
+            cliff_seconds: 0,
+            duration_seconds: 10,
+        });
+        let mut data = Vec::new();
+        offer.try_serialize(&mut data).unwrap();
+        data.truncate(data.len() - 2 - OFFER_RESERVED_SPACE);
+        data.resize(Offer::SPACE, 0);
+        let decoded = Offer::try_from_any_layout(&data).unwrap();
+        assert_eq!(decoded.nft, None);
+        assert_eq!(decoded.auction, None);
+    }
+
+    #[test]
+    fn rejects_foreign_accounts() {
+        let mut data = legacy_account_data(&legacy_offer());
+        data[0] ^= 0xff;
+        assert!(Offer::layout_of(&data).is_err());
+
+        let data = vec![0; Offer::SPACE + 1];
+        assert!(Offer::try_from_any_layout(&data).is_err());
+    }
+}
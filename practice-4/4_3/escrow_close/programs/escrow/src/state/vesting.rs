@@ -0,0 +1,88 @@
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)]
+pub struct Vesting {
+    pub beneficiary: Pubkey,
+    pub token_mint: Pubkey,
+    pub offer: Pubkey,
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    pub start_ts: i64,
+    pub cliff_ts: i64,
+    pub end_ts: i64,
+    pub bump: u8,
+}
+
+impl Vesting {
+    /// Amount unlocked at `now`: nothing before the cliff, then linear from
+    /// `start_ts` to `end_ts`.
+    pub fn unlocked_amount(&self, now: i64) -> u64 {
+        if now < self.cliff_ts {
+            return 0;
+        }
+        if now >= self.end_ts {
+            return self.total_amount;
+        }
+        let elapsed = (now - self.start_ts) as u128;
+        let duration = (self.end_ts - self.start_ts) as u128;
+        (self.total_amount as u128 * elapsed / duration) as u64
+    }
+
+    pub fn claimable_amount(&self, now: i64) -> u64 {
+        self.unlocked_amount(now)
+            .saturating_sub(self.claimed_amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vesting(claimed_amount: u64) -> Vesting {
+        Vesting {
+            beneficiary: Pubkey::new_unique(),
+            token_mint: Pubkey::new_unique(),
+            offer: Pubkey::new_unique(),
+            total_amount: 1_000,
+            claimed_amount,
+            start_ts: 1_000,
+            cliff_ts: 1_100,
+            end_ts: 1_400,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn nothing_unlocks_before_the_cliff() {
+        let vesting = vesting(0);
+        assert_eq!(vesting.unlocked_amount(i64::MIN), 0);
+        assert_eq!(vesting.unlocked_amount(1_000), 0);
+        assert_eq!(vesting.unlocked_amount(1_099), 0);
+    }
+
+    #[test]
+    fn unlocks_linearly_from_the_start() {
+        let vesting = vesting(0);
+        // The cliff releases what accrued since the start all at once
+        assert_eq!(vesting.unlocked_amount(1_100), 250);
+        assert_eq!(vesting.unlocked_amount(1_200), 500);
+        assert_eq!(vesting.unlocked_amount(1_399), 997);
+        assert_eq!(vesting.unlocked_amount(1_400), 1_000);
+        assert_eq!(vesting.unlocked_amount(i64::MAX), 1_000);
+
+        let whale = Vesting {
+            total_amount: u64::MAX,
+            ..vesting
+        };
+        assert_eq!(whale.unlocked_amount(1_200), u64::MAX / 2);
+    }
+
+    #[test]
+    fn claims_only_what_was_not_claimed_yet() {
+        assert_eq!(vesting(0).claimable_amount(1_200), 500);
+        assert_eq!(vesting(300).claimable_amount(1_200), 200);
+        assert_eq!(vesting(500).claimable_amount(1_200), 0);
+        assert_eq!(vesting(500).claimable_amount(1_400), 500);
+    }
+}
@@ -0,0 +1,195 @@
+//! Collection bids: the maker locks token A for any NFT of a collection and
+//! `take_collection_bid` checks the taker's NFT against its metadata.
+//!
+//! Metadata accounts are fixtures written straight into the bank, the checks
+//! themselves run in the escrow program.
+
+mod common;
+
+use anchor_spl::metadata::mpl_token_metadata::types::Collection;
+use common::{custom_error, nft_metadata, OfferSetup, TOKEN_PROGRAMS};
+use escrow::{error::ErrorCode, NftSide, NftTerms};
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+
+const BID: u64 = 1_000;
+
+fn member(collection: Pubkey, verified: bool) -> Option<Collection> {
+    Some(Collection {
+        verified,
+        key: collection,
+    })
+}
+
+/// The maker's bid of `BID` token A for any NFT of collection token B, made
+/// as offer 1.
+async fn start(token_program: Pubkey) -> OfferSetup {
+    let mut setup = OfferSetup::start_collection_bid(token_program, BID).await;
+    let terms = NftTerms {
+        side: NftSide::AnyInCollection,
+        collection: Some(setup.mint_b),
+    };
+    let instruction = setup.harness.make_nft_offer_ix(
+        &setup.maker.pubkey(),
+        1,
+        (&setup.mint_a, BID),
+        (&setup.mint_b, 1),
+        terms,
+    );
+    setup.harness.execute(&[instruction], &[&setup.maker]).await;
+    setup
+}
+
+#[tokio::test]
+async fn bids_name_token_b_as_their_collection() {
+    let OfferSetup {
+        mut harness,
+        maker,
+        mint_a: usdc,
+        mint_b: collection,
+        ..
+    } = start(TOKEN_PROGRAMS[0].1).await;
+    harness.mint_to(&usdc, &maker.pubkey(), BID).await;
+
+    // Naming another collection than token B, or wanting more than one NFT
+    for (id, named, wanted) in [(2, Pubkey::new_unique(), 1), (3, collection, 2)] {
+        let terms = NftTerms {
+            side: NftSide::AnyInCollection,
+            collection: Some(named),
+        };
+        let instruction = harness.make_nft_offer_ix(
+            &maker.pubkey(),
+            id,
+            (&usdc, BID),
+            (&collection, wanted),
+            terms,
+        );
+        assert_eq!(
+            custom_error(harness.process(&[instruction], &[&maker]).await),
+            Some(u32::from(ErrorCode::InvalidCollectionBid))
+        );
+    }
+}
+
+#[tokio::test]
+async fn bids_need_a_collection_nft() {
+    let OfferSetup {
+        mut harness,
+        maker,
+        mint_a: usdc,
+        ..
+    } = start(TOKEN_PROGRAMS[0].1).await;
+    harness.mint_to(&usdc, &maker.pubkey(), 2 * BID).await;
+
+    // A mint without metadata, then an ordinary NFT
+    let bare = harness.mint_nft(&maker.pubkey()).await;
+    let plain = harness.mint_nft(&maker.pubkey()).await;
+    harness.set_metadata(&nft_metadata(plain, &[], None));
+    for (id, named) in [(2, bare), (3, plain)] {
+        let terms = NftTerms {
+            side: NftSide::AnyInCollection,
+            collection: Some(named),
+        };
+        let instruction =
+            harness.make_nft_offer_ix(&maker.pubkey(), id, (&usdc, BID), (&named, 1), terms);
+        assert_eq!(
+            custom_error(harness.process(&[instruction], &[&maker]).await),
+            Some(u32::from(ErrorCode::NotACollection))
+        );
+    }
+}
+
+#[tokio::test]
+async fn only_verified_members_fill_a_bid() {
+    let OfferSetup {
+        mut harness,
+        maker,
+        taker,
+        mint_a: usdc,
+        mint_b: collection,
+    } = start(TOKEN_PROGRAMS[0].1).await;
+
+    let unverified = harness.mint_nft(&taker.pubkey()).await;
+    harness.set_metadata(&nft_metadata(unverified, &[], member(collection, false)));
+    let elsewhere = harness.mint_nft(&taker.pubkey()).await;
+    harness.set_metadata(&nft_metadata(
+        elsewhere,
+        &[],
+        member(Pubkey::new_unique(), true),
+    ));
+    let bare = harness.mint_nft(&taker.pubkey()).await;
+
+    for nft in [unverified, elsewhere, bare] {
+        let instruction =
+            harness.take_collection_bid_ix(&taker.pubkey(), &maker.pubkey(), 1, &usdc, &nft, &[]);
+        assert_eq!(
+            custom_error(harness.process(&[instruction], &[&taker]).await),
+            Some(u32::from(ErrorCode::NotInCollection))
+        );
+    }
+
+    // Nor can the bid be filled with the collection NFT itself through take_offer
+    harness.mint_to(&collection, &taker.pubkey(), 0).await;
+    let instruction = harness.take_nft_offer_ix(
+        &taker.pubkey(),
+        &maker.pubkey(),
+        1,
+        &usdc,
+        &collection,
+        &collection,
+        &[],
+    );
+    assert_eq!(
+        custom_error(harness.process(&[instruction], &[&taker]).await),
+        Some(u32::from(ErrorCode::OfferIsCollectionBid))
+    );
+    assert_eq!(
+        harness
+            .token_balance(&harness.ata(&usdc, &maker.pubkey()))
+            .await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn any_member_takes_the_bid_and_pays_royalties() {
+    for (name, token_program) in TOKEN_PROGRAMS {
+        let OfferSetup {
+            mut harness,
+            maker,
+            taker,
+            mint_a: usdc,
+            mint_b: collection,
+        } = start(token_program).await;
+        let creator = Pubkey::new_unique();
+        let royalty_account = harness.mint_to(&usdc, &creator, 0).await;
+        let nft = harness.mint_nft(&taker.pubkey()).await;
+        harness.set_metadata(&nft_metadata(
+            nft,
+            &[(creator, 100)],
+            member(collection, true),
+        ));
+
+        let instruction = harness.take_collection_bid_ix(
+            &taker.pubkey(),
+            &maker.pubkey(),
+            1,
+            &usdc,
+            &nft,
+            &[royalty_account],
+        );
+        harness.execute(&[instruction], &[&taker]).await;
+
+        let maker_nft = harness.ata(&nft, &maker.pubkey());
+        assert_eq!(harness.token_balance(&maker_nft).await, 1, "{name}");
+        let taker_usdc = harness.ata(&usdc, &taker.pubkey());
+        assert_eq!(harness.token_balance(&taker_usdc).await, 950, "{name}");
+        assert_eq!(harness.token_balance(&royalty_account).await, 50, "{name}");
+        assert!(
+            harness
+                .account(&common::offer_address(&maker.pubkey(), 1))
+                .await
+                .is_none(),
+            "{name}"
+        );
+    }
+}
@@ -0,0 +1,725 @@
+//! In-process harness shared by the Rust integration tests of the escrow.
+#![allow(dead_code)]
+
+use anchor_lang::{
+    prelude::AccountInfo, solana_program::entrypoint::ProgramResult, InstructionData,
+    ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id,
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    metadata::mpl_token_metadata::{
+        accounts::Metadata,
+        types::{Collection, CollectionDetails, Creator, Key},
+    },
+    token::spl_token,
+    token_2022::spl_token_2022,
+};
+use solana_program_test::{
+    processor, BanksClientError, BanksTransactionResultWithMetadata, ProgramTest,
+    ProgramTestContext,
+};
+use solana_sdk::{
+    account::Account,
+    bpf_loader,
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_system_interface::instruction as system_instruction;
+
+use escrow::{DutchAuction, NftTerms, VestingSchedule};
+
+pub const LAMPORTS_PER_USER: u64 = 10_000_000_000;
+
+/// Token programs the escrow is exercised against.
+pub const TOKEN_PROGRAMS: [(&str, Pubkey); 2] = [
+    ("spl-token", spl_token::ID),
+    ("token-2022", spl_token_2022::ID),
+];
+
+/// Path of the SBF build produced by `anchor build`.
+pub fn sbf_program_path() -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../target/deploy/escrow.so")
+}
+
+pub struct Harness {
+    pub context: ProgramTestContext,
+    pub token_program: Pubkey,
+    pub mint_authority: Keypair,
+}
+
+impl Harness {
+    /// Starts a bank that runs the compiled escrow ELF, so compute units are
+    /// metered exactly like on a validator.
+    pub async fn start_sbf(elf: Vec<u8>, token_program: Pubkey) -> Self {
+        let mut program_test = ProgramTest::default();
+        program_test.add_account(
+            escrow::ID,
+            Account {
+                lamports: Rent::default().minimum_balance(elf.len()),
+                data: elf,
+                owner: bpf_loader::id(),
+                executable: true,
+                rent_epoch: 0,
+            },
+        );
+        Self::start(program_test, token_program).await
+    }
+
+    /// Starts a bank that runs the escrow natively, which is much faster but
+    /// does not meter compute units.
+    pub async fn start_native(token_program: Pubkey) -> Self {
+        let program_test = ProgramTest::new("escrow", escrow::ID, processor!(process_instruction));
+        Self::start(program_test, token_program).await
+    }
+
+    async fn start(program_test: ProgramTest, token_program: Pubkey) -> Self {
+        Harness {
+            context: program_test.start_with_context().await,
+            token_program,
+            mint_authority: Keypair::new(),
+        }
+    }
+
+    pub fn payer(&self) -> Keypair {
+        self.context.payer.insecure_clone()
+    }
+
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<BanksTransactionResultWithMetadata, BanksClientError> {
+        let payer = self.payer();
+        let mut all_signers = vec![&payer];
+        all_signers.extend_from_slice(signers);
+        let blockhash = self.context.get_new_latest_blockhash().await?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+    }
+
+    /// Like `process`, but panics unless the transaction succeeded.
+    pub async fn execute(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> u64 {
+        let result = self.process(instructions, signers).await.unwrap();
+        result.result.unwrap();
+        result
+            .metadata
+            .map_or(0, |metadata| metadata.compute_units_consumed)
+    }
+
+    pub async fn create_user(&mut self) -> Keypair {
+        let user = Keypair::new();
+        let payer = self.payer();
+        self.execute(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &user.pubkey(),
+                LAMPORTS_PER_USER,
+            )],
+            &[],
+        )
+        .await;
+        user
+    }
+
+    /// The two sides of an offer, both funded with SOL.
+    pub async fn create_maker_and_taker(&mut self) -> (Keypair, Keypair) {
+        let maker = self.create_user().await;
+        let taker = self.create_user().await;
+        (maker, taker)
+    }
+
+    pub async fn create_mint(&mut self, decimals: u8) -> Pubkey {
+        let mint = Keypair::new();
+        let payer = self.payer();
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = spl_token::state::Mint::LEN;
+        let initialize = if self.token_program == spl_token::ID {
+            spl_token::instruction::initialize_mint2(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &self.mint_authority.pubkey(),
+                None,
+                decimals,
+            )
+        } else {
+            spl_token_2022::instruction::initialize_mint2(
+                &spl_token_2022::ID,
+                &mint.pubkey(),
+                &self.mint_authority.pubkey(),
+                None,
+                decimals,
+            )
+        }
+        .unwrap();
+        self.execute(
+            &[
+                system_instruction::create_account(
+                    &payer.pubkey(),
+                    &mint.pubkey(),
+                    rent.minimum_balance(space),
+                    space as u64,
+                    &self.token_program,
+                ),
+                initialize,
+            ],
+            &[&mint],
+        )
+        .await;
+        mint.pubkey()
+    }
+
+    pub fn ata(&self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, mint, &self.token_program)
+    }
+
+    /// Creates `owner`'s ATA for `mint` if needed and mints `amount` into it.
+    pub async fn mint_to(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        let payer = self.payer();
+        let ata = self.ata(mint, owner);
+        let mint_to = if self.token_program == spl_token::ID {
+            spl_token::instruction::mint_to(
+                &spl_token::ID,
+                mint,
+                &ata,
+                &self.mint_authority.pubkey(),
+                &[],
+                amount,
+            )
+        } else {
+            spl_token_2022::instruction::mint_to(
+                &spl_token_2022::ID,
+                mint,
+                &ata,
+                &self.mint_authority.pubkey(),
+                &[],
+                amount,
+            )
+        }
+        .unwrap();
+        let mint_authority = self.mint_authority.insecure_clone();
+        self.execute(
+            &[
+                create_associated_token_account_idempotent(
+                    &payer.pubkey(),
+                    owner,
+                    mint,
+                    &self.token_program,
+                ),
+                mint_to,
+            ],
+            &[&mint_authority],
+        )
+        .await;
+        ata
+    }
+
+    /// Mints the single token of a fresh 0-decimal mint to `owner`.
+    pub async fn mint_nft(&mut self, owner: &Pubkey) -> Pubkey {
+        let nft = self.create_mint(0).await;
+        self.mint_to(&nft, owner, 1).await;
+        nft
+    }
+
+    pub async fn now(&mut self) -> i64 {
+        let clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp
+    }
+
+    /// Moves the bank's clock to `unix_timestamp`, slots stay where they are.
+    pub async fn warp_to(&mut self, unix_timestamp: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    pub async fn account(&mut self, address: &Pubkey) -> Option<Account> {
+        self.context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+    }
+
+    pub async fn token_balance(&mut self, address: &Pubkey) -> u64 {
+        match self.account(address).await {
+            // The amount sits at the same offset for both token programs.
+            Some(account) => u64::from_le_bytes(account.data[64..72].try_into().unwrap()),
+            None => 0,
+        }
+    }
+
+    pub async fn mint_supply(&mut self, mint: &Pubkey) -> u64 {
+        let account = self.account(mint).await.unwrap();
+        u64::from_le_bytes(account.data[36..44].try_into().unwrap())
+    }
+
+    /// Writes `metadata` to the Metaplex metadata PDA of its mint, as if the
+    /// token metadata program had created it.
+    pub fn set_metadata(&mut self, metadata: &Metadata) {
+        let data = borsh::to_vec(metadata).unwrap();
+        self.context.set_account(
+            &metadata_address(&metadata.mint),
+            &Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: anchor_spl::metadata::ID,
+                executable: false,
+                rent_epoch: 0,
+            }
+            .into(),
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn make_offer_ix(
+        &self,
+        maker: &Pubkey,
+        id: u64,
+        token_mint_a: &Pubkey,
+        token_a_offered_amount: u64,
+        token_mint_b: &Pubkey,
+        token_b_wanted_amount: u64,
+        vesting: Option<VestingSchedule>,
+    ) -> Instruction {
+        self.offer_ix(
+            maker,
+            id,
+            (token_mint_a, token_a_offered_amount),
+            (token_mint_b, token_b_wanted_amount),
+            vesting,
+            None,
+            None,
+        )
+    }
+
+    /// Offers one NFT for `price` of the other token, or the other way round
+    /// depending on `nft.side`.
+    pub fn make_nft_offer_ix(
+        &self,
+        maker: &Pubkey,
+        id: u64,
+        offered: (&Pubkey, u64),
+        wanted: (&Pubkey, u64),
+        nft: NftTerms,
+    ) -> Instruction {
+        self.offer_ix(maker, id, offered, wanted, None, Some(nft), None)
+    }
+
+    /// Offers token A for token B at a price falling from `start_price` as
+    /// laid out by `auction`.
+    pub fn make_auction_offer_ix(
+        &self,
+        maker: &Pubkey,
+        id: u64,
+        offered: (&Pubkey, u64),
+        (token_mint_b, start_price): (&Pubkey, u64),
+        auction: DutchAuction,
+    ) -> Instruction {
+        self.offer_ix(
+            maker,
+            id,
+            offered,
+            (token_mint_b, start_price),
+            None,
+            None,
+            Some(auction),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn offer_ix(
+        &self,
+        maker: &Pubkey,
+        id: u64,
+        (token_mint_a, token_a_offered_amount): (&Pubkey, u64),
+        (token_mint_b, token_b_wanted_amount): (&Pubkey, u64),
+        vesting: Option<VestingSchedule>,
+        nft: Option<NftTerms>,
+        auction: Option<DutchAuction>,
+    ) -> Instruction {
+        let offer = offer_address(maker, id);
+        // A collection bid names the collection as token B
+        let nft_metadata = nft.map(|nft| match nft.side {
+            escrow::NftSide::TokenA => metadata_address(token_mint_a),
+            escrow::NftSide::TokenB | escrow::NftSide::AnyInCollection => {
+                metadata_address(token_mint_b)
+            }
+        });
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::MakeOffer {
+                maker: *maker,
+                token_mint_a: *token_mint_a,
+                token_mint_b: *token_mint_b,
+                maker_token_account_a: self.ata(token_mint_a, maker),
+                offer,
+                vault: self.ata(token_mint_a, &offer),
+                associated_token_program: anchor_spl::associated_token::ID,
+                token_program: self.token_program,
+                system_program: solana_sdk::system_program::ID,
+                nft_metadata,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::MakeOffer {
+                id,
+                token_a_offered_amount,
+                token_b_wanted_amount,
+                vesting,
+                nft,
+                auction,
+            }
+            .data(),
+        }
+    }
+
+    pub fn take_offer_ix(
+        &self,
+        taker: &Pubkey,
+        maker: &Pubkey,
+        id: u64,
+        token_mint_a: &Pubkey,
+        token_mint_b: &Pubkey,
+    ) -> Instruction {
+        self.settle_ix(taker, maker, id, token_mint_a, token_mint_b, None, &[])
+    }
+
+    /// Takes an NFT offer, paying royalties into `royalty_accounts`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn take_nft_offer_ix(
+        &self,
+        taker: &Pubkey,
+        maker: &Pubkey,
+        id: u64,
+        token_mint_a: &Pubkey,
+        token_mint_b: &Pubkey,
+        nft_mint: &Pubkey,
+        royalty_accounts: &[Pubkey],
+    ) -> Instruction {
+        self.settle_ix(
+            taker,
+            maker,
+            id,
+            token_mint_a,
+            token_mint_b,
+            Some(metadata_address(nft_mint)),
+            royalty_accounts,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn settle_ix(
+        &self,
+        taker: &Pubkey,
+        maker: &Pubkey,
+        id: u64,
+        token_mint_a: &Pubkey,
+        token_mint_b: &Pubkey,
+        nft_metadata: Option<Pubkey>,
+        royalty_accounts: &[Pubkey],
+    ) -> Instruction {
+        let offer = offer_address(maker, id);
+        let mut accounts = escrow::accounts::TakeOffer {
+            taker: *taker,
+            maker: *maker,
+            token_mint_a: *token_mint_a,
+            token_mint_b: *token_mint_b,
+            taker_token_account_a: self.ata(token_mint_a, taker),
+            taker_token_account_b: self.ata(token_mint_b, taker),
+            maker_token_account_b: self.ata(token_mint_b, maker),
+            offer,
+            vault: self.ata(token_mint_a, &offer),
+            associated_token_program: anchor_spl::associated_token::ID,
+            token_program: self.token_program,
+            system_program: solana_sdk::system_program::ID,
+            nft_metadata,
+        }
+        .to_account_metas(None);
+        accounts.extend(
+            royalty_accounts
+                .iter()
+                .map(|account| AccountMeta::new(*account, false)),
+        );
+        Instruction {
+            program_id: escrow::ID,
+            accounts,
+            data: escrow::instruction::TakeOffer {}.data(),
+        }
+    }
+
+    /// Sells `nft_mint` into a collection bid, paying royalties into
+    /// `royalty_accounts`.
+    pub fn take_collection_bid_ix(
+        &self,
+        taker: &Pubkey,
+        maker: &Pubkey,
+        id: u64,
+        token_mint_a: &Pubkey,
+        nft_mint: &Pubkey,
+        royalty_accounts: &[Pubkey],
+    ) -> Instruction {
+        let offer = offer_address(maker, id);
+        let mut accounts = escrow::accounts::TakeCollectionBid {
+            taker: *taker,
+            maker: *maker,
+            token_mint_a: *token_mint_a,
+            nft_mint: *nft_mint,
+            taker_token_account_a: self.ata(token_mint_a, taker),
+            taker_nft_account: self.ata(nft_mint, taker),
+            maker_nft_account: self.ata(nft_mint, maker),
+            offer,
+            vault: self.ata(token_mint_a, &offer),
+            nft_metadata: metadata_address(nft_mint),
+            associated_token_program: anchor_spl::associated_token::ID,
+            token_program: self.token_program,
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None);
+        accounts.extend(
+            royalty_accounts
+                .iter()
+                .map(|account| AccountMeta::new(*account, false)),
+        );
+        Instruction {
+            program_id: escrow::ID,
+            accounts,
+            data: escrow::instruction::TakeCollectionBid {}.data(),
+        }
+    }
+
+    pub fn take_offer_vested_ix(
+        &self,
+        taker: &Pubkey,
+        maker: &Pubkey,
+        id: u64,
+        token_mint_a: &Pubkey,
+        token_mint_b: &Pubkey,
+    ) -> Instruction {
+        let offer = offer_address(maker, id);
+        let vesting = vesting_address(&offer, taker);
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::TakeOfferVested {
+                taker: *taker,
+                maker: *maker,
+                token_mint_a: *token_mint_a,
+                token_mint_b: *token_mint_b,
+                taker_token_account_b: self.ata(token_mint_b, taker),
+                maker_token_account_b: self.ata(token_mint_b, maker),
+                offer,
+                vault: self.ata(token_mint_a, &offer),
+                vesting,
+                vesting_vault: self.ata(token_mint_a, &vesting),
+                associated_token_program: anchor_spl::associated_token::ID,
+                token_program: self.token_program,
+                system_program: solana_sdk::system_program::ID,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::TakeOfferVested {}.data(),
+        }
+    }
+
+    pub fn claim_vested_ix(
+        &self,
+        beneficiary: &Pubkey,
+        vesting: &Pubkey,
+        token_mint: &Pubkey,
+    ) -> Instruction {
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::ClaimVested {
+                beneficiary: *beneficiary,
+                vesting: *vesting,
+                token_mint: *token_mint,
+                vesting_vault: self.ata(token_mint, vesting),
+                beneficiary_token_account: self.ata(token_mint, beneficiary),
+                associated_token_program: anchor_spl::associated_token::ID,
+                token_program: self.token_program,
+                system_program: solana_sdk::system_program::ID,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::ClaimVested {}.data(),
+        }
+    }
+
+    pub fn close_offer_ix(&self, maker: &Pubkey, id: u64, token_mint_a: &Pubkey) -> Instruction {
+        let offer = offer_address(maker, id);
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::CloseOffer {
+                maker: *maker,
+                offer,
+                token_mint_a: *token_mint_a,
+                vault: self.ata(token_mint_a, &offer),
+                maker_ata_a: self.ata(token_mint_a, maker),
+                token_program: self.token_program,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::CloseOffer { id }.data(),
+        }
+    }
+
+    pub fn migrate_offer_ix(&self, maker: &Pubkey, offer: &Pubkey, id: u64) -> Instruction {
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::MigrateOffer {
+                maker: *maker,
+                offer: *offer,
+                system_program: anchor_lang::system_program::ID,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::MigrateOffer { id }.data(),
+        }
+    }
+}
+
+/// A fresh bank with the two sides of an offer and the mints it trades.
+/// Nothing is offered yet.
+pub struct OfferSetup {
+    pub harness: Harness,
+    pub maker: Keypair,
+    pub taker: Keypair,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+}
+
+impl OfferSetup {
+    /// Fungible token A and B, the maker holds `offered` token A and the
+    /// taker `wanted` token B.
+    pub async fn start(token_program: Pubkey, offered: u64, wanted: u64) -> Self {
+        let mut harness = Harness::start_native(token_program).await;
+        let (maker, taker) = harness.create_maker_and_taker().await;
+        let mint_a = harness.create_mint(6).await;
+        let mint_b = harness.create_mint(6).await;
+        harness.mint_to(&mint_a, &maker.pubkey(), offered).await;
+        harness.mint_to(&mint_b, &taker.pubkey(), wanted).await;
+        OfferSetup {
+            harness,
+            maker,
+            taker,
+            mint_a,
+            mint_b,
+        }
+    }
+
+    /// Fungible token A, of which the maker holds `offered`, bidding for
+    /// token B, a collection NFT with metadata.
+    pub async fn start_collection_bid(token_program: Pubkey, offered: u64) -> Self {
+        let mut harness = Harness::start_native(token_program).await;
+        let (maker, taker) = harness.create_maker_and_taker().await;
+        let mint_a = harness.create_mint(6).await;
+        harness.mint_to(&mint_a, &maker.pubkey(), offered).await;
+        let mint_b = harness.mint_nft(&maker.pubkey()).await;
+        harness.set_metadata(&collection_metadata(mint_b, 0));
+        OfferSetup {
+            harness,
+            maker,
+            taker,
+            mint_a,
+            mint_b,
+        }
+    }
+
+    pub fn offer(&self, id: u64) -> Pubkey {
+        offer_address(&self.maker.pubkey(), id)
+    }
+}
+
+/// The custom error code a processed transaction failed with, if any.
+pub fn custom_error(
+    result: Result<BanksTransactionResultWithMetadata, BanksClientError>,
+) -> Option<u32> {
+    match result.unwrap().result {
+        Err(TransactionError::InstructionError(_, InstructionError::Custom(code))) => Some(code),
+        _ => None,
+    }
+}
+
+// `escrow::entry` wants the account slice to live as long as the accounts
+// themselves, which the native processor signature cannot express.
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    escrow::entry(program_id, accounts, data)
+}
+
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Metadata::find_pda(mint).0
+}
+
+/// Metadata of an NFT taking a 5% royalty, split between `creators` by share.
+pub fn nft_metadata(
+    mint: Pubkey,
+    creators: &[(Pubkey, u8)],
+    collection: Option<Collection>,
+) -> Metadata {
+    Metadata {
+        key: Key::MetadataV1,
+        update_authority: Pubkey::new_unique(),
+        mint,
+        name: "Escrowed".to_string(),
+        symbol: "ESC".to_string(),
+        uri: String::new(),
+        seller_fee_basis_points: 500,
+        creators: Some(
+            creators
+                .iter()
+                .map(|&(address, share)| Creator {
+                    address,
+                    verified: true,
+                    share,
+                })
+                .collect(),
+        ),
+        primary_sale_happened: true,
+        is_mutable: true,
+        edition_nonce: None,
+        token_standard: None,
+        collection,
+        uses: None,
+        collection_details: None,
+        programmable_config: None,
+    }
+}
+
+/// Metadata of the collection NFT `size` members were verified against.
+pub fn collection_metadata(mint: Pubkey, size: u64) -> Metadata {
+    Metadata {
+        collection_details: Some(CollectionDetails::V1 { size }),
+        ..nft_metadata(mint, &[], None)
+    }
+}
+
+pub fn offer_address(maker: &Pubkey, id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"offer", maker.as_ref(), id.to_le_bytes().as_ref()],
+        &escrow::ID,
+    )
+    .0
+}
+
+pub fn vesting_address(offer: &Pubkey, beneficiary: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"vesting", offer.as_ref(), beneficiary.as_ref()],
+        &escrow::ID,
+    )
+    .0
+}
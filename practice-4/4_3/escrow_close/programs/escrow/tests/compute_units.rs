@@ -0,0 +1,201 @@
+//! Compute-unit benchmark for the escrow instructions.
+//!
+//! Runs `make_offer`, `take_offer` and `close_offer` against the SBF build in
+//! `target/deploy/escrow.so` for both token programs and compares the result
+//! with `compute_units.md`. Natively executed programs are not metered, so the
+//! benchmark is ignored by default and fails outright without the ELF or a
+//! baseline row for every measurement. Build the ELF first:
+//!
+//! ```text
+//! cargo build-sbf --manifest-path programs/escrow/Cargo.toml
+//! cargo test -p escrow --test compute_units -- --ignored
+//! ```
+//!
+//! * `UPDATE_COMPUTE_UNITS=1` rewrites the report with the measured values.
+//!   Without a local Solana toolchain, run the "Escrow compute units" workflow
+//!   with `update` checked and commit the `compute_units.md` it uploads.
+//! * `COMPUTE_UNITS_TOLERANCE` sets the allowed regression in percent (default 5).
+
+mod common;
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use common::{offer_address, Harness, TOKEN_PROGRAMS};
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+
+const REPORT_HEADER: &str = "# Escrow compute units
+
+Generated by `cargo test -p escrow --test compute_units -- --ignored` against `target/deploy/escrow.so`.
+Rewrite it with `UPDATE_COMPUTE_UNITS=1` after an intentional change.
+
+| instruction | token program | compute units | bytes allocated |
+|-------------|---------------|---------------|-----------------|
+";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Measurement {
+    compute_units: u64,
+    bytes_allocated: usize,
+}
+
+type Report = BTreeMap<(String, String), Measurement>;
+
+fn report_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("compute_units.md")
+}
+
+fn parse_report(contents: &str) -> Report {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let cells: Vec<&str> = line
+                .trim()
+                .trim_matches('|')
+                .split('|')
+                .map(str::trim)
+                .collect();
+            match cells.as_slice() {
+                [instruction, token_program, compute_units, bytes_allocated] => Some((
+                    (instruction.to_string(), token_program.to_string()),
+                    Measurement {
+                        compute_units: compute_units.parse().ok()?,
+                        bytes_allocated: bytes_allocated.parse().ok()?,
+                    },
+                )),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn render_report(report: &Report) -> String {
+    let mut contents = REPORT_HEADER.to_string();
+    for ((instruction, token_program), measurement) in report {
+        contents.push_str(&format!(
+            "| {instruction} | {token_program} | {} | {} |\n",
+            measurement.compute_units, measurement.bytes_allocated
+        ));
+    }
+    contents
+}
+
+async fn data_len(harness: &mut Harness, addresses: &[Pubkey]) -> usize {
+    let mut total = 0;
+    for address in addresses {
+        total += harness
+            .account(address)
+            .await
+            .map_or(0, |account| account.data.len());
+    }
+    total
+}
+
+async fn measure(elf: &[u8], token_program_name: &str, token_program: Pubkey, report: &mut Report) {
+    let mut harness = Harness::start_sbf(elf.to_vec(), token_program).await;
+    let maker = harness.create_user().await;
+    let taker = harness.create_user().await;
+    let mint_a = harness.create_mint(6).await;
+    let mint_b = harness.create_mint(6).await;
+    harness.mint_to(&mint_a, &maker.pubkey(), 1_000_000).await;
+    harness.mint_to(&mint_b, &taker.pubkey(), 1_000_000).await;
+
+    let mut record = |instruction: &str, compute_units: u64, bytes_allocated: usize| {
+        report.insert(
+            (instruction.to_string(), token_program_name.to_string()),
+            Measurement {
+                compute_units,
+                bytes_allocated,
+            },
+        );
+    };
+
+    let make = harness.make_offer_ix(&maker.pubkey(), 1, &mint_a, 1_000, &mint_b, 2_000, None);
+    let compute_units = harness.execute(&[make], &[&maker]).await;
+    let offer = offer_address(&maker.pubkey(), 1);
+    let vault = harness.ata(&mint_a, &offer);
+    let bytes = data_len(&mut harness, &[offer, vault]).await;
+    record("make_offer", compute_units, bytes);
+
+    // Neither the taker's token A account nor the maker's token B account
+    // exist yet, so both `init_if_needed` branches are paid for.
+    let take = harness.take_offer_ix(&taker.pubkey(), &maker.pubkey(), 1, &mint_a, &mint_b);
+    let compute_units = harness.execute(&[take], &[&taker]).await;
+    let taker_ata_a = harness.ata(&mint_a, &taker.pubkey());
+    let maker_ata_b = harness.ata(&mint_b, &maker.pubkey());
+    let bytes = data_len(&mut harness, &[taker_ata_a, maker_ata_b]).await;
+    record("take_offer", compute_units, bytes);
+
+    let make = harness.make_offer_ix(&maker.pubkey(), 2, &mint_a, 1_000, &mint_b, 2_000, None);
+    harness.execute(&[make], &[&maker]).await;
+    let close = harness.close_offer_ix(&maker.pubkey(), 2, &mint_a);
+    let compute_units = harness.execute(&[close], &[&maker]).await;
+    record("close_offer", compute_units, 0);
+}
+
+#[tokio::test]
+#[ignore = "needs the SBF build of the escrow, see the module docs"]
+async fn compute_units_do_not_regress() {
+    let path = common::sbf_program_path();
+    let elf = fs::read(&path)
+        .unwrap_or_else(|error| panic!("{}: {error}, run `cargo build-sbf` first", path.display()));
+
+    let mut measured = Report::new();
+    for (name, token_program) in TOKEN_PROGRAMS {
+        measure(&elf, name, token_program, &mut measured).await;
+    }
+    print!("{}", render_report(&measured));
+
+    if std::env::var_os("UPDATE_COMPUTE_UNITS").is_some() {
+        fs::write(report_path(), render_report(&measured)).unwrap();
+        return;
+    }
+
+    let baseline = parse_report(&fs::read_to_string(report_path()).unwrap());
+    let tolerance: u64 = std::env::var("COMPUTE_UNITS_TOLERANCE")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(5);
+
+    let missing: Vec<String> = measured
+        .keys()
+        .filter(|key| !baseline.contains_key(*key))
+        .map(|(instruction, token_program)| format!("{instruction} ({token_program})"))
+        .collect();
+    assert!(
+        missing.is_empty(),
+        "no baseline for {}, rerun with UPDATE_COMPUTE_UNITS=1 and commit compute_units.md",
+        missing.join(", ")
+    );
+
+    let regressions: Vec<String> = measured
+        .iter()
+        .filter_map(|(key, measurement)| {
+            let expected = baseline.get(key)?;
+            let limit = expected.compute_units * (100 + tolerance) / 100;
+            (measurement.compute_units > limit).then(|| {
+                format!(
+                    "{} ({}): {} CU, baseline {} CU",
+                    key.0, key.1, measurement.compute_units, expected.compute_units
+                )
+            })
+        })
+        .collect();
+    assert!(
+        regressions.is_empty(),
+        "compute units regressed by more than {tolerance}%:\n{}",
+        regressions.join("\n")
+    );
+}
+
+#[test]
+fn report_round_trips() {
+    let mut report = Report::new();
+    report.insert(
+        ("take_offer".to_string(), "token-2022".to_string()),
+        Measurement {
+            compute_units: 42_000,
+            bytes_allocated: 340,
+        },
+    );
+    assert_eq!(parse_report(&render_report(&report)), report);
+}
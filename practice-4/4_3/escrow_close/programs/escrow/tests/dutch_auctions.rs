@@ -0,0 +1,171 @@
+//! Dutch-auction offers: `take_offer` charges the ask the auction has fallen
+//! to by the bank's clock, which the tests warp.
+
+mod common;
+
+use common::{custom_error, OfferSetup, TOKEN_PROGRAMS};
+use escrow::{error::ErrorCode, DutchAuction, NftSide, NftTerms};
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+
+const OFFERED: u64 = 50;
+const START_PRICE: u64 = 1_000;
+const FLOOR: u64 = 400;
+const WINDOW: i64 = 600;
+
+struct Auction {
+    offer: OfferSetup,
+    start_time: i64,
+}
+
+async fn start(token_program: Pubkey) -> Auction {
+    let mut offer = OfferSetup::start(token_program, OFFERED, START_PRICE).await;
+    let start_time = offer.harness.now().await + 60;
+    Auction { offer, start_time }
+}
+
+impl Auction {
+    fn terms(&self, step_seconds: i64) -> DutchAuction {
+        DutchAuction {
+            floor_price: FLOOR,
+            start_time: self.start_time,
+            end_time: self.start_time + WINDOW,
+            step_seconds,
+        }
+    }
+
+    async fn make(&mut self, auction: DutchAuction) {
+        let instruction = self.offer.harness.make_auction_offer_ix(
+            &self.offer.maker.pubkey(),
+            1,
+            (&self.offer.mint_a, OFFERED),
+            (&self.offer.mint_b, START_PRICE),
+            auction,
+        );
+        self.offer
+            .harness
+            .execute(&[instruction], &[&self.offer.maker])
+            .await;
+    }
+
+    /// Takes the offer at `unix_timestamp` and returns what the maker got.
+    async fn take_at(&mut self, unix_timestamp: i64) -> u64 {
+        self.offer.harness.warp_to(unix_timestamp).await;
+        let instruction = self.offer.harness.take_offer_ix(
+            &self.offer.taker.pubkey(),
+            &self.offer.maker.pubkey(),
+            1,
+            &self.offer.mint_a,
+            &self.offer.mint_b,
+        );
+        self.offer
+            .harness
+            .execute(&[instruction], &[&self.offer.taker])
+            .await;
+
+        let taker_a = self
+            .offer
+            .harness
+            .ata(&self.offer.mint_a, &self.offer.taker.pubkey());
+        assert_eq!(self.offer.harness.token_balance(&taker_a).await, OFFERED);
+        let maker_b = self
+            .offer
+            .harness
+            .ata(&self.offer.mint_b, &self.offer.maker.pubkey());
+        self.offer.harness.token_balance(&maker_b).await
+    }
+}
+
+#[tokio::test]
+async fn linear_auctions_charge_the_current_price() {
+    for (name, token_program) in TOKEN_PROGRAMS {
+        // Halfway through the window
+        let mut auction = start(token_program).await;
+        auction.make(auction.terms(0)).await;
+        let paid = auction.take_at(auction.start_time + WINDOW / 2).await;
+        assert_eq!(paid, 700, "{name}");
+
+        // Before it opens the start price holds
+        let mut auction = start(token_program).await;
+        auction.make(auction.terms(0)).await;
+        let paid = auction.take_at(auction.start_time - 30).await;
+        assert_eq!(paid, START_PRICE, "{name}");
+
+        // Long after it closes only the floor is left
+        let mut auction = start(token_program).await;
+        auction.make(auction.terms(0)).await;
+        let paid = auction.take_at(auction.start_time + 10 * WINDOW).await;
+        assert_eq!(paid, FLOOR, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn stepwise_auctions_drop_once_per_step() {
+    let mut auction = start(TOKEN_PROGRAMS[0].1).await;
+    auction.make(auction.terms(200)).await;
+    // Two steps of 200s have passed, the third is 1s away
+    let paid = auction.take_at(auction.start_time + 399).await;
+    assert_eq!(paid, 800);
+}
+
+#[tokio::test]
+async fn rejects_invalid_auctions() {
+    let mut auction = start(TOKEN_PROGRAMS[0].1).await;
+    let maker = auction.offer.maker.insecure_clone();
+    let above_start = DutchAuction {
+        floor_price: START_PRICE + 1,
+        ..auction.terms(0)
+    };
+    let oversized_step = auction.terms(WINDOW + 1);
+    for terms in [above_start, oversized_step] {
+        let instruction = auction.offer.harness.make_auction_offer_ix(
+            &maker.pubkey(),
+            1,
+            (&auction.offer.mint_a, OFFERED),
+            (&auction.offer.mint_b, START_PRICE),
+            terms,
+        );
+        assert_eq!(
+            custom_error(
+                auction
+                    .offer
+                    .harness
+                    .process(&[instruction], &[&maker])
+                    .await
+            ),
+            Some(u32::from(ErrorCode::InvalidAuction))
+        );
+    }
+
+    // The ask is in token B, which can't be an NFT
+    let nft = auction
+        .offer
+        .harness
+        .mint_nft(&auction.offer.taker.pubkey())
+        .await;
+    let nft_terms = NftTerms {
+        side: NftSide::TokenB,
+        collection: None,
+    };
+    let instruction = auction.offer.harness.offer_ix(
+        &maker.pubkey(),
+        2,
+        (&auction.offer.mint_a, OFFERED),
+        (&nft, 1),
+        None,
+        Some(nft_terms),
+        Some(DutchAuction {
+            floor_price: 0,
+            ..auction.terms(0)
+        }),
+    );
+    assert_eq!(
+        custom_error(
+            auction
+                .offer
+                .harness
+                .process(&[instruction], &[&maker])
+                .await
+        ),
+        Some(u32::from(ErrorCode::AuctionNeedsFungiblePrice))
+    );
+}
@@ -0,0 +1,368 @@
+//! Property test that drives random sequences of `make_offer`, `take_offer`
+//! and `close_offer` through an in-process bank and checks the escrow
+//! invariants after every step.
+//!
+//! Set `PROPTEST_CASES` to run more sequences than the default.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{offer_address, Harness, TOKEN_PROGRAMS};
+use proptest::{prelude::*, test_runner::TestCaseError};
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const MAKERS: usize = 2;
+const TAKERS: usize = 2;
+const MINTS: usize = 3;
+const IDS: u64 = 3;
+const INITIAL_BALANCE: u64 = 1_000;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Make {
+        maker: usize,
+        id: u64,
+        mint_a: usize,
+        mint_b: usize,
+        offered: u64,
+        wanted: u64,
+    },
+    Take {
+        taker: usize,
+        maker: usize,
+        id: u64,
+    },
+    Close {
+        maker: usize,
+        id: u64,
+    },
+}
+
+/// What the test expects an open offer to look like.
+#[derive(Debug, Clone)]
+struct OpenOffer {
+    mint_a: usize,
+    mint_b: usize,
+    offered: u64,
+    wanted: u64,
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    let amount = 0..=INITIAL_BALANCE / 2;
+    prop_oneof![
+        (
+            0..MAKERS,
+            0..IDS,
+            0..MINTS,
+            1..MINTS,
+            amount.clone(),
+            amount
+        )
+            .prop_map(|(maker, id, mint_a, shift, offered, wanted)| Op::Make {
+                maker,
+                id,
+                mint_a,
+                // Always a different mint, swapping a mint for itself is not a trade.
+                mint_b: (mint_a + shift) % MINTS,
+                offered,
+                wanted,
+            }),
+        (0..TAKERS, 0..MAKERS, 0..IDS).prop_map(|(taker, maker, id)| Op::Take { taker, maker, id }),
+        (0..MAKERS, 0..IDS).prop_map(|(maker, id)| Op::Close { maker, id }),
+    ]
+}
+
+struct World {
+    harness: Harness,
+    makers: Vec<Keypair>,
+    takers: Vec<Keypair>,
+    mints: Vec<Pubkey>,
+    supplies: Vec<u64>,
+    open_offers: BTreeMap<(usize, u64), OpenOffer>,
+}
+
+impl World {
+    async fn new(token_program: Pubkey) -> Self {
+        let mut harness = Harness::start_native(token_program).await;
+        let mut makers = Vec::new();
+        for _ in 0..MAKERS {
+            makers.push(harness.create_user().await);
+        }
+        let mut takers = Vec::new();
+        for _ in 0..TAKERS {
+            takers.push(harness.create_user().await);
+        }
+        let mut mints = Vec::new();
+        for _ in 0..MINTS {
+            mints.push(harness.create_mint(6).await);
+        }
+        for user in makers.iter().chain(takers.iter()) {
+            for mint in &mints {
+                harness.mint_to(mint, &user.pubkey(), INITIAL_BALANCE).await;
+            }
+        }
+        let mut supplies = Vec::new();
+        for mint in &mints {
+            supplies.push(harness.mint_supply(mint).await);
+        }
+
+        World {
+            harness,
+            makers,
+            takers,
+            mints,
+            supplies,
+            open_offers: BTreeMap::new(),
+        }
+    }
+
+    fn users(&self) -> impl Iterator<Item = Pubkey> + '_ {
+        self.makers
+            .iter()
+            .chain(self.takers.iter())
+            .map(|user| user.pubkey())
+    }
+
+    fn offer_and_vault(&self, maker: usize, id: u64, mint: usize) -> (Pubkey, Pubkey) {
+        let offer = offer_address(&self.makers[maker].pubkey(), id);
+        (offer, self.harness.ata(&self.mints[mint], &offer))
+    }
+
+    /// Every address whose lamports the sequence can move around.
+    fn tracked_addresses(&self) -> Vec<Pubkey> {
+        let mut addresses = vec![self.harness.context.payer.pubkey()];
+        for user in self.users() {
+            addresses.push(user);
+            for mint in &self.mints {
+                addresses.push(self.harness.ata(mint, &user));
+            }
+        }
+        for maker in &self.makers {
+            for id in 0..IDS {
+                let offer = offer_address(&maker.pubkey(), id);
+                addresses.push(offer);
+                for mint in &self.mints {
+                    addresses.push(self.harness.ata(mint, &offer));
+                }
+            }
+        }
+        addresses
+    }
+
+    async fn total_lamports(&mut self) -> u64 {
+        let mut total = 0;
+        for address in self.tracked_addresses() {
+            total += self
+                .harness
+                .account(&address)
+                .await
+                .map_or(0, |account| account.lamports);
+        }
+        total
+    }
+
+    async fn balance(&mut self, owner: &Pubkey, mint: usize) -> u64 {
+        let ata = self.harness.ata(&self.mints[mint], owner);
+        self.harness.token_balance(&ata).await
+    }
+
+    /// Builds the transaction for `op` from the current model.
+    fn instruction_for(&self, op: &Op) -> (Instruction, Keypair) {
+        match *op {
+            Op::Make {
+                maker,
+                id,
+                mint_a,
+                mint_b,
+                offered,
+                wanted,
+            } => (
+                self.harness.make_offer_ix(
+                    &self.makers[maker].pubkey(),
+                    id,
+                    &self.mints[mint_a],
+                    offered,
+                    &self.mints[mint_b],
+                    wanted,
+                    None,
+                ),
+                self.makers[maker].insecure_clone(),
+            ),
+            Op::Take { taker, maker, id } => {
+                // Unknown offers are still sent, with whatever mints, to make
+                // sure the program rejects them.
+                let (mint_a, mint_b) = self
+                    .open_offers
+                    .get(&(maker, id))
+                    .map_or((0, 1), |offer| (offer.mint_a, offer.mint_b));
+                (
+                    self.harness.take_offer_ix(
+                        &self.takers[taker].pubkey(),
+                        &self.makers[maker].pubkey(),
+                        id,
+                        &self.mints[mint_a],
+                        &self.mints[mint_b],
+                    ),
+                    self.takers[taker].insecure_clone(),
+                )
+            }
+            Op::Close { maker, id } => {
+                let mint_a = self
+                    .open_offers
+                    .get(&(maker, id))
+                    .map_or(0, |offer| offer.mint_a);
+                (
+                    self.harness.close_offer_ix(
+                        &self.makers[maker].pubkey(),
+                        id,
+                        &self.mints[mint_a],
+                    ),
+                    self.makers[maker].insecure_clone(),
+                )
+            }
+        }
+    }
+
+    /// Sends one step and returns whether the bank accepted it.
+    async fn send(&mut self, instruction: Instruction, signer: Keypair) -> bool {
+        let result = self.harness.process(&[instruction], &[&signer]).await;
+        matches!(result, Ok(ref outcome) if outcome.result.is_ok())
+    }
+
+    /// What the model says should happen to `op`; updates the model when the
+    /// step is expected to succeed.
+    async fn expect(&mut self, op: &Op) -> bool {
+        match *op {
+            Op::Make {
+                maker,
+                id,
+                mint_a,
+                mint_b,
+                offered,
+                wanted,
+            } => {
+                let maker_key = self.makers[maker].pubkey();
+                let funded = self.balance(&maker_key, mint_a).await >= offered;
+                let accepted = funded && !self.open_offers.contains_key(&(maker, id));
+                if accepted {
+                    self.open_offers.insert(
+                        (maker, id),
+                        OpenOffer {
+                            mint_a,
+                            mint_b,
+                            offered,
+                            wanted,
+                        },
+                    );
+                }
+                accepted
+            }
+            Op::Take { taker, maker, id } => {
+                let Some(offer) = self.open_offers.get(&(maker, id)).cloned() else {
+                    return false;
+                };
+                let taker_key = self.takers[taker].pubkey();
+                let accepted = self.balance(&taker_key, offer.mint_b).await >= offer.wanted;
+                if accepted {
+                    self.open_offers.remove(&(maker, id));
+                }
+                accepted
+            }
+            Op::Close { maker, id } => self.open_offers.remove(&(maker, id)).is_some(),
+        }
+    }
+
+    async fn check_invariants(&mut self) -> Result<(), TestCaseError> {
+        for mint in 0..MINTS {
+            let supply = self.harness.mint_supply(&self.mints[mint]).await;
+            prop_assert_eq!(
+                supply,
+                self.supplies[mint],
+                "supply of mint {} changed",
+                mint
+            );
+
+            let mut held = 0;
+            for user in self.users().collect::<Vec<_>>() {
+                held += self.balance(&user, mint).await;
+            }
+            for maker in 0..MAKERS {
+                for id in 0..IDS {
+                    let (_, vault) = self.offer_and_vault(maker, id, mint);
+                    held += self.harness.token_balance(&vault).await;
+                }
+            }
+            prop_assert_eq!(held, supply, "tokens of mint {} leaked", mint);
+        }
+
+        for maker in 0..MAKERS {
+            for id in 0..IDS {
+                let open = self.open_offers.get(&(maker, id)).cloned();
+                for mint in 0..MINTS {
+                    let (offer, vault) = self.offer_and_vault(maker, id, mint);
+                    let vault_account = self.harness.account(&vault).await;
+                    match &open {
+                        Some(expected) if expected.mint_a == mint => {
+                            prop_assert!(self.harness.account(&offer).await.is_some());
+                            prop_assert_eq!(
+                                self.harness.token_balance(&vault).await,
+                                expected.offered,
+                                "vault of offer ({}, {}) is off",
+                                maker,
+                                id
+                            );
+                        }
+                        Some(_) => prop_assert!(vault_account.is_none()),
+                        None => {
+                            prop_assert!(vault_account.is_none(), "closed offer kept its vault");
+                            prop_assert!(self.harness.account(&offer).await.is_none());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn run_sequence(token_program: Pubkey, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut world = World::new(token_program).await;
+    let mut lamports = world.total_lamports().await;
+
+    for op in &ops {
+        let (instruction, signer) = world.instruction_for(op);
+        let expected = world.expect(op).await;
+        let accepted = world.send(instruction, signer).await;
+        prop_assert_eq!(accepted, expected, "unexpected outcome for {:?}", op);
+
+        world.check_invariants().await?;
+        let after = world.total_lamports().await;
+        prop_assert!(after <= lamports, "{:?} created lamports", op);
+        lamports = after;
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: std::env::var("PROPTEST_CASES")
+            .ok()
+            .and_then(|cases| cases.parse().ok())
+            .unwrap_or(8),
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn escrow_invariants_hold(
+        token_program in prop::sample::select(TOKEN_PROGRAMS.map(|(_, id)| id).to_vec()),
+        ops in prop::collection::vec(op_strategy(), 1..16),
+    ) {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(run_sequence(token_program, ops))?;
+    }
+}
@@ -0,0 +1,86 @@
+//! `migrate_offer` grows an offer written with the original layout into the
+//! current one, at the maker's expense.
+
+mod common;
+
+use anchor_lang::{AccountSerialize, AnchorSerialize, Discriminator};
+use common::{custom_error, offer_address, Harness, TOKEN_PROGRAMS};
+use escrow::{error::ErrorCode, Offer, OfferLayout, OfferV0, OFFER_VERSION};
+use solana_sdk::{account::Account, pubkey::Pubkey, rent::Rent, signature::Signer};
+
+const ID: u64 = 3;
+
+/// Writes an offer of `maker` the way the escrow stored it before offers
+/// carried a version byte.
+fn store_v0_offer(harness: &mut Harness, maker: &Pubkey) -> OfferV0 {
+    let (offer, bump) = Pubkey::find_program_address(
+        &[b"offer", maker.as_ref(), ID.to_le_bytes().as_ref()],
+        &escrow::ID,
+    );
+    let legacy = OfferV0 {
+        id: ID,
+        maker: *maker,
+        token_mint_a: Pubkey::new_unique(),
+        token_mint_b: Pubkey::new_unique(),
+        token_b_wanted_amount: 1_000,
+        bump,
+    };
+    let mut data = Offer::DISCRIMINATOR.to_vec();
+    legacy.serialize(&mut data).unwrap();
+    assert_eq!(data.len(), Offer::V0_SPACE);
+    harness.context.set_account(
+        &offer,
+        &Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: escrow::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+        .into(),
+    );
+    legacy
+}
+
+#[tokio::test]
+async fn migrates_an_original_offer_once() {
+    let mut harness = Harness::start_native(TOKEN_PROGRAMS[0].1).await;
+    let (maker, stranger) = harness.create_maker_and_taker().await;
+    let legacy = store_v0_offer(&mut harness, &maker.pubkey());
+    let offer = offer_address(&maker.pubkey(), ID);
+
+    // The offer address is derived from the maker, so nobody else matches it
+    let instruction = harness.migrate_offer_ix(&stranger.pubkey(), &offer, ID);
+    assert_eq!(
+        custom_error(harness.process(&[instruction], &[&stranger]).await),
+        Some(u32::from(anchor_lang::error::ErrorCode::ConstraintSeeds))
+    );
+
+    let maker_before = harness.account(&maker.pubkey()).await.unwrap().lamports;
+    let instruction = harness.migrate_offer_ix(&maker.pubkey(), &offer, ID);
+    harness.execute(&[instruction], &[&maker]).await;
+
+    let rent = Rent::default();
+    let paid = maker_before - harness.account(&maker.pubkey()).await.unwrap().lamports;
+    assert_eq!(
+        paid,
+        rent.minimum_balance(Offer::SPACE) - rent.minimum_balance(Offer::V0_SPACE)
+    );
+    let account = harness.account(&offer).await.unwrap();
+    assert_eq!(account.lamports, rent.minimum_balance(Offer::SPACE));
+    assert_eq!(Offer::layout_of(&account.data).unwrap(), OfferLayout::V1);
+    let migrated = Offer::try_from_any_layout(&account.data).unwrap();
+    assert_eq!(migrated.version, OFFER_VERSION);
+    assert_eq!(migrated.maker, maker.pubkey());
+    assert_eq!(migrated.vesting, None);
+    let mut expected = Vec::new();
+    Offer::from(legacy).try_serialize(&mut expected).unwrap();
+    expected.resize(Offer::SPACE, 0);
+    assert_eq!(account.data, expected);
+
+    let instruction = harness.migrate_offer_ix(&maker.pubkey(), &offer, ID);
+    assert_eq!(
+        custom_error(harness.process(&[instruction], &[&maker]).await),
+        Some(u32::from(ErrorCode::OfferAlreadyMigrated))
+    );
+}
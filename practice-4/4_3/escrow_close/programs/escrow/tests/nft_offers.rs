@@ -0,0 +1,278 @@
+//! NFT offers: `make_offer` only accepts real NFTs and verified collection
+//! members, `take_offer` pays the creators their royalties out of the price.
+//!
+//! The Metaplex metadata accounts are written straight into the bank, so the
+//! token metadata program itself is not needed.
+
+mod common;
+
+use anchor_spl::metadata::mpl_token_metadata::types::Collection;
+use common::{custom_error, metadata_address, nft_metadata, Harness, TOKEN_PROGRAMS};
+use escrow::{error::ErrorCode, NftSide, NftTerms};
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const PRICE: u64 = 1_000;
+
+async fn make(harness: &mut Harness, maker: &Keypair, instruction: Instruction) -> Option<u32> {
+    custom_error(harness.process(&[instruction], &[maker]).await)
+}
+
+#[tokio::test]
+async fn only_nfts_make_nft_offers() {
+    let mut harness = Harness::start_native(TOKEN_PROGRAMS[0].1).await;
+    let maker = harness.create_user().await;
+    let fungible = harness.create_mint(6).await;
+    let usdc = harness.create_mint(6).await;
+    harness.mint_to(&fungible, &maker.pubkey(), 1).await;
+    let nft = NftTerms {
+        side: NftSide::TokenA,
+        collection: None,
+    };
+
+    let instruction =
+        harness.make_nft_offer_ix(&maker.pubkey(), 1, (&fungible, 1), (&usdc, PRICE), nft);
+    assert_eq!(
+        make(&mut harness, &maker, instruction).await,
+        Some(u32::from(ErrorCode::NotAnNft))
+    );
+
+    // A second token of the mint makes it fungible again
+    let editions = harness.mint_nft(&maker.pubkey()).await;
+    harness.mint_to(&editions, &maker.pubkey(), 1).await;
+    let instruction =
+        harness.make_nft_offer_ix(&maker.pubkey(), 2, (&editions, 1), (&usdc, PRICE), nft);
+    assert_eq!(
+        make(&mut harness, &maker, instruction).await,
+        Some(u32::from(ErrorCode::NotAnNft))
+    );
+
+    let single = harness.mint_nft(&maker.pubkey()).await;
+    let instruction =
+        harness.make_nft_offer_ix(&maker.pubkey(), 3, (&single, 1), (&usdc, PRICE), nft);
+    assert_eq!(make(&mut harness, &maker, instruction).await, None);
+}
+
+#[tokio::test]
+async fn collection_offers_need_a_verified_member() {
+    let mut harness = Harness::start_native(TOKEN_PROGRAMS[0].1).await;
+    let maker = harness.create_user().await;
+    let usdc = harness.create_mint(6).await;
+    let collection = Pubkey::new_unique();
+    let nft = NftTerms {
+        side: NftSide::TokenA,
+        collection: Some(collection),
+    };
+
+    let unverified = harness.mint_nft(&maker.pubkey()).await;
+    harness.set_metadata(&nft_metadata(
+        unverified,
+        &[],
+        Some(Collection {
+            verified: false,
+            key: collection,
+        }),
+    ));
+    let instruction =
+        harness.make_nft_offer_ix(&maker.pubkey(), 1, (&unverified, 1), (&usdc, PRICE), nft);
+    assert_eq!(
+        make(&mut harness, &maker, instruction).await,
+        Some(u32::from(ErrorCode::NotInCollection))
+    );
+
+    let missing = harness.mint_nft(&maker.pubkey()).await;
+    let instruction =
+        harness.make_nft_offer_ix(&maker.pubkey(), 2, (&missing, 1), (&usdc, PRICE), nft);
+    assert_eq!(
+        make(&mut harness, &maker, instruction).await,
+        Some(u32::from(ErrorCode::NotInCollection))
+    );
+
+    let member = harness.mint_nft(&maker.pubkey()).await;
+    harness.set_metadata(&nft_metadata(
+        member,
+        &[],
+        Some(Collection {
+            verified: true,
+            key: collection,
+        }),
+    ));
+    let instruction =
+        harness.make_nft_offer_ix(&maker.pubkey(), 3, (&member, 1), (&usdc, PRICE), nft);
+    assert_eq!(make(&mut harness, &maker, instruction).await, None);
+}
+
+#[tokio::test]
+async fn nfts_with_metadata_need_nft_terms() {
+    let mut harness = Harness::start_native(TOKEN_PROGRAMS[0].1).await;
+    let maker = harness.create_user().await;
+    let usdc = harness.create_mint(6).await;
+    let nft = harness.mint_nft(&maker.pubkey()).await;
+    harness.set_metadata(&nft_metadata(nft, &[(Pubkey::new_unique(), 100)], None));
+
+    // Without its metadata account the escrow can't tell the NFT has none
+    let instruction = harness.make_offer_ix(&maker.pubkey(), 1, &nft, 1, &usdc, PRICE, None);
+    assert_eq!(
+        make(&mut harness, &maker, instruction).await,
+        Some(u32::from(ErrorCode::NftMetadataRequired))
+    );
+
+    // `nft_metadata` is the last account of `make_offer`
+    let mut instruction = harness.make_offer_ix(&maker.pubkey(), 1, &nft, 1, &usdc, PRICE, None);
+    instruction.accounts.last_mut().unwrap().pubkey = metadata_address(&nft);
+    assert_eq!(
+        make(&mut harness, &maker, instruction).await,
+        Some(u32::from(ErrorCode::NftTermsRequired))
+    );
+
+    // A mint that never got metadata owes no royalties
+    let bare = harness.mint_nft(&maker.pubkey()).await;
+    let mut instruction = harness.make_offer_ix(&maker.pubkey(), 2, &bare, 1, &usdc, PRICE, None);
+    instruction.accounts.last_mut().unwrap().pubkey = metadata_address(&bare);
+    assert_eq!(make(&mut harness, &maker, instruction).await, None);
+}
+
+#[tokio::test]
+async fn nfts_are_not_swapped_for_nfts() {
+    let mut harness = Harness::start_native(TOKEN_PROGRAMS[0].1).await;
+    let maker = harness.create_user().await;
+    let offered = harness.mint_nft(&maker.pubkey()).await;
+    let wanted = harness.mint_nft(&Pubkey::new_unique()).await;
+
+    for side in [NftSide::TokenA, NftSide::TokenB] {
+        let nft = NftTerms {
+            side,
+            collection: None,
+        };
+        let instruction =
+            harness.make_nft_offer_ix(&maker.pubkey(), 1, (&offered, 1), (&wanted, 1), nft);
+        assert_eq!(
+            make(&mut harness, &maker, instruction).await,
+            Some(u32::from(ErrorCode::NftForNftSwap))
+        );
+    }
+
+    // Without terms there would be no metadata account for the second NFT
+    let mut instruction = harness.make_offer_ix(&maker.pubkey(), 1, &offered, 1, &wanted, 1, None);
+    instruction.accounts.last_mut().unwrap().pubkey = metadata_address(&offered);
+    assert_eq!(
+        make(&mut harness, &maker, instruction).await,
+        Some(u32::from(ErrorCode::NftForNftSwap))
+    );
+}
+
+#[tokio::test]
+async fn selling_an_nft_pays_royalties_out_of_the_price() {
+    for (name, token_program) in TOKEN_PROGRAMS {
+        let mut harness = Harness::start_native(token_program).await;
+        let (maker, taker) = harness.create_maker_and_taker().await;
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let usdc = harness.create_mint(6).await;
+        harness.mint_to(&usdc, &taker.pubkey(), PRICE).await;
+        let royalty_accounts = [
+            harness.mint_to(&usdc, &first, 0).await,
+            harness.mint_to(&usdc, &second, 0).await,
+        ];
+        let nft = harness.mint_nft(&maker.pubkey()).await;
+        harness.set_metadata(&nft_metadata(nft, &[(first, 70), (second, 30)], None));
+
+        let terms = NftTerms {
+            side: NftSide::TokenA,
+            collection: None,
+        };
+        let instruction =
+            harness.make_nft_offer_ix(&maker.pubkey(), 1, (&nft, 1), (&usdc, PRICE), terms);
+        harness.execute(&[instruction], &[&maker]).await;
+
+        // Skipping the creators is not an option
+        let instruction =
+            harness.take_nft_offer_ix(&taker.pubkey(), &maker.pubkey(), 1, &nft, &usdc, &nft, &[]);
+        assert_eq!(
+            custom_error(harness.process(&[instruction], &[&taker]).await),
+            Some(u32::from(ErrorCode::InvalidRoyaltyAccount)),
+            "{name}"
+        );
+
+        let instruction = harness.take_nft_offer_ix(
+            &taker.pubkey(),
+            &maker.pubkey(),
+            1,
+            &nft,
+            &usdc,
+            &nft,
+            &royalty_accounts,
+        );
+        harness.execute(&[instruction], &[&taker]).await;
+
+        let taker_nft = harness.ata(&nft, &taker.pubkey());
+        assert_eq!(harness.token_balance(&taker_nft).await, 1, "{name}");
+        let maker_usdc = harness.ata(&usdc, &maker.pubkey());
+        assert_eq!(harness.token_balance(&maker_usdc).await, 950, "{name}");
+        assert_eq!(
+            harness.token_balance(&royalty_accounts[0]).await,
+            35,
+            "{name}"
+        );
+        assert_eq!(
+            harness.token_balance(&royalty_accounts[1]).await,
+            15,
+            "{name}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn buying_an_nft_pays_royalties_out_of_the_vault() {
+    let mut harness = Harness::start_native(TOKEN_PROGRAMS[0].1).await;
+    let (maker, taker) = harness.create_maker_and_taker().await;
+    let creator = Pubkey::new_unique();
+    let usdc = harness.create_mint(6).await;
+    harness.mint_to(&usdc, &maker.pubkey(), PRICE).await;
+    let royalty_account = harness.mint_to(&usdc, &creator, 0).await;
+    let nft = harness.mint_nft(&taker.pubkey()).await;
+    harness.set_metadata(&nft_metadata(nft, &[(creator, 100)], None));
+
+    let terms = NftTerms {
+        side: NftSide::TokenB,
+        collection: None,
+    };
+    let instruction =
+        harness.make_nft_offer_ix(&maker.pubkey(), 1, (&usdc, PRICE), (&nft, 1), terms);
+    harness.execute(&[instruction], &[&maker]).await;
+
+    // Royalties go to the creators' accounts for the token paying for the NFT
+    let wrong_mint = harness.mint_to(&nft, &creator, 0).await;
+    let instruction = harness.take_nft_offer_ix(
+        &taker.pubkey(),
+        &maker.pubkey(),
+        1,
+        &usdc,
+        &nft,
+        &nft,
+        &[wrong_mint],
+    );
+    assert_eq!(
+        custom_error(harness.process(&[instruction], &[&taker]).await),
+        Some(u32::from(ErrorCode::InvalidRoyaltyAccount))
+    );
+
+    let instruction = harness.take_nft_offer_ix(
+        &taker.pubkey(),
+        &maker.pubkey(),
+        1,
+        &usdc,
+        &nft,
+        &nft,
+        &[royalty_account],
+    );
+    harness.execute(&[instruction], &[&taker]).await;
+
+    let maker_nft = harness.ata(&nft, &maker.pubkey());
+    assert_eq!(harness.token_balance(&maker_nft).await, 1);
+    let taker_usdc = harness.ata(&usdc, &taker.pubkey());
+    assert_eq!(harness.token_balance(&taker_usdc).await, 950);
+    assert_eq!(harness.token_balance(&royalty_account).await, 50);
+}
@@ -0,0 +1,209 @@
+//! Vested offers: `take_offer_vested` locks token A in a vesting vault the
+//! taker drains with `claim_vested` as the bank's clock, which the tests
+//! warp, moves past the cliff.
+
+mod common;
+
+use anchor_spl::associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use common::{custom_error, vesting_address, OfferSetup, TOKEN_PROGRAMS};
+use escrow::{error::ErrorCode, VestingSchedule};
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+
+const OFFERED: u64 = 1_000;
+const WANTED: u64 = 500;
+const SCHEDULE: VestingSchedule = VestingSchedule {
+    cliff_seconds: 100,
+    duration_seconds: 400,
+};
+
+struct Vested {
+    offer: OfferSetup,
+    vesting: Pubkey,
+    start_ts: i64,
+}
+
+/// `OFFERED` token A for `WANTED` token B vesting by `schedule`, made as
+/// offer 1.
+async fn start(token_program: Pubkey, schedule: VestingSchedule) -> Vested {
+    let mut offer = OfferSetup::start(token_program, OFFERED, WANTED).await;
+    let instruction = offer.harness.make_offer_ix(
+        &offer.maker.pubkey(),
+        1,
+        &offer.mint_a,
+        OFFERED,
+        &offer.mint_b,
+        WANTED,
+        Some(schedule),
+    );
+    offer.harness.execute(&[instruction], &[&offer.maker]).await;
+
+    let vesting = vesting_address(&offer.offer(1), &offer.taker.pubkey());
+    let start_ts = offer.harness.now().await + 60;
+    Vested {
+        offer,
+        vesting,
+        start_ts,
+    }
+}
+
+impl Vested {
+    /// Takes the offer at `start_ts`.
+    async fn take(&mut self) -> Option<u32> {
+        self.offer.harness.warp_to(self.start_ts).await;
+        let instruction = self.offer.harness.take_offer_vested_ix(
+            &self.offer.taker.pubkey(),
+            &self.offer.maker.pubkey(),
+            1,
+            &self.offer.mint_a,
+            &self.offer.mint_b,
+        );
+        custom_error(
+            self.offer
+                .harness
+                .process(&[instruction], &[&self.offer.taker])
+                .await,
+        )
+    }
+
+    /// Claims `seconds` after the offer was taken and returns the error code,
+    /// if the claim failed.
+    async fn claim_at(&mut self, seconds: i64) -> Option<u32> {
+        self.offer.harness.warp_to(self.start_ts + seconds).await;
+        let instruction = self.offer.harness.claim_vested_ix(
+            &self.offer.taker.pubkey(),
+            &self.vesting,
+            &self.offer.mint_a,
+        );
+        custom_error(
+            self.offer
+                .harness
+                .process(&[instruction], &[&self.offer.taker])
+                .await,
+        )
+    }
+
+    async fn claimed(&mut self) -> u64 {
+        let taker_a = self
+            .offer
+            .harness
+            .ata(&self.offer.mint_a, &self.offer.taker.pubkey());
+        self.offer.harness.token_balance(&taker_a).await
+    }
+}
+
+#[tokio::test]
+async fn claims_follow_the_schedule() {
+    for (name, token_program) in TOKEN_PROGRAMS {
+        let mut vested = start(token_program, SCHEDULE).await;
+        assert_eq!(vested.take().await, None, "{name}");
+        let maker_b = vested
+            .offer
+            .harness
+            .ata(&vested.offer.mint_b, &vested.offer.maker.pubkey());
+        assert_eq!(
+            vested.offer.harness.token_balance(&maker_b).await,
+            WANTED,
+            "{name}"
+        );
+
+        // Nothing before the cliff
+        assert_eq!(
+            vested.claim_at(99).await,
+            Some(u32::from(ErrorCode::NothingToClaim)),
+            "{name}"
+        );
+
+        // A quarter of the way through, then again without time passing
+        assert_eq!(vested.claim_at(100).await, None, "{name}");
+        assert_eq!(vested.claimed().await, 250, "{name}");
+        assert_eq!(
+            vested.claim_at(100).await,
+            Some(u32::from(ErrorCode::NothingToClaim)),
+            "{name}"
+        );
+
+        // Only what accrued since the last claim
+        assert_eq!(vested.claim_at(300).await, None, "{name}");
+        assert_eq!(vested.claimed().await, 750, "{name}");
+
+        // The last claim empties and closes the vesting accounts
+        assert_eq!(vested.claim_at(1_000).await, None, "{name}");
+        assert_eq!(vested.claimed().await, OFFERED, "{name}");
+        let vesting_vault = vested
+            .offer
+            .harness
+            .ata(&vested.offer.mint_a, &vested.vesting);
+        assert!(
+            vested.offer.harness.account(&vesting_vault).await.is_none(),
+            "{name}"
+        );
+        assert!(
+            vested
+                .offer
+                .harness
+                .account(&vested.vesting)
+                .await
+                .is_none(),
+            "{name}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn only_the_taker_claims() {
+    let mut vested = start(TOKEN_PROGRAMS[0].1, SCHEDULE).await;
+    assert_eq!(vested.take().await, None);
+    vested.offer.harness.warp_to(vested.start_ts + 200).await;
+
+    let maker = &vested.offer.maker;
+    let instruction = vested.offer.harness.claim_vested_ix(
+        &maker.pubkey(),
+        &vested.vesting,
+        &vested.offer.mint_a,
+    );
+    let result = vested.offer.harness.process(&[instruction], &[maker]).await;
+    assert!(result.unwrap().result.is_err());
+    assert_eq!(vested.claimed().await, 0);
+}
+
+#[tokio::test]
+async fn takes_when_someone_created_the_vesting_vault_first() {
+    let mut vested = start(TOKEN_PROGRAMS[0].1, SCHEDULE).await;
+    let stranger = vested.offer.harness.create_user().await;
+    let instruction = create_associated_token_account_idempotent(
+        &stranger.pubkey(),
+        &vested.vesting,
+        &vested.offer.mint_a,
+        &vested.offer.harness.token_program,
+    );
+    vested
+        .offer
+        .harness
+        .execute(&[instruction], &[&stranger])
+        .await;
+
+    assert_eq!(vested.take().await, None);
+    let vesting_vault = vested
+        .offer
+        .harness
+        .ata(&vested.offer.mint_a, &vested.vesting);
+    assert_eq!(
+        vested.offer.harness.token_balance(&vesting_vault).await,
+        OFFERED
+    );
+    assert_eq!(vested.claim_at(1_000).await, None);
+    assert_eq!(vested.claimed().await, OFFERED);
+}
+
+#[tokio::test]
+async fn schedules_ending_past_the_clock_range_are_rejected() {
+    let schedule = VestingSchedule {
+        cliff_seconds: 0,
+        duration_seconds: i64::MAX,
+    };
+    let mut vested = start(TOKEN_PROGRAMS[0].1, schedule).await;
+    assert_eq!(
+        vested.take().await,
+        Some(u32::from(ErrorCode::InvalidVestingSchedule))
+    );
+}
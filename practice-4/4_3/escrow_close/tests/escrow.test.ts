@@ -175,10 +175,11 @@ describe("escrow", () => {
     offeredMint: PublicKey,
     offeredAmount: BN,
     wantedMint: PublicKey,
-    wantedAmount: BN
+    wantedAmount: BN,
+    vesting: { cliffSeconds: BN; durationSeconds: BN } | null = null
   ): Promise<{ offerAddress: PublicKey; vaultAddress: PublicKey }> => {
     const sig = await program.methods
-      .makeOffer(offerId, offeredAmount, wantedAmount)
+      .makeOffer(offerId, offeredAmount, wantedAmount, vesting, null, null)
       .accounts({
         maker: maker.publicKey,
         tokenMintA: offeredMint,
@@ -235,6 +236,50 @@ describe("escrow", () => {
     await confirmTransaction(connection, transactionSignature);
   };
 
+  const takeOfferVestedTx = async (
+    offerAddress: PublicKey,
+    taker: Keypair
+  ): Promise<PublicKey> => {
+    const sig = await program.methods
+      .takeOfferVested()
+      .accounts({
+        taker: taker.publicKey,
+        offer: offerAddress,
+        tokenProgram: TOKEN_PROGRAM,
+      })
+      .signers([taker])
+      .rpc();
+    await confirmTransaction(connection, sig);
+
+    const [vestingAddress] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("vesting"),
+        offerAddress.toBuffer(),
+        taker.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+    return vestingAddress;
+  };
+
+  const claimVestedTx = async (
+    beneficiary: Keypair,
+    vestingAddress: PublicKey,
+    tokenMint: PublicKey
+  ): Promise<void> => {
+    const sig = await program.methods
+      .claimVested()
+      .accounts({
+        beneficiary: beneficiary.publicKey,
+        vesting: vestingAddress,
+        tokenMint,
+        tokenProgram: TOKEN_PROGRAM,
+      })
+      .signers([beneficiary])
+      .rpc();
+    await confirmTransaction(connection, sig);
+  };
+
   test("Offer created by Alice, vault holds the offer tokens", async () => {
     const offeredUsdc = new BN(10_000_000);
     const wantedWif = new BN(100_000_000);
@@ -255,6 +300,7 @@ describe("escrow", () => {
     expect(await getTokenBalance(vaultAddress)).toEqual(offeredUsdc);
 
     const offerAccount = await program.account.offer.fetch(offerAddress);
+    expect(offerAccount.version).toEqual(1);
     expect(offerAccount.maker).toEqual(alice.publicKey);
     expect(offerAccount.tokenMintA).toEqual(usdcMint.publicKey);
     expect(offerAccount.tokenMintB).toEqual(wifMint.publicKey);
@@ -297,4 +343,55 @@ describe("escrow", () => {
     const offerInfo = await connection.getAccountInfo(offerAddress);
     expect(offerInfo).toBeNull();
   });
+
+  test("Vested offer locks token A for Bob until it unlocks", async () => {
+    const getTokenBalance = getTokenBalanceOn(connection);
+    const vestedOfferId = getRandomBigNumber();
+    const offeredUsdc = new BN(10_000_000);
+    const wantedWif = new BN(50_000_000);
+
+    const { offerAddress, vaultAddress } = await makeOfferTx(
+      alice,
+      vestedOfferId,
+      usdcMint.publicKey,
+      offeredUsdc,
+      wifMint.publicKey,
+      wantedWif,
+      { cliffSeconds: new BN(0), durationSeconds: new BN(2) }
+    );
+
+    await expect(takeOfferTx(offerAddress, bob)).rejects.toThrow(
+      "OfferIsVested"
+    );
+
+    const vestingAddress = await takeOfferVestedTx(offerAddress, bob);
+    const vestingVault = getAssociatedTokenAddressSync(
+      usdcMint.publicKey,
+      vestingAddress,
+      true,
+      TOKEN_PROGRAM
+    );
+
+    expect(await connection.getAccountInfo(vaultAddress)).toBeNull();
+    expect(await connection.getAccountInfo(offerAddress)).toBeNull();
+    expect(await getTokenBalance(vestingVault)).toEqual(offeredUsdc);
+    expect(await getTokenBalance(bobUsdcAccount)).toEqual(new BN(20_000_000));
+    expect(await getTokenBalance(aliceWifAccount)).toEqual(
+      new BN(5_000_000).add(wantedWif)
+    );
+
+    const vesting = await program.account.vesting.fetch(vestingAddress);
+    expect(vesting.beneficiary).toEqual(bob.publicKey);
+    expect(vesting.totalAmount).toEqual(offeredUsdc);
+    expect(vesting.endTs.sub(vesting.startTs)).toEqual(new BN(2));
+
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+    await claimVestedTx(bob, vestingAddress, usdcMint.publicKey);
+
+    expect(await getTokenBalance(bobUsdcAccount)).toEqual(
+      new BN(20_000_000).add(offeredUsdc)
+    );
+    expect(await connection.getAccountInfo(vestingVault)).toBeNull();
+    expect(await connection.getAccountInfo(vestingAddress)).toBeNull();
+  });
 });